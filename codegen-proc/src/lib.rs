@@ -1,20 +1,69 @@
 use std::env;
-use std::fs;
 use std::path::PathBuf;
-use syn::{parse_macro_input, LitStr};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, LitInt, LitStr, Token};
 use feather_protocol_codegen::*;
 
 #[proc_macro]
 pub fn protocol(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let protocol = parse_macro_input!(input as LitStr).value();
-    let path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is unset"));
-    let path = path.join(protocol);
+    let minecraft = load(&protocol);
+
+    let protocol = ProtocolGenerator::generate_minecraft(minecraft);
+
+    protocol.into()
+}
+
+/// Generates a module for each of the given protocol versions of a spec covering several,
+/// as in `protocol_versions!("protocols/combined.ron", 498, 578)`.
+#[proc_macro]
+pub fn protocol_versions(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let arguments = Punctuated::<syn::Lit, Token![,]>::parse_terminated
+        .parse(input)
+        .unwrap_or_else(|error| panic!("{}", error));
+    let mut arguments = arguments.into_iter();
+    let protocol = match arguments.next() {
+        Some(syn::Lit::Str(protocol)) => protocol.value(),
+        _ => panic!("expected the path of a spec followed by protocol versions"),
+    };
+    let versions: Vec<ProtocolVersion> = arguments
+        .map(|version| match version {
+            syn::Lit::Int(version) => version_of(&version),
+            _ => panic!("expected a protocol version"),
+        })
+        .collect();
+    let minecraft = load(&protocol);
 
-    let file = fs::File::open(path).unwrap();
+    let protocol = ProtocolGenerator::generate_versions(minecraft.protocol, &versions);
+
+    protocol.into()
+}
 
-    let protocol_de: Protocol = ron::de::from_reader(&file).unwrap();
+/// Generates every version of a spec covering several at once, see
+/// [`ProtocolGenerator::generate_versioned`].
+#[proc_macro]
+pub fn versioned_protocol(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let protocol = parse_macro_input!(input as LitStr).value();
+    let minecraft = load(&protocol);
 
-    let protocol = ProtocolGenerator::generate(protocol_de);
+    let protocol = ProtocolGenerator::generate_versioned(minecraft.protocol);
 
     protocol.into()
 }
+
+/// Loads a spec relative to the crate using the macro.
+fn load(protocol: &str) -> Minecraft {
+    let path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is unset"));
+    let path = path.join(protocol);
+
+    // The spec may be a patch on top of another spec, which is resolved here.
+    Minecraft::load(&path).unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn version_of(version: &LitInt) -> ProtocolVersion {
+    version
+        .base10_parse::<u64>()
+        .unwrap_or_else(|error| panic!("{}", error))
+        .into()
+}
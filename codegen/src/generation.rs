@@ -1,19 +1,84 @@
-use proc_macro2::{Ident, Span, TokenStream};
+use proc_macro2::{Ident, Literal as LiteralToken, Span, TokenStream};
 use quote::{quote, ToTokens};
-use syn::LitStr;
 
 use indexmap::IndexMap;
 use std::collections::BTreeMap;
 
 use feather_protocol_spec::{
-    ArrayLength, BitField, CustomType, FieldName, FieldType, FlagName, FloatType, IntegerType,
-    Literal, PacketDirection, PacketIdentifier, PacketStage, PrimitiveType, Protocol,
+    ArrayLength, BitField, Condition, CustomType, FieldName, FieldType, FlagName, FloatType,
+    IntegerType, Literal, Minecraft, PacketDirection, PacketIdentifier, PacketStage,
+    PrimitiveType, Protocol, ProtocolVersion, VersionRange,
 };
 
 pub struct ProtocolGenerator;
 
 impl ProtocolGenerator {
+    /// Generates the protocol of a spec file along with `Protocol`, a marker type
+    /// implementing `crate::packet::Protocol` for it. A spec covering several protocol
+    /// versions is generated for the version it names.
+    pub fn generate_minecraft(minecraft: Minecraft) -> TokenStream {
+        let version = LiteralToken::u64_unsuffixed(*minecraft.protocol.version);
+        let minecraft_version = &*minecraft.version;
+        let minecraft_major_version = &*minecraft.major_version;
+        let protocol = Self::generate(minecraft.protocol);
+
+        quote! {
+            #protocol
+
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct Protocol;
+
+            impl crate::packet::Protocol for Protocol {
+                fn version() -> u64 {
+                    #version
+                }
+
+                fn minecraft_version() -> &'static str {
+                    #minecraft_version
+                }
+
+                fn minecraft_major_version() -> &'static str {
+                    #minecraft_major_version
+                }
+            }
+        }
+    }
+
+    /// Generates a protocol for a single version, see [`Protocol::project`].
     pub fn generate(protocol: Protocol) -> TokenStream {
+        Self::generate_with(protocol.project(protocol.version), false, quote! {})
+    }
+
+    /// Generates a module named after each of `versions`, holding the protocol as it is
+    /// in that version along with its number as `VERSION`. The `protocol` and `shared`
+    /// modules in them are public, so that they can be reached from outside.
+    pub fn generate_versions(protocol: Protocol, versions: &[ProtocolVersion]) -> TokenStream {
+        let modules = versions.iter().map(|version| {
+            let module = Ident::new(&format!("v{}", **version), Span::call_site());
+            let number = LiteralToken::u64_unsuffixed(**version);
+            let protocol = Self::generate_with(protocol.project(*version), false, quote! { pub });
+            quote! {
+                pub mod #module {
+                    pub const VERSION: u64 = #number;
+
+                    #protocol
+                }
+            }
+        });
+        quote! { #(#modules)* }
+    }
+
+    /// Generates a protocol covering every version of a spec.
+    ///
+    /// Types are read and written through `try_read_versioned` and `write_versioned`, which
+    /// take the protocol version, instead of `TryReadFrom` and `WriteInto`. Struct fields
+    /// which are not in every version are optional, and the `Packet` enum of each stage
+    /// reads a packet by its ID in the given version.
+    pub fn generate_versioned(protocol: Protocol) -> TokenStream {
+        Self::generate_with(protocol, true, quote! {})
+    }
+
+    fn generate_with(protocol: Protocol, versioned: bool, visibility: TokenStream) -> TokenStream {
         let mut packets = protocol.packets;
         let server_bound_packets = packets.split_off(&PacketIdentifier(
             PacketDirection::Server,
@@ -27,24 +92,28 @@ impl ProtocolGenerator {
             0.into(),
         ));
 
+        let scope = KeyScope::top_level(versioned);
         let (client, _, _) =
-            DirectionGenerator::generate(PacketDirection::Server, server_bound_packets);
+            DirectionGenerator::generate(PacketDirection::Server, server_bound_packets, &scope);
         let (server, _, _) =
-            DirectionGenerator::generate(PacketDirection::Client, client_bound_packets);
+            DirectionGenerator::generate(PacketDirection::Client, client_bound_packets, &scope);
 
-        let (shared_types_tokens, _shared_types_idents): (Vec<_>, Vec<_>) = protocol
+        let (_shared_types_idents, shared_types_tokens): (Vec<_>, Vec<_>) = protocol
             .shared_types
             .iter()
-            .map(CustomTypeGenerator::generate)
+            .map(|shared_type| CustomTypeGenerator::generate(shared_type, &scope))
             .unzip();
 
         quote! {
-            mod protocol {
+            #visibility mod protocol {
                 #client
                 #server
             }
 
-            mod shared {
+            #visibility mod shared {
+                #[allow(unused_imports)]
+                use super::shared;
+
                 #(#shared_types_tokens)*
             }
         }
@@ -57,6 +126,7 @@ impl DirectionGenerator {
     fn generate(
         direction: PacketDirection,
         mut packets: BTreeMap<PacketIdentifier, CustomType>,
+        scope: &KeyScope,
     ) -> (TokenStream, (Ident, Ident, Ident, Ident), Ident) {
         let direction_ident = Self::ident(direction);
 
@@ -75,14 +145,18 @@ impl DirectionGenerator {
             0.into(),
         ));
 
-        let (handshaking_ident, handshaking_tokens) =
-            StageGenerator::generate(direction, PacketStage::Handshaking, handshaking_packets);
+        let (handshaking_ident, handshaking_tokens) = StageGenerator::generate(
+            direction,
+            PacketStage::Handshaking,
+            handshaking_packets,
+            scope,
+        );
         let (status_ident, status_tokens) =
-            StageGenerator::generate(direction, PacketStage::Status, login_packets);
+            StageGenerator::generate(direction, PacketStage::Status, status_packets, scope);
         let (login_ident, login_tokens) =
-            StageGenerator::generate(direction, PacketStage::Login, status_packets);
+            StageGenerator::generate(direction, PacketStage::Login, login_packets, scope);
         let (play_ident, play_tokens) =
-            StageGenerator::generate(direction, PacketStage::Play, play_packets);
+            StageGenerator::generate(direction, PacketStage::Play, play_packets, scope);
 
         (
             quote! {
@@ -113,13 +187,21 @@ impl StageGenerator {
         _direction: PacketDirection,
         stage: PacketStage,
         packets: BTreeMap<PacketIdentifier, CustomType>,
+        scope: &KeyScope,
     ) -> (Ident, TokenStream) {
         let stage_ident = Self::ident(stage);
 
-        let (packet_idents, packet_tokens): (Vec<_>, Vec<_>) =
-            packets.iter().map(PacketGenerator::generate).unzip();
+        let (packet_idents, packet_tokens): (Vec<_>, Vec<_>) = packets
+            .iter()
+            .map(|packet| PacketGenerator::generate(packet, scope))
+            .unzip();
 
         let _packet_ids: Vec<_> = packets.iter().map(|(i, _)| *i.id()).collect();
+        let versioned_impls = if scope.versioned {
+            Self::versioned_impls(&packets, &packet_idents)
+        } else {
+            quote! {}
+        };
 
         let tokens = quote! {
             pub mod #stage_ident {
@@ -135,6 +217,8 @@ impl StageGenerator {
                     }
                 )*
 
+                #versioned_impls
+
                 #(#packet_tokens)*
             }
         };
@@ -142,6 +226,107 @@ impl StageGenerator {
         (stage_ident, tokens)
     }
 
+    /// Generates the ID of each packet in a given version, and reading and writing `Packet`
+    /// by those IDs. A packet is left out of the versions it is not in, and the packets after
+    /// it move up to close its ID there.
+    fn versioned_impls(
+        packets: &BTreeMap<PacketIdentifier, CustomType>,
+        packet_idents: &[Ident],
+    ) -> TokenStream {
+        // The IDs only change where a packet comes or goes.
+        let mut starts = vec![0];
+        for packet in packets.values() {
+            let versions = packet.versions();
+            starts.extend(versions.since.map(|since| *since));
+            starts.extend(versions.until.and_then(|until| until.checked_add(1)));
+        }
+        starts.sort_unstable();
+        starts.dedup();
+
+        // The versions each packet is in, with its ID in them.
+        let mut ids: Vec<Vec<(u64, u64, u64)>> = vec![Vec::new(); packets.len()];
+        for (index, start) in starts.iter().enumerate() {
+            let end = starts.get(index + 1).map_or(u64::MAX, |next| next - 1);
+            let mut left_out = 0;
+            for ((identifier, packet), ids) in packets.iter().zip(ids.iter_mut()) {
+                if !packet.versions().contains((*start).into()) {
+                    left_out += 1;
+                    continue;
+                }
+                let id = *identifier.id() - left_out;
+                match ids.last_mut() {
+                    Some((_, last_end, last_id)) if *last_end + 1 == *start && *last_id == id => {
+                        *last_end = end;
+                    }
+                    _ => ids.push((*start, end, id)),
+                }
+            }
+        }
+
+        let id_bodies = ids.iter().map(|ids| match ids.as_slice() {
+            [(0, u64::MAX, id)] => {
+                let id = LiteralToken::u64_unsuffixed(*id);
+                quote! { Some(#id) }
+            }
+            ids => {
+                let arms = ids.iter().map(|(start, end, id)| {
+                    let start = LiteralToken::u64_unsuffixed(*start);
+                    let end = LiteralToken::u64_unsuffixed(*end);
+                    let id = LiteralToken::u64_unsuffixed(*id);
+                    quote! { #start..=#end => Some(#id), }
+                });
+                quote! {
+                    match version {
+                        #(#arms)*
+                        _ => None,
+                    }
+                }
+            }
+        });
+
+        quote! {
+            #(
+                impl #packet_idents {
+                    /// The ID of this packet in a protocol version, if the version has it.
+                    pub fn id(version: u64) -> Option<u64> {
+                        #id_bodies
+                    }
+                }
+            )*
+
+            impl Packet {
+                pub fn id(&self, version: u64) -> Option<u64> {
+                    match *self {
+                        #(Packet::#packet_idents(_) => #packet_idents::id(version),)*
+                    }
+                }
+
+                #[allow(unused_variables)]
+                pub fn try_read_versioned(
+                    buf: &mut impl bytes::Buf,
+                    version: u64,
+                    id: u64,
+                ) -> Result<Self, crate::types::Error> {
+                    #(
+                        if #packet_idents::id(version) == Some(id) {
+                            return Ok(Packet::#packet_idents(
+                                #packet_idents::try_read_versioned(buf, version)?
+                            ));
+                        }
+                    )*
+                    Err(crate::types::Error::UnknownPacket { id })
+                }
+
+                #[allow(unused_variables)]
+                pub fn write_versioned(&self, buf: &mut impl bytes::BufMut, version: u64) -> usize {
+                    match *self {
+                        #(Packet::#packet_idents(ref packet) => packet.write_versioned(buf, version),)*
+                    }
+                }
+            }
+        }
+    }
+
     fn ident(stage: PacketStage) -> Ident {
         match stage {
             PacketStage::Handshaking => Ident::new("handshaking", Span::call_site()),
@@ -155,35 +340,29 @@ impl StageGenerator {
 pub struct PacketGenerator;
 
 impl PacketGenerator {
-    fn generate(packet: (&PacketIdentifier, &CustomType)) -> (Ident, TokenStream) {
+    fn generate(
+        packet: (&PacketIdentifier, &CustomType),
+        scope: &KeyScope,
+    ) -> (Ident, TokenStream) {
         let custom_type = packet.1;
-        let (custom_type_ident, custom_type_tokens) = CustomTypeGenerator::generate(custom_type);
+        let (custom_type_ident, custom_type_tokens) =
+            CustomTypeGenerator::generate(custom_type, scope);
 
         let packet_ident = Self::ident(custom_type.name());
 
+        // Packet modules live in `protocol::<direction>::<stage>`, next to `shared`.
         let tokens = quote! {
             pub use #packet_ident::#custom_type_ident;
-            mod #packet_ident {
+            pub mod #packet_ident {
+                #[allow(unused_imports)]
+                use super::super::super::super::shared;
+
                 #custom_type_tokens
             }
         };
         (custom_type_ident, tokens)
     }
 
-    fn generate_write(packet: (&PacketIdentifier, &CustomType)) -> TokenStream {
-        let custom_type = packet.1;
-        let ident = Self::ident(custom_type.name());
-        let custom_type_write = CustomTypeGenerator::generate_write(custom_type);
-        quote! {
-            impl TryWriteInto for #ident {
-                fn write(&self, buf: &mut impl BufMut) -> usize {
-                    let mut total = 0;
-                    total += buf.write(<Self as Packet>::ID);
-                }
-            }
-        }
-    }
-
     fn ident(name: &str) -> Ident {
         use heck::SnakeCase;
         let name = name.to_snake_case();
@@ -194,91 +373,606 @@ impl PacketGenerator {
 pub struct CustomTypeGenerator;
 
 impl CustomTypeGenerator {
-    fn generate(custom_type: &CustomType) -> (Ident, TokenStream) {
+    /// Generates `custom_type`, where `scope` holds the fields of enclosing structs.
+    fn generate(custom_type: &CustomType, scope: &KeyScope) -> (Ident, TokenStream) {
+        let keys = Self::free_keys(custom_type);
+        let scope = scope.nested();
+        let docs = doc_attributes(custom_type.doc(), custom_type.wiki());
         match custom_type {
             CustomType::Enum {
                 name,
                 variant,
                 variants,
-            } => Self::generate_enum(name, variant, variants),
-            CustomType::Struct { name, fields } => Self::generate_struct(name, fields),
-            CustomType::BitField { name, fields } => Self::generate_bit_field(name, fields),
+                ..
+            } => Self::generate_enum(name, &docs, variant, variants, &keys, &scope),
+            CustomType::Struct { name, fields, .. } => {
+                Self::generate_struct(name, &docs, fields, &keys, &scope)
+            }
+            CustomType::BitField { name, fields, .. } => {
+                Self::generate_bit_field(name, &docs, fields, &scope)
+            }
             CustomType::BitFlags {
                 name,
                 field_type,
                 flags,
-            } => Self::generate_bit_flags(name, field_type, flags),
-            CustomType::Unit { name } => Self::generate_unit(name),
+                ..
+            } => Self::generate_bit_flags(name, &docs, field_type, flags, &scope),
+            CustomType::Unit { name, .. } => Self::generate_unit(name, &docs, &scope),
         }
     }
 
     fn generate_enum(
         name: &str,
+        docs: &TokenStream,
         variant: &FieldType,
         variants: &BTreeMap<Literal, CustomType>,
+        keys: &[FieldName],
+        scope: &KeyScope,
     ) -> (Ident, TokenStream) {
         let ident = Self::ident(name);
 
-        let (variants_idents, variants_tokens): (Vec<_>, Vec<_>) =
-            variants.values().map(Self::generate).unzip();
+        let (variants_idents, variants_tokens): (Vec<_>, Vec<_>) = variants
+            .values()
+            .map(|variant| Self::generate(variant, scope))
+            .unzip();
+        let variants_docs: Vec<_> = variants
+            .values()
+            .map(|variant| doc_attributes(variant.doc(), variant.wiki()))
+            .collect();
+        let variants_reads: Vec<_> = variants
+            .values()
+            .map(|variant| {
+                let variant_ident = Self::ident(variant.name());
+                let variant_keys = Self::free_keys(variant);
+                Self::read_with(variant_ident.to_token_stream(), &variant_keys, scope)
+            })
+            .collect();
+
+        // A keyed discriminant is a field of an enclosing struct and has been read already.
+        let key = match variant {
+            FieldType::Key(key) => Some(key),
+            _ => None,
+        };
+        let discriminant = match key {
+            Some(key) => scope.key_type(key),
+            None => variant,
+        };
 
-        let (variant_ident, variant_tokens) = FieldGenerator::tokenize_field_type(variant);
+        let (variant_ident, variant_tokens) =
+            FieldGenerator::tokenize_field_type(discriminant, scope);
+
+        // Variants which are not in every version only match in the versions they are in.
+        let literals: Vec<_> = variants
+            .iter()
+            .map(|(literal, variant)| {
+                let literal = FieldGenerator::tokenize_literal(literal);
+                match variant.versions() {
+                    versions if scope.versioned && !versions.is_unbounded() => {
+                        let in_versions = FieldGenerator::in_versions(&versions);
+                        quote! { #literal if #in_versions }
+                    }
+                    _ => literal,
+                }
+            })
+            .collect();
+        let read_variant = match key {
+            Some(key) => {
+                let key = FieldGenerator::ident(key);
+                quote! { #key.to_owned() }
+            }
+            None => FieldGenerator::read_field_type(variant, scope),
+        };
+        let scrutinee = match discriminant {
+            FieldType::Primitive(PrimitiveType::String(_)) => quote! { variant.as_str() },
+            _ => quote! { variant },
+        };
+        let literal_values: Vec<_> = variants
+            .keys()
+            .map(|literal| FieldGenerator::literal_value(discriminant, literal))
+            .collect();
+
+        // A boolean discriminant with both variants defined leaves nothing for `Other`.
+        let exhaustive = discriminant == &FieldType::Primitive(PrimitiveType::Boolean)
+            && variants.contains_key(&Literal::Boolean(false))
+            && variants.contains_key(&Literal::Boolean(true))
+            && !(scope.versioned
+                && variants
+                    .values()
+                    .any(|variant| !variant.versions().is_unbounded()));
+
+        // The body of a variant with an unknown string key cannot be read, so it keeps the
+        // rest of the buffer instead.
+        let string_keyed = matches!(discriminant, FieldType::Primitive(PrimitiveType::String(_)));
+
+        let other_read = if exhaustive {
+            quote! {}
+        } else if string_keyed {
+            let rest = FieldGenerator::read_as(quote! { bytes::Bytes });
+            quote! { _ => #ident::Other(variant, #rest), }
+        } else {
+            quote! { _ => #ident::Other(variant), }
+        };
+        let other_variant = if exhaustive {
+            quote! {}
+        } else if string_keyed {
+            quote! { Other(#variant_ident, bytes::Bytes), }
+        } else {
+            quote! { Other(#variant_ident), }
+        };
+
+        let write_impl = if key.is_some() {
+            let other_write = if exhaustive {
+                quote! {}
+            } else if string_keyed {
+                quote! { #ident::Other(_, rest) => crate::types::WriteInto::write(rest, buf), }
+            } else {
+                quote! { #ident::Other(_) => 0, }
+            };
+            let write_inner = Self::write_with(quote! { inner }, scope);
+            let write_impl = Self::write_impl(
+                &ident,
+                scope,
+                quote! {
+                    match self {
+                        #(#ident::#variants_idents(inner) => #write_inner,)*
+                        #other_write
+                    }
+                },
+            );
+            let other_key = if exhaustive {
+                quote! {}
+            } else if string_keyed {
+                quote! { #ident::Other(variant, _) => variant.clone(), }
+            } else {
+                quote! { #ident::Other(variant) => variant.clone(), }
+            };
+            quote! {
+                impl #ident {
+                    /// The discriminant of this variant, which is written by the enclosing struct.
+                    pub fn key(&self) -> #variant_ident {
+                        match self {
+                            #(#ident::#variants_idents(_) => #literal_values,)*
+                            #other_key
+                        }
+                    }
+                }
+
+                #write_impl
+            }
+        } else {
+            let write_variant = FieldGenerator::write_field_type(
+                variant,
+                quote! { variant },
+                &WriteScope {
+                    versioned: scope.versioned,
+                    ..WriteScope::default()
+                },
+            );
+            let write_inner = Self::write_with(quote! { inner }, scope);
+            let other_write = if exhaustive {
+                quote! {}
+            } else if string_keyed {
+                quote! {
+                    #ident::Other(variant, rest) => {
+                        #write_variant + crate::types::WriteInto::write(rest, buf)
+                    }
+                }
+            } else {
+                quote! { #ident::Other(variant) => #write_variant, }
+            };
+            Self::write_impl(
+                &ident,
+                scope,
+                quote! {
+                    match self {
+                        #(#ident::#variants_idents(inner) => {
+                            let variant: &#variant_ident = &#literal_values;
+                            #write_variant + #write_inner
+                        })*
+                        #other_write
+                    }
+                },
+            )
+        };
+
+        let read_impl = Self::read_impl(
+            &ident,
+            keys,
+            scope,
+            quote! {
+                let variant: #variant_ident = #read_variant;
+                Ok(match #scrutinee {
+                    #(#literals => #ident::#variants_idents(#variants_reads),)*
+                    #other_read
+                })
+            },
+        );
 
         let tokens = quote! {
+            #docs
+            #[derive(Debug, Clone, PartialEq)]
             pub enum #ident {
-                #(#variants_idents(#variants_idents)),*,
-                Other(#variant_ident),
+                #(#variants_docs #variants_idents(#variants_idents),)*
+                #other_variant
             }
 
+            #read_impl
+
+            #write_impl
+
             #(#variants_tokens)*
-            #(#variant_tokens)
+            #variant_tokens
         };
         (ident, tokens)
     }
 
     fn generate_struct(
         name: &str,
+        docs: &TokenStream,
         fields: &IndexMap<FieldName, FieldType>,
+        keys: &[FieldName],
+        scope: &KeyScope,
     ) -> (Ident, TokenStream) {
         let ident = Self::ident(name);
+        let field_docs: Vec<_> = fields
+            .values()
+            .map(|field_type| doc_attributes(field_type.doc(), field_type.wiki()))
+            .collect();
+        // Fields which are only in some versions are optional when generating every version.
+        let field_versions: Vec<_> = fields
+            .values()
+            .map(|field_type| match field_type.versions() {
+                versions if scope.versioned && !versions.is_unbounded() => Some(versions),
+                _ => None,
+            })
+            .collect();
+        let field_defaults: Vec<_> = fields
+            .values()
+            .zip(field_versions.iter())
+            .map(|(field_type, versions)| {
+                field_type.default_value().map(|default| {
+                    let default = FieldGenerator::literal_value(field_type.unannotated(), default);
+                    match versions {
+                        Some(_) => quote! { Some(#default) },
+                        None => default,
+                    }
+                })
+            })
+            .collect();
+        let field_checks: Vec<_> = fields
+            .iter()
+            .zip(field_versions.iter())
+            .map(|((field_name, field_type), versions)| {
+                let ident = FieldGenerator::ident(field_name);
+                let constraints = Constraints {
+                    field: format!("{}.{}", name, **field_name),
+                    min: field_type.min(),
+                    max: field_type.max(),
+                    max_len: field_type.max_len(),
+                };
+                let field_type = match versions {
+                    Some(_) => FieldType::Primitive(PrimitiveType::Option(Box::new(
+                        field_type.unannotated().clone(),
+                    ))),
+                    None => field_type.unannotated().clone(),
+                };
+                FieldGenerator::check(&field_type, quote! { #ident }, &constraints)
+            })
+            .collect();
+        let fields: IndexMap<_, _> = fields
+            .iter()
+            .map(|(field_name, field_type)| (field_name.clone(), field_type.unannotated().clone()))
+            .collect();
+        let fields = &fields;
+        // Lengths and keys are taken from optional fields through their option.
+        let write_fields: IndexMap<_, _> = fields
+            .iter()
+            .zip(field_versions.iter())
+            .map(|((field_name, field_type), versions)| {
+                let field_type = match (versions, field_type) {
+                    (Some(_), FieldType::Const { .. }) | (None, _) => field_type.clone(),
+                    (Some(_), field_type) => {
+                        FieldType::Primitive(PrimitiveType::Option(Box::new(field_type.clone())))
+                    }
+                };
+                (field_name.clone(), field_type)
+            })
+            .collect();
+        let write_scope = WriteScope::new(&write_fields, scope.versioned);
+
+        let mut field_scope = scope.clone();
+        let mut field_idents = Vec::new();
+        let mut field_types = Vec::new();
+        let mut field_type_custom_types = Vec::new();
+        let mut field_reads = Vec::new();
+        for ((field_name, field_type), versions) in fields.iter().zip(field_versions.iter()) {
+            let (field_ident, (field_type_tokens, custom_type_tokens)) =
+                FieldGenerator::generate((field_name, field_type), &field_scope);
+            let read = match field_type {
+                FieldType::Const { field_type, value } => FieldGenerator::read_const(
+                    field_type,
+                    value,
+                    &format!("{}.{}", name, **field_name),
+                    &field_scope,
+                ),
+                field_type => FieldGenerator::read_field_type(field_type, &field_scope),
+            };
+            match versions {
+                Some(versions) => {
+                    let in_versions = FieldGenerator::in_versions(versions);
+                    field_types.push(quote! { Option<#field_type_tokens> });
+                    field_reads.push(quote! {
+                        if #in_versions {
+                            Some(#read)
+                        } else {
+                            None
+                        }
+                    });
+                }
+                None => {
+                    field_types.push(field_type_tokens);
+                    field_reads.push(read);
+                }
+            }
+            field_idents.push(field_ident);
+            field_type_custom_types.push(custom_type_tokens);
+            field_scope.bind(field_name, field_type);
+        }
+
+        let mut stored_docs = Vec::new();
+        let mut stored_idents = Vec::new();
+        let mut stored_types = Vec::new();
+        let mut stored_defaults = Vec::new();
+        for (index, field_name) in fields.keys().enumerate() {
+            if !write_scope.is_derived(field_name) {
+                stored_docs.push(&field_docs[index]);
+                stored_idents.push(&field_idents[index]);
+                stored_types.push(&field_types[index]);
+                stored_defaults.push(&field_defaults[index]);
+            }
+        }
+        let constructors =
+            Self::constructors(&ident, &stored_idents, &stored_types, &stored_defaults);
+
+        let (derived_idents, derived_values): (Vec<_>, Vec<_>) = write_scope
+            .derived
+            .iter()
+            .map(|(field_name, value)| (FieldGenerator::ident(field_name), value))
+            .unzip();
+        let field_writes: Vec<_> = fields
+            .iter()
+            .zip(field_versions.iter())
+            .map(|((field_name, field_type), versions)| {
+                let field = write_scope.field(field_name);
+                let versions = match versions {
+                    Some(versions) => versions,
+                    None => {
+                        return FieldGenerator::write_field_type(
+                            field_type,
+                            quote! { &#field },
+                            &write_scope,
+                        )
+                    }
+                };
+                let in_versions = FieldGenerator::in_versions(versions);
+                if write_scope.is_derived(field_name) {
+                    let write = FieldGenerator::write_field_type(
+                        field_type,
+                        quote! { &#field },
+                        &write_scope,
+                    );
+                    quote! { if #in_versions { #write } else { 0 } }
+                } else {
+                    let write = FieldGenerator::write_field_type(
+                        field_type,
+                        quote! { __value },
+                        &write_scope,
+                    );
+                    quote! {
+                        match &#field {
+                            Some(__value) if #in_versions => #write,
+                            _ => 0,
+                        }
+                    }
+                }
+            })
+            .collect();
 
-        let (field_idents, field_types): (Vec<_>, Vec<_>) =
-            fields.iter().map(FieldGenerator::generate).unzip();
+        let write_body = if fields.is_empty() {
+            quote! { 0 }
+        } else {
+            quote! {
+                #(let #derived_idents = #derived_values;)*
+                let mut total = 0;
+                #(total += #field_writes;)*
+                total
+            }
+        };
 
-        let (field_types, field_type_custom_types): (Vec<_>, Vec<_>) =
-            field_types.into_iter().unzip();
+        let write_impl = Self::write_impl(&ident, scope, write_body);
+        let read_impl = Self::read_impl(
+            &ident,
+            keys,
+            scope,
+            quote! {
+                #(
+                    let #field_idents = #field_reads;
+                    #field_checks
+                )*
+                Ok(#ident {
+                    #(#stored_idents),*
+                })
+            },
+        );
 
         let tokens = quote! {
+            #docs
+            #[derive(Debug, Clone, PartialEq)]
             pub struct #ident {
-                #(#field_idents: #field_types),*
+                #(#stored_docs pub #stored_idents: #stored_types),*
             }
 
+            #read_impl
+
+            #write_impl
+
+            #constructors
+
             #(#field_type_custom_types)*
         };
 
         (ident, tokens)
     }
 
+    /// Generates `new`, taking the fields without a default, for structs with defaulted fields,
+    /// and `Default` for structs whose fields all have one.
+    fn constructors(
+        ident: &Ident,
+        idents: &[&Ident],
+        types: &[&TokenStream],
+        defaults: &[&Option<TokenStream>],
+    ) -> TokenStream {
+        if defaults.iter().all(|default| default.is_none()) {
+            return quote! {};
+        }
+
+        let mut parameters = Vec::new();
+        let mut values = Vec::new();
+        for ((ident, field_type), default) in idents.iter().zip(types).zip(defaults) {
+            match default {
+                Some(default) => values.push(quote! { #ident: #default }),
+                None => {
+                    parameters.push(quote! { #ident: #field_type });
+                    values.push(quote! { #ident });
+                }
+            }
+        }
+
+        let default_impl = if parameters.is_empty() {
+            quote! {
+                impl Default for #ident {
+                    fn default() -> Self {
+                        Self::new()
+                    }
+                }
+            }
+        } else {
+            quote! {}
+        };
+
+        quote! {
+            impl #ident {
+                #[allow(clippy::too_many_arguments)]
+                pub fn new(#(#parameters),*) -> Self {
+                    #ident {
+                        #(#values),*
+                    }
+                }
+            }
+
+            #default_impl
+        }
+    }
+
     fn generate_bit_field(
         name: &str,
-        _fields: &IndexMap<FieldName, BitField>,
+        docs: &TokenStream,
+        fields: &IndexMap<FieldName, BitField>,
+        scope: &KeyScope,
     ) -> (Ident, TokenStream) {
         let ident = Self::ident(name);
+        let versioned = Self::versioned_wrappers(&ident, scope);
+
+        let total_width: u32 = fields.values().map(|field| u32::from(field.width())).sum();
+        let container = match total_width {
+            0..=8 => quote! { u8 },
+            9..=16 => quote! { u16 },
+            17..=32 => quote! { u32 },
+            _ => quote! { u64 },
+        };
+
+        let field_idents: Vec<_> = fields
+            .keys()
+            .map(|name| FieldGenerator::ident(name))
+            .collect();
+        let field_types: Vec<_> = fields
+            .values()
+            .map(FieldGenerator::tokenize_bit_field)
+            .collect();
+
+        // The first field occupies the most significant bits.
+        let mut offset = 0;
+        let (field_reads, field_writes): (Vec<_>, Vec<_>) = fields
+            .iter()
+            .map(|(field_name, field)| {
+                let width = u32::from(field.width());
+                offset += width;
+                let shift = total_width - offset;
+                let mask = if width >= 64 {
+                    u64::MAX
+                } else {
+                    (1u64 << width) - 1
+                };
+                let field_ident = FieldGenerator::ident(field_name);
+                let field_type = FieldGenerator::tokenize_bit_field(field);
+
+                let read = match field {
+                    BitField::Boolean => quote! { (bits >> #shift) & 1 != 0 },
+                    _ if field.is_signed() => {
+                        let unused = 64 - width;
+                        quote! { ((((bits >> #shift) << #unused) as i64) >> #unused) as #field_type }
+                    }
+                    _ => quote! { ((bits >> #shift) & #mask) as #field_type },
+                };
+                let write = quote! { ((self.#field_ident as u64) & #mask) << #shift };
+                (read, write)
+            })
+            .unzip();
 
-        (ident, quote! {})
+        let tokens = quote! {
+            #docs
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub struct #ident {
+                #(pub #field_idents: #field_types),*
+            }
+
+            impl crate::types::TryReadFrom for #ident {
+                fn try_read(buf: &mut impl bytes::Buf) -> Result<Self, crate::types::Error> {
+                    let bits = u64::from(<#container as crate::types::TryReadFrom>::try_read(buf)?);
+                    Ok(#ident {
+                        #(#field_idents: #field_reads),*
+                    })
+                }
+            }
+
+            impl crate::types::WriteInto for #ident {
+                fn write(&self, buf: &mut impl bytes::BufMut) -> usize {
+                    let bits = 0u64 #(| #field_writes)*;
+                    crate::types::WriteInto::write(&(bits as #container), buf)
+                }
+            }
+
+            #versioned
+        };
+
+        (ident, tokens)
     }
 
     fn generate_bit_flags(
         name: &str,
+        docs: &TokenStream,
         integer: &IntegerType,
         flags: &BTreeMap<u64, FlagName>,
+        scope: &KeyScope,
     ) -> (Ident, TokenStream) {
         let ident = Self::ident(name);
+        let versioned = Self::versioned_wrappers(&ident, scope);
 
         let field_type = FieldGenerator::tokenize_integer(integer);
         let (flag_bits, flag_idents): (Vec<_>, Vec<_>) = flags
             .iter()
             .map(|(bits, name)| {
+                let bits = LiteralToken::u64_unsuffixed(*bits);
                 (
                     quote! { #bits },
                     Ident::new(&name.to_uppercase(), Span::call_site()),
@@ -286,85 +980,250 @@ impl CustomTypeGenerator {
             })
             .unzip();
 
+        let read_bits = FieldGenerator::read_integer(integer);
+        let write_bits = FieldGenerator::write_integer(integer, quote! { &self.bits() });
+
         let tokens = quote! {
             bitflags::bitflags! {
+                #docs
                 pub struct #ident: #field_type {
                     #(const #flag_idents = #flag_bits;)*
                 }
             }
+
+            impl crate::types::TryReadFrom for #ident {
+                fn try_read(buf: &mut impl bytes::Buf) -> Result<Self, crate::types::Error> {
+                    Ok(#ident::from_bits_truncate(#read_bits))
+                }
+            }
+
+            impl crate::types::WriteInto for #ident {
+                fn write(&self, buf: &mut impl bytes::BufMut) -> usize {
+                    #write_bits
+                }
+            }
+
+            #versioned
         };
 
         (ident, tokens)
     }
 
-    fn generate_unit(name: &str) -> (Ident, TokenStream) {
+    fn generate_unit(name: &str, docs: &TokenStream, scope: &KeyScope) -> (Ident, TokenStream) {
         let ident = Self::ident(name);
+        let versioned = Self::versioned_wrappers(&ident, scope);
 
         let tokens = quote! {
+            #docs
+            #[derive(Debug, Clone, PartialEq)]
             pub struct #ident;
+
+            impl crate::types::TryReadFrom for #ident {
+                fn try_read(_buf: &mut impl bytes::Buf) -> Result<Self, crate::types::Error> {
+                    Ok(#ident)
+                }
+            }
+
+            impl crate::types::WriteInto for #ident {
+                fn write(&self, _buf: &mut impl bytes::BufMut) -> usize {
+                    0
+                }
+            }
+
+            #versioned
         };
         (ident, tokens)
     }
 
-    fn generate_write(custom_type: &CustomType) -> TokenStream {
-        let ident = Self::ident(custom_type.name());
-        
+    /// Implements reading `ident`. Types which need the keys `keys` from enclosing structs
+    /// get an inherent `try_read_with` taking them by reference instead of `TryReadFrom`.
+    /// When generating every version, types get an inherent `try_read_versioned` taking the
+    /// version and the keys instead.
+    fn read_impl(
+        ident: &Ident,
+        keys: &[FieldName],
+        scope: &KeyScope,
+        body: TokenStream,
+    ) -> TokenStream {
+        if scope.versioned {
+            let parameters = scope.parameters(keys);
+            quote! {
+                impl #ident {
+                    #[allow(unused_variables)]
+                    pub fn try_read_versioned(
+                        buf: &mut impl bytes::Buf,
+                        version: u64,
+                        #(#parameters),*
+                    ) -> Result<Self, crate::types::Error> {
+                        #body
+                    }
+                }
+            }
+        } else if keys.is_empty() {
+            quote! {
+                impl crate::types::TryReadFrom for #ident {
+                    fn try_read(buf: &mut impl bytes::Buf) -> Result<Self, crate::types::Error> {
+                        #body
+                    }
+                }
+            }
+        } else {
+            let parameters = scope.parameters(keys);
+            quote! {
+                impl #ident {
+                    pub fn try_read_with(
+                        buf: &mut impl bytes::Buf,
+                        #(#parameters),*
+                    ) -> Result<Self, crate::types::Error> {
+                        #body
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns an expression which reads a `rust_type` needing the keys `keys` from `buf`.
+    fn read_with(rust_type: TokenStream, keys: &[FieldName], scope: &KeyScope) -> TokenStream {
+        if scope.versioned {
+            let arguments = scope.arguments(keys);
+            quote! { #rust_type::try_read_versioned(buf, version, #(#arguments),*)? }
+        } else if keys.is_empty() {
+            FieldGenerator::read_as(rust_type)
+        } else {
+            let arguments = scope.arguments(keys);
+            quote! { #rust_type::try_read_with(buf, #(#arguments),*)? }
+        }
+    }
+
+    /// Implements writing `ident` through `WriteInto`, or through an inherent
+    /// `write_versioned` taking the version when generating every version.
+    fn write_impl(ident: &Ident, scope: &KeyScope, body: TokenStream) -> TokenStream {
+        if scope.versioned {
+            quote! {
+                impl #ident {
+                    #[allow(unused_variables)]
+                    pub fn write_versioned(&self, buf: &mut impl bytes::BufMut, version: u64) -> usize {
+                        #body
+                    }
+                }
+            }
+        } else {
+            quote! {
+                impl crate::types::WriteInto for #ident {
+                    fn write(&self, buf: &mut impl bytes::BufMut) -> usize {
+                        #body
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns an expression which writes `value`, a reference to a custom type.
+    fn write_with(value: TokenStream, scope: &KeyScope) -> TokenStream {
+        if scope.versioned {
+            quote! { (#value).write_versioned(buf, version) }
+        } else {
+            FieldGenerator::write_as(value)
+        }
+    }
+
+    /// Adds `try_read_versioned` and `write_versioned` to a type which reads and writes the
+    /// same in every version, when generating every version.
+    fn versioned_wrappers(ident: &Ident, scope: &KeyScope) -> TokenStream {
+        if !scope.versioned {
+            return quote! {};
+        }
+        quote! {
+            impl #ident {
+                pub fn try_read_versioned(
+                    buf: &mut impl bytes::Buf,
+                    _version: u64,
+                ) -> Result<Self, crate::types::Error> {
+                    crate::types::TryReadFrom::try_read(buf)
+                }
+
+                pub fn write_versioned(&self, buf: &mut impl bytes::BufMut, _version: u64) -> usize {
+                    crate::types::WriteInto::write(self, buf)
+                }
+            }
+        }
+    }
+
+    /// Returns the keys which enums in `custom_type` take from fields outside of it.
+    fn free_keys(custom_type: &CustomType) -> Vec<FieldName> {
+        let mut keys = Vec::new();
         match custom_type {
-            CustomType::Struct {
-                fields,
-                ..
+            CustomType::Enum {
+                variant, variants, ..
             } => {
-                let field_names: Vec<_> = fields.keys().map(|name| FieldGenerator::ident(&name)).collect();
-                quote! {
-                    impl TryWriteInto for #ident {
-                        fn write(&self, buf: &mut impl BufMut) -> usize {
-                            #(self.#field_names.write(buf);)*
-                        }
-                    }
+                if let FieldType::Key(key) = &**variant {
+                    keys.push(key.clone());
                 }
-            },
+                for variant in variants.values() {
+                    keys.extend(Self::free_keys(variant));
+                }
+            }
+            CustomType::Struct { fields, .. } => {
+                for (index, field_type) in fields.values().enumerate() {
+                    let field_type = field_type.unannotated();
+                    keys.extend(FieldGenerator::free_keys(field_type).into_iter().filter(
+                        |key| !matches!(fields.get_index_of(key), Some(bound) if bound < index),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        let mut unique = Vec::new();
+        for key in keys {
+            if !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+        unique
+    }
+
+    /// Returns an expression evaluating to the `key` of the first keyed enum in `value`,
+    /// a reference to a `custom_type`, as an `Option`.
+    fn find_key(custom_type: &CustomType, value: TokenStream, key: &FieldName) -> TokenStream {
+        match custom_type {
             CustomType::Enum {
+                name,
                 variant,
                 variants,
                 ..
             } => {
-                // let (literals, idents) = variants
-                //     .iter()
-                //     .map(|(literal, custom_type)| )
-
-                quote! {
-                    // impl TryWriteInto for #ident {
-                    //     fn write(&self, buf: &mut impl BufMut) -> usize {
-                    //         match self {
-                    //             #(
-                    //                 #name::#idents(inner) => inner.write(buf)
-                    //             ),*
-                    //         }
-                    //     }
-                    // }
+                if **variant == FieldType::Key(key.clone()) {
+                    return quote! { Some((#value).key()) };
                 }
-            },
-            CustomType::Unit { .. } => quote! {
-                impl TryWriteInto for #ident {
-                    fn write(&self, buf: &mut impl BufMut) -> usize {
-                        0
-                    }
-                }
-            },
-            CustomType::BitField { .. } => quote! {
-                impl TryWriteInto for #ident {
-                    fn write(&self, buf: &mut impl BufMut) -> usize {
-                        0
-                    }
-                }
-            },
-            CustomType::BitFlags { .. } => quote! {
-                impl TryWriteInto for #ident {
-                    fn write(&self, buf: &mut impl BufMut) -> usize {
-                        0
+                let ident = Self::ident(name);
+                let arms: Vec<_> = variants
+                    .values()
+                    .filter(|variant| Self::free_keys(variant).contains(key))
+                    .map(|variant| {
+                        let variant_ident = Self::ident(variant.name());
+                        let inner = Self::find_key(variant, quote! { __inner }, key);
+                        quote! { #ident::#variant_ident(__inner) => #inner, }
+                    })
+                    .collect();
+                quote! {
+                    match #value {
+                        #(#arms)*
+                        #[allow(unreachable_patterns)]
+                        _ => None,
                     }
                 }
             }
+            CustomType::Struct { fields, .. } => fields
+                .iter()
+                .map(|(field_name, field_type)| (field_name, field_type.unannotated()))
+                .filter(|(_, field_type)| FieldGenerator::free_keys(field_type).contains(key))
+                .map(|(field_name, field_type)| {
+                    let field_ident = FieldGenerator::ident(field_name);
+                    FieldGenerator::find_key(field_type, quote! { &(#value).#field_ident }, key)
+                })
+                .fold(None, FieldGenerator::or_else)
+                .unwrap_or_else(|| quote! { None }),
+            _ => quote! { None },
         }
     }
 
@@ -375,48 +1234,264 @@ impl CustomTypeGenerator {
     }
 }
 
+/// Renders `doc` and a link to the `wiki` anchor as `#[doc]` attributes.
+fn doc_attributes(doc: Option<&str>, wiki: Option<&str>) -> TokenStream {
+    let mut lines: Vec<String> = doc.into_iter().flat_map(str::lines).map(String::from).collect();
+    if let Some(wiki) = wiki {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("See [wiki.vg](https://wiki.vg/Protocol#{}).", wiki));
+    }
+    let lines = lines.iter().map(|line| match line.as_str() {
+        "" => String::new(),
+        line => format!(" {}", line),
+    });
+    quote! { #(#[doc = #lines])* }
+}
+
+/// The constraints on the values of a struct field, named `struct.field` in errors.
+#[derive(Clone)]
+struct Constraints {
+    field: String,
+    min: Option<i64>,
+    max: Option<i64>,
+    max_len: Option<u64>,
+}
+
+/// Resolves references to sibling fields while writing a struct.
+///
+/// Fields which can be derived from other fields, such as array lengths, the keys of enums
+/// and booleans saying whether a conditional field is present, are not stored in the generated
+/// struct. They are computed into local variables before writing instead.
+/// Constant fields are not stored either and get a local holding their value.
+#[derive(Default)]
+struct WriteScope {
+    derived: IndexMap<FieldName, TokenStream>,
+    /// Whether every version is generated, see [`KeyScope::versioned`].
+    versioned: bool,
+}
+
+impl WriteScope {
+    fn new(fields: &IndexMap<FieldName, FieldType>, versioned: bool) -> Self {
+        let mut derived = IndexMap::new();
+        for (field_name, field_type) in fields {
+            if let FieldType::Const { field_type, value } = field_type {
+                derived.insert(
+                    field_name.clone(),
+                    FieldGenerator::literal_value(field_type, value),
+                );
+            }
+        }
+        for (field_name, field_type) in fields {
+            if let Some(length_field) = FieldGenerator::length_field(field_type) {
+                if derived.contains_key(length_field) {
+                    continue;
+                }
+                if let Some(FieldType::Integer(integer_type)) = fields.get(length_field) {
+                    let length_type = FieldGenerator::tokenize_integer(integer_type);
+                    let field_ident = FieldGenerator::ident(field_name);
+                    let length =
+                        FieldGenerator::length_of(field_type, quote! { &self.#field_ident });
+                    derived.insert(
+                        length_field.clone(),
+                        FieldGenerator::length_as(length, length_type),
+                    );
+                }
+            }
+        }
+        // A boolean which only says whether a conditional field follows is taken from it.
+        for (field_name, field_type) in fields {
+            if let FieldType::Conditional { condition, .. } = field_type {
+                let guard = condition.field();
+                if derived.contains_key(guard) {
+                    continue;
+                }
+                if let Some(FieldType::Primitive(PrimitiveType::Boolean)) = fields.get(guard) {
+                    let field_ident = FieldGenerator::ident(field_name);
+                    let present = quote! { self.#field_ident.is_some() };
+                    derived.insert(
+                        guard.clone(),
+                        FieldGenerator::tokenize_condition(condition, present),
+                    );
+                }
+            }
+        }
+        // A key is taken from the first enum using it, or its default without any.
+        for (index, key) in fields.keys().enumerate() {
+            if derived.contains_key(key) {
+                continue;
+            }
+            let found = fields
+                .iter()
+                .skip(index + 1)
+                .filter(|(_, field_type)| FieldGenerator::free_keys(field_type).contains(key))
+                .map(|(field_name, field_type)| {
+                    let field_ident = FieldGenerator::ident(field_name);
+                    FieldGenerator::find_key(field_type, quote! { &self.#field_ident }, key)
+                })
+                .fold(None, FieldGenerator::or_else);
+            if let Some(found) = found {
+                derived.insert(key.clone(), quote! { #found.unwrap_or_default() });
+            }
+        }
+        WriteScope { derived, versioned }
+    }
+
+    fn is_derived(&self, field_name: &FieldName) -> bool {
+        self.derived.contains_key(field_name)
+    }
+
+    /// Returns an expression evaluating to the value of the field `field_name`.
+    fn field(&self, field_name: &FieldName) -> TokenStream {
+        let ident = FieldGenerator::ident(field_name);
+        if self.is_derived(field_name) {
+            quote! { #ident }
+        } else {
+            quote! { self.#ident }
+        }
+    }
+}
+
+/// Fields of enclosing structs which enums can take their discriminant from, innermost last.
+///
+/// Fields of the struct being read are local variables, while fields of structs further out
+/// are passed down by reference as arguments of `try_read_with`.
+#[derive(Clone, Default)]
+struct KeyScope {
+    keys: Vec<(FieldName, FieldType, bool)>,
+    /// Whether every version of a spec is generated at once, reading with the version in a
+    /// local called `version`.
+    versioned: bool,
+}
+
+impl KeyScope {
+    /// The scope of a packet or shared type.
+    fn top_level(versioned: bool) -> KeyScope {
+        KeyScope {
+            keys: Vec::new(),
+            versioned,
+        }
+    }
+
+    /// The scope inside a nested custom type, where every key is an argument.
+    fn nested(&self) -> KeyScope {
+        KeyScope {
+            keys: self
+                .keys
+                .iter()
+                .map(|(key, field_type, _)| (key.clone(), field_type.clone(), false))
+                .collect(),
+            versioned: self.versioned,
+        }
+    }
+
+    fn bind(&mut self, key: &FieldName, field_type: &FieldType) {
+        self.keys.push((key.clone(), field_type.clone(), true));
+    }
+
+    fn resolve(&self, key: &FieldName) -> &(FieldName, FieldType, bool) {
+        self.keys
+            .iter()
+            .rev()
+            .find(|(bound, _, _)| bound == key)
+            .unwrap_or_else(|| {
+                panic!(
+                    "the key \"{}\" is not a field of an enclosing struct",
+                    **key
+                )
+            })
+    }
+
+    fn key_type(&self, key: &FieldName) -> &FieldType {
+        &self.resolve(key).1
+    }
+
+    fn parameters(&self, keys: &[FieldName]) -> Vec<TokenStream> {
+        keys.iter()
+            .map(|key| {
+                let ident = FieldGenerator::ident(key);
+                let (key_type, _) = match self.key_type(key) {
+                    FieldType::Primitive(PrimitiveType::String(_)) => (quote! { str }, quote! {}),
+                    key_type => FieldGenerator::tokenize_field_type(key_type, self),
+                };
+                quote! { #ident: &#key_type }
+            })
+            .collect()
+    }
+
+    fn arguments(&self, keys: &[FieldName]) -> Vec<TokenStream> {
+        keys.iter()
+            .map(|key| {
+                let ident = FieldGenerator::ident(key);
+                if self.resolve(key).2 {
+                    quote! { &#ident }
+                } else {
+                    quote! { #ident }
+                }
+            })
+            .collect()
+    }
+}
+
 pub struct FieldGenerator;
 impl FieldGenerator {
-    fn generate(field: (&FieldName, &FieldType)) -> (Ident, (TokenStream, TokenStream)) {
+    fn generate(
+        field: (&FieldName, &FieldType),
+        scope: &KeyScope,
+    ) -> (Ident, (TokenStream, TokenStream)) {
         let field_name = field.0;
         let ident = Self::ident(&field_name);
 
         let field_type = field.1;
 
-        let field_type_tokens = Self::tokenize_field_type(field_type);
+        let field_type_tokens = Self::tokenize_field_type(field_type, scope);
         (ident, field_type_tokens)
     }
 
-    fn tokenize_field_type(field_type: &FieldType) -> (TokenStream, TokenStream) {
+    fn tokenize_field_type(field_type: &FieldType, scope: &KeyScope) -> (TokenStream, TokenStream) {
         match field_type {
-            FieldType::Primitive(primitive_type) => Self::tokenize_primitive(primitive_type),
+            FieldType::Primitive(primitive_type) => Self::tokenize_primitive(primitive_type, scope),
             FieldType::Integer(integer_type) => (Self::tokenize_integer(integer_type), quote! {}),
             FieldType::Float(float_type) => (Self::tokenize_float(float_type), quote! {}),
             FieldType::CustomType(custom_type) => {
-                let (ident, tokens) = CustomTypeGenerator::generate(custom_type);
+                let (ident, tokens) = CustomTypeGenerator::generate(custom_type, scope);
                 (ident.to_token_stream(), tokens)
             }
+            FieldType::Conditional { field_type, .. } => {
+                let (inner_ident, inner_tokens) = Self::tokenize_field_type(field_type, scope);
+                (quote! { Option<#inner_ident> }, inner_tokens)
+            }
+            FieldType::Const { field_type, .. } => Self::tokenize_field_type(field_type, scope),
+            FieldType::Key(_) => unreachable!("keys are only valid as enum discriminants"),
+            FieldType::Annotated { .. } => {
+                unreachable!("attributes are only valid directly on struct fields")
+            }
         }
     }
 
-    fn tokenize_primitive(primitive_type: &PrimitiveType) -> (TokenStream, TokenStream) {
+    fn tokenize_primitive(
+        primitive_type: &PrimitiveType,
+        scope: &KeyScope,
+    ) -> (TokenStream, TokenStream) {
         match primitive_type {
             PrimitiveType::Boolean => (quote! { bool }, quote! {}),
             PrimitiveType::Uuid => (quote! { uuid::Uuid }, quote! {}),
             PrimitiveType::String(_) => (quote! { String }, quote! {}),
-            PrimitiveType::Nbt => (quote! { nbt::Blob }, quote! {}),
+            PrimitiveType::Nbt => (quote! { crate::types::Nbt }, quote! {}),
+            PrimitiveType::EntityMetadata => (quote! { crate::types::EntityMetadata }, quote! {}),
             PrimitiveType::Array { field_type, .. } => {
-                let (ident, tokens) = Self::tokenize_field_type(field_type);
+                let (ident, tokens) = Self::tokenize_field_type(field_type, scope);
                 (quote! {Vec<#ident>}, tokens)
             }
             PrimitiveType::Option(inner) => {
-                let (inner_ident, inner_tokens) = Self::tokenize_field_type(inner);
+                let (inner_ident, inner_tokens) = Self::tokenize_field_type(inner, scope);
                 (quote! { Option<#inner_ident> }, inner_tokens)
             }
-            PrimitiveType::Shared(name) => (
-                CustomTypeGenerator::ident(name).to_token_stream(),
-                quote! {},
-            ),
+            PrimitiveType::Shared(name) => {
+                let ident = CustomTypeGenerator::ident(name);
+                (quote! { shared::#ident }, quote! {})
+            }
         }
     }
 
@@ -440,9 +1515,571 @@ impl FieldGenerator {
         }
     }
 
+    fn tokenize_bit_field(bit_field: &BitField) -> TokenStream {
+        match bit_field {
+            BitField::Boolean => quote! { bool },
+            BitField::I8(_) => quote! { i8 },
+            BitField::U8(_) => quote! { u8 },
+            BitField::I16(_) => quote! { i16 },
+            BitField::U16(_) => quote! { u16 },
+            BitField::I32(_) => quote! { i32 },
+            BitField::U32(_) => quote! { u32 },
+            BitField::I64(_) => quote! { i64 },
+            BitField::U64(_) => quote! { u64 },
+        }
+    }
+
+    fn tokenize_literal(literal: &Literal) -> TokenStream {
+        match literal {
+            Literal::String(string) => LiteralToken::string(string).to_token_stream(),
+            Literal::Boolean(boolean) => quote! { #boolean },
+            Literal::Int(int) => LiteralToken::i64_unsuffixed(*int).to_token_stream(),
+        }
+    }
+
+    /// Tokenizes `literal` as an owned value of `field_type`.
+    fn literal_value(field_type: &FieldType, literal: &Literal) -> TokenStream {
+        match (field_type, literal) {
+            (FieldType::Integer(integer_type), Literal::Int(int)) => {
+                let int = *int;
+                let literal = match integer_type {
+                    IntegerType::U8 => LiteralToken::u8_suffixed(int as u8),
+                    IntegerType::I8 => LiteralToken::i8_suffixed(int as i8),
+                    IntegerType::U16 => LiteralToken::u16_suffixed(int as u16),
+                    IntegerType::I16 => LiteralToken::i16_suffixed(int as i16),
+                    IntegerType::U32 => LiteralToken::u32_suffixed(int as u32),
+                    IntegerType::I32 | IntegerType::VarInt => LiteralToken::i32_suffixed(int as i32),
+                    IntegerType::U64 => LiteralToken::u64_suffixed(int as u64),
+                    IntegerType::I64 | IntegerType::VarLong => LiteralToken::i64_suffixed(int),
+                };
+                literal.to_token_stream()
+            }
+            (_, Literal::String(_)) => {
+                let literal = Self::tokenize_literal(literal);
+                quote! { String::from(#literal) }
+            }
+            _ => Self::tokenize_literal(literal),
+        }
+    }
+
+    /// Tokenizes whether the version being read or written, in a local called `version`,
+    /// is in `versions`.
+    fn in_versions(versions: &VersionRange) -> TokenStream {
+        let bound = |version: ProtocolVersion| LiteralToken::u64_unsuffixed(*version);
+        match (versions.since.map(bound), versions.until.map(bound)) {
+            (Some(since), Some(until)) => quote! { (#since..=#until).contains(&version) },
+            (Some(since), None) => quote! { version >= #since },
+            (None, Some(until)) => quote! { version <= #until },
+            (None, None) => quote! { true },
+        }
+    }
+
+    /// Tokenizes `condition`, where `field` evaluates to the value of the field of the
+    /// enclosing struct it refers to.
+    fn tokenize_condition(condition: &Condition, field: TokenStream) -> TokenStream {
+        let field = match condition.member() {
+            Some(member) => {
+                let member = Self::ident(member);
+                quote! { #field.#member }
+            }
+            None => field,
+        };
+        match condition {
+            Condition::Equal(_, Literal::Boolean(true))
+            | Condition::NotEqual(_, Literal::Boolean(false)) => field,
+            Condition::Equal(_, Literal::Boolean(false))
+            | Condition::NotEqual(_, Literal::Boolean(true)) => quote! { !#field },
+            Condition::Equal(_, literal) => {
+                let literal = Self::tokenize_literal(literal);
+                quote! { #field == #literal }
+            }
+            Condition::NotEqual(_, literal) => {
+                let literal = Self::tokenize_literal(literal);
+                quote! { #field != #literal }
+            }
+        }
+    }
+
+    /// Returns an expression which reads a value of `field_type` from `buf`.
+    ///
+    /// Earlier fields of the enclosing struct are in scope as local variables.
+    fn read_field_type(field_type: &FieldType, scope: &KeyScope) -> TokenStream {
+        match field_type {
+            FieldType::Primitive(primitive_type) => Self::read_primitive(primitive_type, scope),
+            FieldType::Integer(integer_type) => Self::read_integer(integer_type),
+            FieldType::Float(float_type) => Self::read_as(Self::tokenize_float(float_type)),
+            FieldType::CustomType(custom_type) => CustomTypeGenerator::read_with(
+                CustomTypeGenerator::ident(custom_type.name()).to_token_stream(),
+                &CustomTypeGenerator::free_keys(custom_type),
+                scope,
+            ),
+            FieldType::Conditional {
+                condition,
+                field_type,
+            } => {
+                let condition = Self::tokenize_condition(
+                    condition,
+                    Self::ident(&condition.struct_field()).to_token_stream(),
+                );
+                let inner = Self::read_field_type(field_type, scope);
+                quote! {
+                    if #condition {
+                        Some(#inner)
+                    } else {
+                        None
+                    }
+                }
+            }
+            FieldType::Key(_) => unreachable!("keys are only valid as enum discriminants"),
+            FieldType::Annotated { .. } => {
+                unreachable!("attributes are only valid directly on struct fields")
+            }
+            FieldType::Const { .. } => {
+                unreachable!("constants are only valid directly on struct fields")
+            }
+        }
+    }
+
+    /// Tokenizes reading the constant struct field `field`, which fails unless it holds `value`.
+    fn read_const(
+        field_type: &FieldType,
+        value: &Literal,
+        field: &str,
+        scope: &KeyScope,
+    ) -> TokenStream {
+        let inner = Self::read_field_type(field_type, scope);
+        let value = Self::tokenize_literal(value);
+        quote! {{
+            let __value = #inner;
+            if __value != #value {
+                return Err(crate::types::Error::UnexpectedConst {
+                    field: #field,
+                    expected: format!("{:?}", #value),
+                    found: format!("{:?}", __value),
+                });
+            }
+            __value
+        }}
+    }
+
+    fn read_primitive(primitive_type: &PrimitiveType, scope: &KeyScope) -> TokenStream {
+        match primitive_type {
+            PrimitiveType::Boolean => Self::read_as(quote! { bool }),
+            PrimitiveType::Uuid => Self::read_as(quote! { uuid::Uuid }),
+            PrimitiveType::String(_) => Self::read_as(quote! { String }),
+            PrimitiveType::Nbt => Self::read_as(quote! { crate::types::Nbt }),
+            PrimitiveType::EntityMetadata => Self::read_as(quote! { crate::types::EntityMetadata }),
+            PrimitiveType::Array { length, field_type } => {
+                let element = Self::read_field_type(field_type, scope);
+                match length {
+                    ArrayLength::Prefixed(integer_type) => {
+                        let length = Self::read_integer(integer_type);
+                        quote! {{
+                            let __length = <usize as std::convert::TryFrom<_>>::try_from(#length)
+                                .map_err(|_| crate::types::Error::Malformed)?;
+                            let mut __items = Vec::with_capacity(
+                                __length.min(bytes::Buf::remaining(buf))
+                            );
+                            for _ in 0..__length {
+                                __items.push(#element);
+                            }
+                            __items
+                        }}
+                    }
+                    ArrayLength::FixedLength(length) => {
+                        let length = LiteralToken::u64_unsuffixed(*length);
+                        quote! {{
+                            let mut __items = Vec::with_capacity(#length);
+                            for _ in 0..#length {
+                                __items.push(#element);
+                            }
+                            __items
+                        }}
+                    }
+                    ArrayLength::Field(length_field) => {
+                        let length = Self::ident(length_field);
+                        quote! {{
+                            let __length = <usize as std::convert::TryFrom<_>>::try_from(#length)
+                                .map_err(|_| crate::types::Error::Malformed)?;
+                            let mut __items = Vec::with_capacity(
+                                __length.min(bytes::Buf::remaining(buf))
+                            );
+                            for _ in 0..__length {
+                                __items.push(#element);
+                            }
+                            __items
+                        }}
+                    }
+                    ArrayLength::RemainingLength => quote! {{
+                        let mut __items = Vec::new();
+                        while bytes::Buf::has_remaining(buf) {
+                            __items.push(#element);
+                        }
+                        __items
+                    }},
+                    ArrayLength::Terminated(integer_type, terminator) => {
+                        let next = Self::read_integer(integer_type);
+                        let terminator = LiteralToken::i64_unsuffixed(*terminator);
+                        // Peek at the next value and only consume it if it is the terminator.
+                        quote! {{
+                            let mut __items = Vec::new();
+                            loop {
+                                let mut __peek = bytes::Buf::bytes(buf);
+                                let __next = {
+                                    let buf = &mut __peek;
+                                    #next
+                                };
+                                if __next == #terminator {
+                                    let __consumed = bytes::Buf::bytes(buf).len() - __peek.len();
+                                    bytes::Buf::advance(buf, __consumed);
+                                    break;
+                                }
+                                __items.push(#element);
+                            }
+                            __items
+                        }}
+                    }
+                }
+            }
+            PrimitiveType::Option(inner) => {
+                let inner = Self::read_field_type(inner, scope);
+                quote! {
+                    if <bool as crate::types::TryReadFrom>::try_read(buf)? {
+                        Some(#inner)
+                    } else {
+                        None
+                    }
+                }
+            }
+            PrimitiveType::Shared(name) => {
+                let ident = CustomTypeGenerator::ident(name);
+                CustomTypeGenerator::read_with(quote! { shared::#ident }, &[], scope)
+            }
+        }
+    }
+
+    fn read_integer(integer_type: &IntegerType) -> TokenStream {
+        match integer_type {
+            IntegerType::VarInt => {
+                let read = Self::read_as(quote! { crate::types::VarInt });
+                quote! { *#read }
+            }
+            IntegerType::VarLong => {
+                let read = Self::read_as(quote! { crate::types::VarLong });
+                quote! { *#read }
+            }
+            _ => Self::read_as(Self::tokenize_integer(integer_type)),
+        }
+    }
+
+    fn read_as(rust_type: TokenStream) -> TokenStream {
+        quote! { <#rust_type as crate::types::TryReadFrom>::try_read(buf)? }
+    }
+
+    /// Returns an expression which writes `value`, a reference to a value of `field_type`,
+    /// into `buf` and evaluates to the number of bytes written.
+    fn write_field_type(
+        field_type: &FieldType,
+        value: TokenStream,
+        scope: &WriteScope,
+    ) -> TokenStream {
+        match field_type {
+            FieldType::Primitive(primitive_type) => {
+                Self::write_primitive(primitive_type, value, scope)
+            }
+            FieldType::Integer(integer_type) => Self::write_integer(integer_type, value),
+            FieldType::Float(_) => Self::write_as(value),
+            FieldType::CustomType(_) if scope.versioned => {
+                quote! { (#value).write_versioned(buf, version) }
+            }
+            FieldType::CustomType(_) => Self::write_as(value),
+            FieldType::Conditional {
+                condition,
+                field_type,
+            } => {
+                let field = scope.field(&condition.struct_field());
+                let condition = Self::tokenize_condition(condition, field);
+                let inner = Self::write_field_type(field_type, quote! { __value }, scope);
+                quote! {{
+                    assert_eq!(
+                        #condition,
+                        (#value).is_some(),
+                        "conditional field does not match its condition"
+                    );
+                    match #value {
+                        Some(__value) => #inner,
+                        None => 0,
+                    }
+                }}
+            }
+            // The value is a local holding the constant.
+            FieldType::Const { field_type, .. } => Self::write_field_type(field_type, value, scope),
+            FieldType::Key(_) => unreachable!("keys are only valid as enum discriminants"),
+            FieldType::Annotated { .. } => {
+                unreachable!("attributes are only valid directly on struct fields")
+            }
+        }
+    }
+
+    fn write_primitive(
+        primitive_type: &PrimitiveType,
+        value: TokenStream,
+        scope: &WriteScope,
+    ) -> TokenStream {
+        match primitive_type {
+            PrimitiveType::Array { length, field_type } => {
+                let element = Self::write_field_type(field_type, quote! { __item }, scope);
+                let prefix = match length {
+                    ArrayLength::Prefixed(integer_type) => {
+                        let length_type = Self::tokenize_integer(integer_type);
+                        let length = Self::length_as(quote! { (#value).len() }, length_type);
+                        Self::write_integer(integer_type, quote! { &#length })
+                    }
+                    ArrayLength::FixedLength(length) => {
+                        let length = LiteralToken::u64_unsuffixed(*length);
+                        quote! {{
+                            debug_assert_eq!((#value).len(), #length, "array has the wrong length");
+                            0
+                        }}
+                    }
+                    // The length field is derived from this array and written by itself.
+                    ArrayLength::Field(_)
+                    | ArrayLength::RemainingLength
+                    | ArrayLength::Terminated(..) => quote! { 0 },
+                };
+                let suffix = match length {
+                    ArrayLength::Terminated(integer_type, terminator) => {
+                        let terminator_type = Self::tokenize_integer(integer_type);
+                        let terminator = LiteralToken::i64_unsuffixed(*terminator);
+                        Self::write_integer(
+                            integer_type,
+                            quote! { &(#terminator as #terminator_type) },
+                        )
+                    }
+                    _ => quote! { 0 },
+                };
+                quote! {{
+                    let mut __total = #prefix;
+                    for __item in (#value).iter() {
+                        __total += #element;
+                    }
+                    __total + #suffix
+                }}
+            }
+            PrimitiveType::Option(inner) => {
+                let inner = Self::write_field_type(inner, quote! { __value }, scope);
+                quote! {
+                    match #value {
+                        Some(__value) => crate::types::WriteInto::write(&true, buf) + #inner,
+                        None => crate::types::WriteInto::write(&false, buf),
+                    }
+                }
+            }
+            PrimitiveType::Shared(_) if scope.versioned => {
+                quote! { (#value).write_versioned(buf, version) }
+            }
+            PrimitiveType::Boolean
+            | PrimitiveType::Uuid
+            | PrimitiveType::String(_)
+            | PrimitiveType::Nbt
+            | PrimitiveType::EntityMetadata
+            | PrimitiveType::Shared(_) => Self::write_as(value),
+        }
+    }
+
+    fn write_integer(integer_type: &IntegerType, value: TokenStream) -> TokenStream {
+        match integer_type {
+            IntegerType::VarInt => Self::write_as(quote! { &crate::types::VarInt::from(*#value) }),
+            IntegerType::VarLong => {
+                Self::write_as(quote! { &crate::types::VarLong::from(*#value) })
+            }
+            _ => Self::write_as(value),
+        }
+    }
+
+    fn write_as(value: TokenStream) -> TokenStream {
+        quote! { crate::types::WriteInto::write(#value, buf) }
+    }
+
+    /// Returns the field holding the length of the array in `field_type`, if any.
+    fn length_field(field_type: &FieldType) -> Option<&FieldName> {
+        match field_type {
+            FieldType::Primitive(PrimitiveType::Array {
+                length: ArrayLength::Field(length_field),
+                ..
+            }) => Some(length_field),
+            FieldType::Primitive(PrimitiveType::Option(inner))
+            | FieldType::Conditional {
+                field_type: inner, ..
+            } => Self::length_field(inner),
+            _ => None,
+        }
+    }
+
+    /// Returns an expression evaluating to the length of the array in `value`,
+    /// a reference to a value of `field_type`. Absent arrays have a length of zero.
+    fn length_of(field_type: &FieldType, value: TokenStream) -> TokenStream {
+        match field_type {
+            FieldType::Primitive(PrimitiveType::Option(inner))
+            | FieldType::Conditional {
+                field_type: inner, ..
+            } => {
+                let inner = Self::length_of(inner, quote! { __value });
+                quote! { (#value).as_ref().map_or(0, |__value| #inner) }
+            }
+            _ => quote! { (#value).len() },
+        }
+    }
+
+    /// Converts `length`, the length of an array, to the integer type it is written as.
+    /// Writing an array which is too long for it panics instead of writing a wrong length.
+    fn length_as(length: TokenStream, length_type: TokenStream) -> TokenStream {
+        quote! {
+            <#length_type as std::convert::TryFrom<usize>>::try_from(#length)
+                .expect("array is too long for its length field")
+        }
+    }
+
+    /// Returns the keys which enums in `field_type` take from fields outside of it.
+    fn free_keys(field_type: &FieldType) -> Vec<FieldName> {
+        match field_type {
+            FieldType::CustomType(custom_type) => CustomTypeGenerator::free_keys(custom_type),
+            FieldType::Primitive(PrimitiveType::Array { field_type, .. })
+            | FieldType::Primitive(PrimitiveType::Option(field_type))
+            | FieldType::Conditional { field_type, .. } => Self::free_keys(field_type),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns an expression evaluating to the `key` of the first keyed enum in `value`,
+    /// a reference to a value of `field_type`, as an `Option`. The key is written once for
+    /// a whole array, so the expression panics if its elements have different keys.
+    fn find_key(field_type: &FieldType, value: TokenStream, key: &FieldName) -> TokenStream {
+        match field_type {
+            FieldType::CustomType(custom_type) => {
+                CustomTypeGenerator::find_key(custom_type, value, key)
+            }
+            FieldType::Primitive(PrimitiveType::Array { field_type, .. }) => {
+                let inner = Self::find_key(field_type, quote! { __item }, key);
+                quote! {{
+                    let mut __keys = (#value).iter().filter_map(|__item| #inner);
+                    let __key = __keys.next();
+                    assert!(
+                        __keys.all(|__other| Some(&__other) == __key.as_ref()),
+                        "elements of an array sharing a key have different keys"
+                    );
+                    __key
+                }}
+            }
+            FieldType::Primitive(PrimitiveType::Option(field_type))
+            | FieldType::Conditional { field_type, .. } => {
+                let inner = Self::find_key(field_type, quote! { __value }, key);
+                quote! { (#value).as_ref().and_then(|__value| #inner) }
+            }
+            _ => quote! { None },
+        }
+    }
+
+    /// Returns statements which return an error from the read of a struct if `value`,
+    /// a value of `field_type`, violates `constraints`. Strings are limited to their maximum
+    /// length by default.
+    fn check(field_type: &FieldType, value: TokenStream, constraints: &Constraints) -> TokenStream {
+        let field = &constraints.field;
+        match field_type {
+            FieldType::Integer(_) | FieldType::Float(_) => {
+                let bound = |bound: i64| match field_type {
+                    FieldType::Float(_) => LiteralToken::f64_unsuffixed(bound as f64),
+                    _ => LiteralToken::i64_unsuffixed(bound),
+                };
+                let condition = match (constraints.min.map(bound), constraints.max.map(bound)) {
+                    (Some(min), Some(max)) => quote! { !(#min..=#max).contains(&#value) },
+                    (Some(min), None) => quote! { #value < #min },
+                    (None, Some(max)) => quote! { #value > #max },
+                    (None, None) => return quote! {},
+                };
+                quote! {
+                    if #condition {
+                        return Err(crate::types::Error::OutOfRange { field: #field });
+                    }
+                }
+            }
+            FieldType::Primitive(PrimitiveType::String(max_len)) => {
+                let max_len = match constraints.max_len {
+                    Some(max_len) => max_len,
+                    None if *max_len > 0 => u64::from(*max_len),
+                    None => return quote! {},
+                };
+                let max_len = LiteralToken::u64_unsuffixed(max_len);
+                quote! {
+                    if (#value).chars().count() > #max_len {
+                        return Err(crate::types::Error::TooLong { field: #field, max_len: #max_len });
+                    }
+                }
+            }
+            FieldType::Primitive(PrimitiveType::Array { field_type, .. }) => {
+                let length = match constraints.max_len {
+                    Some(max_len) => {
+                        let max_len = LiteralToken::u64_unsuffixed(max_len);
+                        quote! {
+                            if (#value).len() > #max_len {
+                                return Err(crate::types::Error::TooLong {
+                                    field: #field,
+                                    max_len: #max_len,
+                                });
+                            }
+                        }
+                    }
+                    None => quote! {},
+                };
+                let element_constraints = Constraints {
+                    max_len: None,
+                    ..constraints.clone()
+                };
+                let element = Self::check(field_type, quote! { *__item }, &element_constraints);
+                if element.is_empty() {
+                    length
+                } else {
+                    quote! {
+                        #length
+                        for __item in (#value).iter() {
+                            #element
+                        }
+                    }
+                }
+            }
+            FieldType::Primitive(PrimitiveType::Option(field_type))
+            | FieldType::Conditional { field_type, .. } => {
+                let inner = Self::check(field_type, quote! { *__value }, constraints);
+                if inner.is_empty() {
+                    inner
+                } else {
+                    quote! {
+                        if let Some(__value) = &(#value) {
+                            #inner
+                        }
+                    }
+                }
+            }
+            _ => quote! {},
+        }
+    }
+
+    /// Chains two `Option` expressions, preferring the first.
+    fn or_else(found: Option<TokenStream>, next: TokenStream) -> Option<TokenStream> {
+        match found {
+            Some(found) => Some(quote! { #found.or_else(|| #next) }),
+            None => Some(next),
+        }
+    }
+
     fn ident(name: &str) -> Ident {
         use heck::SnakeCase;
         let name = name.to_snake_case();
-        Ident::new(&name, Span::call_site())
+        if syn::parse_str::<Ident>(&name).is_ok() {
+            Ident::new(&name, Span::call_site())
+        } else {
+            // Keywords such as `type` and `match` are common field names.
+            Ident::new_raw(&name, Span::call_site())
+        }
     }
 }
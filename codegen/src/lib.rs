@@ -1,3 +1,3 @@
 mod generation;
 pub use generation::*;
-pub use feather_protocol_spec::Protocol;
\ No newline at end of file
+pub use feather_protocol_spec::{Minecraft, Protocol, ProtocolVersion};
\ No newline at end of file
@@ -1,38 +1,100 @@
 use anyhow::Result;
 use feather_protocol_spec::*;
 use feather_protocol_codegen::ProtocolGenerator;
-use std::collections::BTreeMap;
-use indexmap::IndexMap;
 
 #[test]
 fn player_info_packet() -> Result<()> {
-    let packet_bytes = include_bytes!("../../protocols/1.15.2.ron");
-    // let packet_bytes = include_bytes!("./player_info_packet.ron");
-
-    // let protocol_de: Protocol = ron::de::from_bytes(packet_bytes)?;
-
-    let mut packets = BTreeMap::new();
-    packets.insert(PacketIdentifier(
-        PacketDirection::Client, PacketStage::Handshaking, 0.into()),
-        CustomType::Struct {
-            name: CustomTypeName::from("ma".to_owned()),
-            fields: {
-                let mut fields = IndexMap::new();
-                fields.insert(FieldName::from("username".to_owned()), PrimitiveType::String(0).into());
-                fields
-            }
-        }
-    );
-
-    let protocol = Protocol {
-        version: ProtocolVersion::from(0),
-        shared_types: vec![],
-        packets,
-    };
-
-    let protocol = ProtocolGenerator::generate(protocol);
-
-    println!("{}", protocol);
+    let packet_bytes = include_bytes!("./player_info_packet.ron");
+    let minecraft: Minecraft = ron::de::from_bytes(packet_bytes)?;
+    minecraft.validate()?;
+
+    let tokens = ProtocolGenerator::generate_minecraft(minecraft);
+    syn::parse2::<syn::File>(tokens.clone())?;
+
+    let generated = tokens.to_string();
+    assert!(generated.contains("impl crate :: packet :: Protocol for Protocol { fn version () -> u64 { 578 }"));
+    assert!(generated.contains("fn minecraft_version () -> & 'static str { \"1.15.2\" }"));
+    assert!(generated.contains("fn minecraft_major_version () -> & 'static str { \"1.15\" }"));
+    assert!(generated.contains("pub struct PlayerInfo { pub players : Vec < Player > }"));
+
+    Ok(())
+}
+
+#[test]
+fn doc_comments() -> Result<()> {
+    let protocol: Protocol = ron::de::from_str(r#"(
+        version: 578,
+        packets: {
+            (Client, Play, 0): Struct(
+                name: "spawn_entity",
+                attributes: (
+                    doc: "Sent when a vehicle or other non-living entity is created.",
+                    wiki: "Spawn_Entity",
+                ),
+                fields: {
+                    "velocity_x": Annotated(
+                        attributes: (
+                            doc: "In units of 1/8000 of a block per tick.",
+                        ),
+                        field_type: I16,
+                    ),
+                    "status": Enum(
+                        name: "status",
+                        variant: U8,
+                        variants: {
+                            0: Unit(name: "alive", attributes: (doc: "The entity is alive.")),
+                        },
+                    ),
+                },
+            ),
+        },
+        shared_types: [],
+    )"#)?;
+    protocol.validate()?;
+
+    let tokens = ProtocolGenerator::generate(protocol);
+    syn::parse2::<syn::File>(tokens.clone())?;
+
+    let generated = tokens.to_string();
+    assert!(generated.contains(concat!(
+        r#"# [doc = " Sent when a vehicle or other non-living entity is created."] "#,
+        r#"# [doc = ""] "#,
+        r#"# [doc = " See [wiki.vg](https://wiki.vg/Protocol#Spawn_Entity)."] "#,
+        "# [derive (Debug , Clone , PartialEq)] pub struct SpawnEntity",
+    )));
+    assert!(generated.contains(
+        r#"# [doc = " In units of 1/8000 of a block per tick."] pub velocity_x : i16"#
+    ));
+    assert!(generated.contains(r#"pub enum Status { # [doc = " The entity is alive."] Alive (Alive) ,"#));
+
+    Ok(())
+}
+
+#[test]
+fn entity_metadata_field() -> Result<()> {
+    let protocol: Protocol = ron::de::from_str(r#"(
+        version: 578,
+        packets: {
+            (Client, Play, 68): Struct(
+                name: "entity_metadata",
+                fields: {
+                    "entity_id": VarInt,
+                    "metadata": EntityMetadata,
+                },
+            ),
+        },
+        shared_types: [],
+    )"#)?;
+    protocol.validate()?;
+
+    let tokens = ProtocolGenerator::generate(protocol);
+    syn::parse2::<syn::File>(tokens.clone())?;
+
+    let generated = tokens.to_string();
+    assert!(generated.contains("pub metadata : crate :: types :: EntityMetadata"));
+    assert!(generated.contains(
+        "< crate :: types :: EntityMetadata as crate :: types :: TryReadFrom > :: try_read (buf) ?"
+    ));
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,6 +1,11 @@
-use anyhow::{anyhow, Result};
-use parse_wiki_text::{Configuration, Node};
+use anyhow::{anyhow, Context, Result};
+use parse_wiki_text::Configuration;
 use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+pub mod minecraft_data;
+pub mod wiki;
 
 pub fn fetch_wiki_page(url: &str, page: &str) -> Result<Value> {
     let url = reqwest::Url::parse_with_params(url, &[
@@ -13,13 +18,30 @@ pub fn fetch_wiki_page(url: &str, page: &str) -> Result<Value> {
     Ok(response.json::<Value>()?)
 }
 
+/// The wikitext in a response of the `parse` API, in either of its formats: version 1 wraps
+/// the text in an object under `*`, version 2 gives it directly.
 pub fn extract_wikitext(value: &Value) -> Option<&str> {
     value.get("parse")
         .and_then(|p| p.get("wikitext"))
-        .and_then(|w| w.get("*"))
+        .and_then(|w| w.get("*").or(Some(w)))
         .and_then(|a| a.as_str())
 }
 
+/// Reads a saved copy of a wiki page: either its wikitext, or the JSON response of the API
+/// which `fetch_wiki_page` gets.
+pub fn read_wiki_page(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Reading {}", path.display()))?;
+    // Wikitext is never valid JSON.
+    match serde_json::from_str::<Value>(&contents) {
+        Ok(response) => extract_wikitext(&response)
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("{} does not hold the wikitext of a page", path.display())),
+        Err(_) => Ok(contents),
+    }
+}
+
 pub fn create_configuration() -> ::parse_wiki_text::Configuration {
     Configuration::new(&parse_wiki_text::ConfigurationSource {
         category_namespaces: &[
@@ -98,36 +120,20 @@ pub fn create_configuration() -> ::parse_wiki_text::Configuration {
 mod tests {
     use anyhow::Result;
     use super::*;
+
+    /// Needs access to wiki.vg, see the tests of `wiki` for tests against a saved copy.
     #[test]
+    #[ignore]
     fn test_fetch_tables() -> Result<()> {
         let response = fetch_wiki_page("https://wiki.vg/api.php", "Protocol")?;
         let wiki_text = extract_wikitext(&response)
             .ok_or(anyhow!("WikiText not present"))?;
 
-        let result = create_configuration().parse(wiki_text);
-
-        result.nodes
-            .iter()
-            .filter_map(|n| match n {
-                Node::Table {
-                    rows,
-                    ..
-                } => {
-                    rows.get(0)
-                        .and_then(|r| r.cells.get(0))
-                        .and_then(|c| c.content.get(0))
-                        .filter(|n| match n {
-                            Node::Text {
-                                value: "Packet ID",
-                                ..
-                            } => true,
-                            _ => false,
-                        })
-                        .and(Some(rows))
-                },
-                _ => None,
-            })
-            .for_each(|n| println!("{:#?}", n));
+        let tables = wiki::packet_tables(wiki_text);
+        assert!(!tables.is_empty());
+        for packet_table in tables.iter() {
+            assert_eq!(packet_table.table.rows[0].cells[..3], ["Packet ID", "State", "Bound To"]);
+        }
 
         Ok(())
     }
@@ -1,262 +1,143 @@
-use anyhow::{anyhow, Result};
-use parse_wiki_text::{Configuration, Node};
-
-fn get_content(cell: &parse_wiki_text::TableCell) -> Option<String> {
-    return Some(if let Node::Text{value,..} = cell.content.get(0)? {
-        value.to_string()
-    } else {
-        String::new()
-    });
-}
-
-fn count(cells: &Vec<parse_wiki_text::TableCell>) -> usize {
-    let mut u = 0;
-    for cell in cells {
-        u += 1;
-        if let Some(e) = &cell.attributes {
-            for l in e {
-                if let Node::Text { value, ..} = l {
-                    if value.contains("colspan=") {
-                        u += value.split("\"").skip(1).next().expect("A").parse::<usize>().expect("B")-1;
-                    }
-                }
-            }
+use anyhow::{anyhow, bail, Context, Result};
+use feather_protocol_extractor::wiki::{extract, packet_tables, page_version};
+use feather_protocol_extractor::{
+    extract_wikitext, fetch_wiki_page, minecraft_data, read_wiki_page,
+};
+use feather_protocol_spec::{
+    Diagnostic, Format, Minecraft, MinecraftVersion, Protocol, Spec, Validation, SPEC_VERSION,
+};
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "usage:
+    feather-protocol-extractor wiki <output> [<saved page>]
+    feather-protocol-extractor import <protocol.json> <minecraft version> <protocol version> <output>
+    feather-protocol-extractor export <spec> <protocol.json>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("wiki") => wiki(&args[1..]),
+        Some("import") => import(&args[1..]),
+        Some("export") => export(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(true)
         }
-    }
-    u
-}
-
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
-struct Row {
-    cells: Vec<String>
-}
-
-impl Row {
-
-    fn new(vec: Vec<String>) -> Self {
-        Self {
-            cells: vec
+        _ => Err(anyhow!("Specify what to do.\n{}", USAGE)),
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("error: {:#}", error);
+            process::exit(1);
         }
     }
 }
 
-
-
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
-struct Table {
-    rows: Vec<Row>
-}
-
-impl Table {
-
-    fn new(rows: &std::vec::Vec<parse_wiki_text::TableRow<'_>>) -> Self {
-        let mut p = 0;
-        for row in rows {
-            let k = count(&row.cells);
-            if p < k {
-                p = k;
-            }
-        }
-        let mut table = (0..rows.len()).map(|_| {
-            (0..p).map(|_| {
-                "".to_string()
-            }).collect::<Vec<String>>()
-        }).collect::<Vec<Vec<String>>>();
-        
-        for (y,row) in rows.iter().enumerate() {
-            let mut x = 0;
-            for cell in row.cells.iter() {
-                let content = if let Some(e) = get_content(cell) {
-                    if e.is_empty() {
-                        "<empty>".to_owned()
-                    } else {
-                        e.clone()
-                    }
-                } else {
-                    "<empty>".to_owned()
-                };
-                while !table[y][x].is_empty() {
-                    x += 1;
-                    
-                }
-                table[y][x] = content.clone();
-                if let Some(e) = &cell.attributes {
-                    for l in e {
-                        if let Node::Text { value, ..} = l {
-                            if value.contains("rowspan=") {
-                                for p in 0..value.split("\"").skip(1).next().expect("D").parse::<usize>().expect("C") {
-                                    table[y+p][x] = content.clone();
-                                }
-                            } else if value.contains("colspan=") {
-                                for p in 0..value.split("\"").skip(1).next().expect("A").parse::<usize>().expect("B")-1 {
-                                    table[y][x+p] = content.clone();
-                                }
-                            }
-                        }
-                    }
-                }
-                x += 1;
-            }
-
-        }
-        //println!("{:#?}",table);
-
-        let mut rows: Vec<Row> = Vec::with_capacity(table.len());
-        for t in table {
-            rows.push(Row::new(t));
-        }
-        return Self {
-            rows
+/// Extracts a spec from the Protocol page of wiki.vg, or from a saved copy of it.
+fn wiki(args: &[String]) -> Result<bool> {
+    let wikitext = match args {
+        [_] => {
+            let response = fetch_wiki_page("https://wiki.vg/api.php", "Protocol")?;
+            extract_wikitext(&response)
+                .ok_or_else(|| anyhow!("wikitext not present"))?
+                .to_owned()
         }
-    }
+        [_, page] => read_wiki_page(page)?,
+        _ => bail!("Specify the file path to write the spec to.\n{}", USAGE),
+    };
+    let (version, protocol_version) = page_version(&wikitext)
+        .ok_or_else(|| anyhow!("The page does not say which protocol version it is about."))?;
+
+    let extracted = extract(&packet_tables(&wikitext), protocol_version);
+    write_spec(
+        &args[0],
+        version,
+        extracted.protocol,
+        &extracted.diagnostics,
+    )
 }
 
-struct Tree {
-    value: String,
-    childs: Vec<Tree>
+/// Imports a spec from the `protocol.json` of minecraft-data.
+fn import(args: &[String]) -> Result<bool> {
+    let (path, version, protocol_version, output) = match args {
+        [path, version, protocol_version, output] => (path, version, protocol_version, output),
+        _ => bail!(
+            "Specify the protocol.json, its versions and the output.\n{}",
+            USAGE
+        ),
+    };
+    let protocol_version: u64 = protocol_version
+        .parse()
+        .map_err(|_| anyhow!("`{}` is not a protocol version", protocol_version))?;
+    let source = fs::read_to_string(path).with_context(|| format!("Reading {}", path))?;
+    let protocol_json =
+        serde_json::from_str(&source).with_context(|| format!("Reading {}", path))?;
+
+    let imported = minecraft_data::import(&protocol_json, protocol_version.into())?;
+    write_spec(
+        output,
+        version.to_owned().into(),
+        imported.protocol,
+        &imported.diagnostics,
+    )
 }
 
-impl Tree {
-    /*
-    a a a b b a a a 
-    h j i m o p o e
-    h j i m o p o e
-    h j i m o p o e
-    h j i m o p o e
-    */
-
-    fn new(table: &Table) {
-        let mut tree_map = Vec::new();
-
-        println!("-----------------------");
-
-        for (x,cell) in table.rows[0].cells.iter().enumerate() {
-            if cell == "Field Name" {
-                let mut temp = Vec::new();
-                for row in &table.rows {
-                    temp.push(row.cells[x].to_string());
-                }
-                tree_map.push(temp);
-            }
-        }
-
-        println!("{:?}",tree_map);
-    }
-}
-
-fn main() -> Result<()> {
-    let response = fetch_wiki_page("https://wiki.vg/api.php", "Protocol")?;
-    let wiki_text = response["parse"]["wikitext"]["*"]
-        .as_str()
-        .ok_or(anyhow!("wikitext not present"))?;
-
-    let mut tmap: Vec<Tree> = Vec::new();
-
-    let result = create_configuration().parse(wiki_text);
-
-    result
-        .nodes
-        .iter()
-        .filter_map(|n| match n {
-            Node::Table { rows, .. } => rows
-                .get(0)
-                .and_then(|r| r.cells.get(0))
-                .and_then(|c| c.content.get(0))
-                .filter(|n| match n {
-                    Node::Text {
-                        value: "Packet ID", ..
-                    } => true,
-                    _ => false,
-                })
-                .and(Some(rows)),
-            _ => None,
-        })
-        .for_each(|n| {
-            Tree::new(&Table::new(n));
-            /*n.iter().for_each(|row| {
-                println!("{:#?}", row);
-            });*/
-        });
-    Ok(())
+/// Exports a spec to a `protocol.json` for minecraft-data.
+fn export(args: &[String]) -> Result<bool> {
+    let (path, output) = match args {
+        [path, output] => (path, output),
+        _ => bail!(
+            "Specify the spec and the protocol.json to write.\n{}",
+            USAGE
+        ),
+    };
+    let minecraft = Minecraft::load(path)?;
+
+    let exported = minecraft_data::export(&minecraft.protocol);
+    report(path, &exported.diagnostics);
+    fs::write(
+        output,
+        serde_json::to_string_pretty(&exported.protocol_json)?,
+    )?;
+    Ok(true)
 }
 
-fn fetch_wiki_page(url: &str, page: &str) -> Result<serde_json::Value> {
-    let url = reqwest::Url::parse_with_params(
-        url,
-        &[
-            ("action", "parse"),
-            ("page", page),
-            ("format", "json"),
-            ("prop", "wikitext"),
-        ],
-    )?;
-    let response = reqwest::blocking::get(url)?;
-    Ok(response.json::<serde_json::Value>()?)
+/// Writes a spec of `protocol` for the Minecraft version `version`, in the format of the
+/// output path, and reports what went wrong extracting it and validating the result.
+fn write_spec(
+    output: &str,
+    version: MinecraftVersion,
+    protocol: Protocol,
+    diagnostics: &[Diagnostic],
+) -> Result<bool> {
+    report(output, diagnostics);
+    let major_version = version.split('.').take(2).collect::<Vec<_>>().join(".");
+    let minecraft = Minecraft {
+        spec_version: SPEC_VERSION,
+        version,
+        major_version: major_version.into(),
+        protocol,
+    };
+
+    let format = Format::from_path(output)
+        .ok_or_else(|| anyhow!("`{}` is not a .ron, .json or .yaml file", output))?;
+    let validation = minecraft.diagnostics();
+    let spec = Spec::Minecraft(minecraft)
+        .to_string(format)
+        .map_err(|e| anyhow!("{}: {}", output, e))?;
+    fs::write(output, spec)?;
+
+    report(output, &validation);
+    Ok(!validation.iter().any(Diagnostic::is_error))
 }
 
-fn create_configuration() -> ::parse_wiki_text::Configuration {
-    Configuration::new(&parse_wiki_text::ConfigurationSource {
-        category_namespaces: &["category"],
-        extension_tags: &[
-            "gallery",
-            "indicator",
-            "nowiki",
-            "pre",
-            "ref",
-            "references",
-            "source",
-            "syntaxhighlight",
-        ],
-        file_namespaces: &["file", "image"],
-        link_trail: "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
-        magic_words: &[
-            "EXPECTUNUSEDCATEGORY",
-            "FORCETOC",
-            "HIDDENCAT",
-            "INDEX",
-            "NEWSECTIONLINK",
-            "NOCC",
-            "NOCONTENTCONVERT",
-            "NOEDITSECTION",
-            "NOGALLERY",
-            "NOINDEX",
-            "NONEWSECTIONLINK",
-            "NOTC",
-            "NOTITLECONVERT",
-            "NOTOC",
-            "STATICREDIRECT",
-            "TOC",
-        ],
-        protocols: &[
-            "//",
-            "bitcoin:",
-            "ftp://",
-            "ftps://",
-            "geo:",
-            "git://",
-            "gopher://",
-            "http://",
-            "https://",
-            "irc://",
-            "ircs://",
-            "magnet:",
-            "mailto:",
-            "mms://",
-            "news:",
-            "nntp://",
-            "redis://",
-            "sftp://",
-            "sip:",
-            "sips:",
-            "sms:",
-            "ssh://",
-            "svn://",
-            "tel:",
-            "telnet://",
-            "urn:",
-            "worldwind://",
-            "xmpp:",
-        ],
-        redirect_magic_words: &["REDIRECT"],
-    })
+fn report(path: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic.render(path, ""));
+    }
 }
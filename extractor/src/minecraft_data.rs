@@ -0,0 +1,810 @@
+//! Imports specs from, and exports them to, the `protocol.json` files of
+//! [minecraft-data](https://github.com/PrismarineJS/minecraft-data).
+//!
+//! Packets are read from the `packet` mapper and switch of each stage and direction. Global
+//! containers, bit fields and mappers become shared types, while other global types such as
+//! `string` are inlined wherever they are used.
+
+use anyhow::{anyhow, Context, Result};
+use feather_protocol_spec::*;
+use heck::{CamelCase, SnakeCase};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+mod export;
+pub use export::*;
+
+const STAGES: [(&str, PacketStage); 4] = [
+    ("handshaking", PacketStage::Handshaking),
+    ("status", PacketStage::Status),
+    ("login", PacketStage::Login),
+    ("play", PacketStage::Play),
+];
+
+const DIRECTIONS: [(&str, PacketDirection); 2] = [
+    ("toClient", PacketDirection::Client),
+    ("toServer", PacketDirection::Server),
+];
+
+/// A protocol imported from minecraft-data.
+#[derive(Debug)]
+pub struct Import {
+    pub protocol: Protocol,
+    /// A warning for each type a spec cannot represent, which is imported as the rest of the
+    /// buffer instead.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Imports the `protocol.json` of `version`, which does not name its own protocol version.
+pub fn import(protocol_json: &Value, version: ProtocolVersion) -> Result<Import> {
+    let types = protocol_json
+        .get("types")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("protocol.json has no global types"))?;
+    let mut importer = Importer::new(types);
+
+    let mut packets = BTreeMap::new();
+    for (stage_name, stage) in STAGES.iter() {
+        for (direction_name, direction) in DIRECTIONS.iter() {
+            let local = match protocol_json
+                .pointer(&format!("/{}/{}/types", stage_name, direction_name))
+                .and_then(Value::as_object)
+            {
+                Some(local) => local,
+                None => continue,
+            };
+            let mappings = packet_mappings(local).with_context(|| {
+                format!("Reading the packets of {}.{}", stage_name, direction_name)
+            })?;
+            importer.local = Some(local);
+            for (id, name, type_name) in mappings {
+                let identifier = PacketIdentifier(*direction, *stage, id.into());
+                let packet = importer.packet(identifier.clone(), name, type_name);
+                packets.insert(identifier, packet);
+            }
+        }
+    }
+    importer.local = None;
+    let shared_types = importer.shared_types();
+
+    Ok(Import {
+        protocol: Protocol {
+            version,
+            packets,
+            shared_types,
+        },
+        diagnostics: importer.diagnostics,
+    })
+}
+
+/// Reads the ID, name and type of each packet from the `packet` container of a stage and
+/// direction, which maps the ID to a name and switches on the name to the type.
+fn packet_mappings(local: &Map<String, Value>) -> Result<Vec<(u64, &str, &str)>> {
+    let packet = local
+        .get("packet")
+        .ok_or_else(|| anyhow!("There is no packet type"))?;
+    let names = packet
+        .pointer("/1/0/type/1/mappings")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("The packet type does not map IDs to names"))?;
+    let types = packet
+        .pointer("/1/1/type/1/fields")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("The packet type does not switch on the name"))?;
+
+    names
+        .iter()
+        .map(|(id, name)| {
+            let id = parse_int(id)
+                .filter(|id| *id >= 0)
+                .ok_or_else(|| anyhow!("`{}` is not a packet ID", id))?;
+            let name = name
+                .as_str()
+                .ok_or_else(|| anyhow!("The name of packet {} is not a string", id))?;
+            let type_name = types
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("The packet `{}` has no type", name))?;
+            Ok((id as u64, name, type_name))
+        })
+        .collect()
+}
+
+/// The fields of a container imported so far.
+#[derive(Default)]
+struct Fields {
+    types: IndexMap<FieldName, FieldType>,
+    /// The mappings of mapper fields which a later switch compares against, from the names
+    /// the switch uses to the values on the wire.
+    mappers: BTreeMap<FieldName, BTreeMap<String, Literal>>,
+}
+
+struct Importer<'a> {
+    types: &'a Map<String, Value>,
+    /// The types of the stage and direction whose packets are being imported.
+    local: Option<&'a Map<String, Value>>,
+    /// The global types referenced as shared types so far.
+    shared: BTreeSet<&'a str>,
+    /// The shared types which are referenced but not imported yet.
+    pending: Vec<&'a str>,
+    /// The Rust names of the custom types in the module being imported, which have to differ.
+    names: BTreeSet<String>,
+    /// The names of the structs enclosing the type being imported.
+    scope: Vec<String>,
+    path: Vec<PathSegment>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(types: &'a Map<String, Value>) -> Self {
+        Importer {
+            types,
+            local: None,
+            shared: BTreeSet::new(),
+            pending: Vec::new(),
+            names: BTreeSet::new(),
+            scope: Vec::new(),
+            path: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn at<T>(&mut self, segment: PathSegment, import: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(segment);
+        let result = import(self);
+        self.path.pop();
+        result
+    }
+
+    /// Reports that `name` is `construct`, which a spec cannot represent, and imports it as
+    /// the rest of the buffer.
+    fn unsupported(&mut self, name: &str, construct: String) -> FieldType {
+        self.warning(format!(
+            "\"{}\" is {}, which a spec cannot represent, so it is imported as the rest of the \
+             buffer.",
+            name, construct
+        ));
+        FieldType::Primitive(PrimitiveType::Array {
+            length: ArrayLength::RemainingLength,
+            field_type: Box::new(IntegerType::U8.into()),
+        })
+    }
+
+    fn warning(&mut self, message: String) {
+        let path = SpecPath(self.path.clone());
+        self.diagnostics.push(Diagnostic::warning(path, message));
+    }
+
+    fn definition(&self, reference: &str) -> Option<&'a Value> {
+        self.local
+            .and_then(|local| local.get(reference))
+            .or_else(|| self.types.get(reference))
+    }
+
+    /// Picks a name for a custom type which no other type in the module is generated as,
+    /// prefixing it with the name of the enclosing struct if it has to.
+    fn type_name(&mut self, preferred: &str) -> CustomTypeName {
+        let preferred = preferred.to_snake_case();
+        let mut candidates = vec![preferred.clone()];
+        if let Some(parent) = self.scope.last() {
+            candidates.push(format!("{}_{}", parent, preferred));
+        }
+        let base = candidates.last().cloned().unwrap_or_default();
+        let name = candidates
+            .into_iter()
+            .chain((2..).map(|n| format!("{}_{}", base, n)))
+            .find(|candidate| !self.names.contains(&candidate.to_camel_case()))
+            .expect("some candidate is free");
+        self.names.insert(name.to_camel_case());
+        name.into()
+    }
+
+    fn packet(&mut self, identifier: PacketIdentifier, name: &str, type_name: &str) -> CustomType {
+        self.names.clear();
+        self.names.insert(name.to_camel_case());
+        self.at(PathSegment::Packet(identifier), |importer| {
+            let fields = match importer.definition(type_name).and_then(construct) {
+                Some(("container", fields)) => importer.container(name, fields),
+                _ => {
+                    let placeholder = importer.unsupported(
+                        name,
+                        format!("the type `{}` instead of a container", type_name),
+                    );
+                    vec![(FieldName::from("data".to_owned()), placeholder)]
+                        .into_iter()
+                        .collect()
+                }
+            };
+            custom_struct(name.to_owned().into(), fields)
+        })
+    }
+
+    /// Imports the global types referenced as shared types, sorted by name.
+    fn shared_types(&mut self) -> Vec<CustomType> {
+        self.names.clear();
+        let mut shared_types = Vec::new();
+        while let Some(reference) = self.pending.pop() {
+            let name = reference.to_snake_case();
+            self.names.insert(name.to_camel_case());
+            let definition = &self.types[reference];
+            let shared_type = self.at(PathSegment::SharedType(name.clone().into()), |importer| {
+                importer.custom_type(name.clone().into(), definition)
+            });
+            shared_types.extend(shared_type);
+        }
+        shared_types.sort_by(|a: &CustomType, b| a.name().cmp(b.name()));
+        shared_types
+    }
+
+    /// Imports a container, bit field or mapper, the types which can be shared.
+    fn custom_type(&mut self, name: CustomTypeName, definition: &'a Value) -> Option<CustomType> {
+        match construct(definition)? {
+            ("container", fields) => {
+                let fields = self.container(&name, fields);
+                Some(custom_struct(name, fields))
+            }
+            ("bitfield", fields) => Some(self.bit_field(name, fields)),
+            ("mapper", mapper) => match self.mapper(name.clone(), mapper, &Fields::default()) {
+                Ok(mapper) => Some(mapper),
+                Err(construct) => {
+                    self.unsupported(&name, construct);
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+
+    fn container(&mut self, name: &str, fields: &'a Value) -> IndexMap<FieldName, FieldType> {
+        let mut imported = Fields::default();
+        self.scope.push(name.to_owned());
+        match fields.as_array() {
+            Some(fields) => self.fields(fields, &mut imported),
+            None => {
+                let placeholder = self.unsupported(name, format!("a container of {}", fields));
+                imported.types.insert("data".to_owned().into(), placeholder);
+            }
+        }
+        self.scope.pop();
+        imported.types
+    }
+
+    fn fields(&mut self, fields: &'a [Value], imported: &mut Fields) {
+        let mut compared = BTreeSet::new();
+        for field in fields {
+            compared_fields(&field["type"], &mut compared);
+        }
+
+        for field in fields {
+            let definition = &field["type"];
+            if field.get("anon") == Some(&Value::Bool(true)) {
+                self.anonymous(definition, imported);
+                continue;
+            }
+            let name: FieldName = field["name"]
+                .as_str()
+                .unwrap_or("anonymous")
+                .to_snake_case()
+                .into();
+            let field_type = self.at(
+                PathSegment::Field(name.clone()),
+                |importer| match construct(definition) {
+                    Some(("mapper", mapper)) if compared.contains(&name) => {
+                        let field_type = importer.field_type(&name, &mapper["type"], imported)?;
+                        let mappings = mapper["mappings"]
+                            .as_object()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|(value, mapped)| {
+                                Some((mapped.as_str()?.to_owned(), literal(&field_type, value)?))
+                            })
+                            .collect();
+                        imported.mappers.insert(name.clone(), mappings);
+                        Some(field_type)
+                    }
+                    _ => importer.field_type(&name, definition, imported),
+                },
+            );
+            if let Some(field_type) = field_type {
+                imported.types.insert(name, field_type);
+            }
+        }
+    }
+
+    /// Imports a field without a name, whose fields belong to the enclosing container.
+    fn anonymous(&mut self, definition: &'a Value, imported: &mut Fields) {
+        let definition = match definition {
+            Value::String(reference) => match self.definition(reference) {
+                Some(definition) if definition != "native" => definition,
+                _ => definition,
+            },
+            definition => definition,
+        };
+        match construct(definition) {
+            Some(("container", Value::Array(fields))) => self.fields(fields, imported),
+            Some(("switch", switch)) => {
+                let compare_to = switch["compareTo"].as_str().unwrap_or_default();
+                let name = format!("{}_data", compare_to.to_snake_case());
+                let reported = self.diagnostics.len();
+                let field_type = self.at(PathSegment::Field(name.clone().into()), |importer| {
+                    importer.switch(&name, switch, imported)
+                });
+                match field_type {
+                    // The fields of a container which is only there in one case are each only
+                    // there in that case.
+                    Some(FieldType::Conditional {
+                        condition,
+                        field_type,
+                    }) if matches!(
+                        *field_type,
+                        FieldType::CustomType(CustomType::Struct { .. })
+                    ) =>
+                    {
+                        // Diagnostics about them move to the enclosing container with them.
+                        let depth = self.path.len();
+                        for diagnostic in self.diagnostics[reported..].iter_mut() {
+                            diagnostic.path.0.remove(depth);
+                        }
+                        if let FieldType::CustomType(CustomType::Struct { fields, .. }) =
+                            *field_type
+                        {
+                            for (field_name, field_type) in fields {
+                                let field_type = FieldType::Conditional {
+                                    condition: condition.clone(),
+                                    field_type: Box::new(field_type),
+                                };
+                                imported.types.insert(field_name, field_type);
+                            }
+                        }
+                    }
+                    Some(field_type) => {
+                        imported.types.insert(name.into(), field_type);
+                    }
+                    None => {}
+                }
+            }
+            _ => {
+                let name = "anonymous";
+                let field_type = self.at(PathSegment::Field(name.to_owned().into()), |importer| {
+                    importer.field_type(name, definition, imported)
+                });
+                if let Some(field_type) = field_type {
+                    imported.types.insert(name.to_owned().into(), field_type);
+                }
+            }
+        }
+    }
+
+    /// Imports the type of the field `name`, which is `None` for `void`.
+    fn field_type(
+        &mut self,
+        name: &str,
+        definition: &'a Value,
+        siblings: &Fields,
+    ) -> Option<FieldType> {
+        match definition {
+            Value::String(reference) => self.reference(name, reference, siblings),
+            _ => match construct(definition) {
+                Some((kind, arguments)) => self.construct(name, kind, arguments, siblings),
+                None => Some(self.unsupported(name, format!("the type {}", definition))),
+            },
+        }
+    }
+
+    /// Imports the type of a field which is one of the types of `protocol.json`, rather than a
+    /// type constructed from them.
+    fn reference(
+        &mut self,
+        name: &str,
+        reference: &'a str,
+        siblings: &Fields,
+    ) -> Option<FieldType> {
+        let field_type = match reference {
+            "void" => return None,
+            "bool" => PrimitiveType::Boolean.into(),
+            "UUID" => PrimitiveType::Uuid.into(),
+            "nbt" => PrimitiveType::Nbt.into(),
+            "restBuffer" => PrimitiveType::Array {
+                length: ArrayLength::RemainingLength,
+                field_type: Box::new(IntegerType::U8.into()),
+            }
+            .into(),
+            "f32" => FloatType::F32.into(),
+            "f64" => FloatType::F64.into(),
+            reference => match integer_type(reference) {
+                Some(integer_type) => integer_type.into(),
+                None => match self.definition(reference) {
+                    Some(definition) if definition == "native" => {
+                        self.unsupported(name, format!("the native type `{}`", reference))
+                    }
+                    // Global containers, bit fields and mappers are shared, while packets are not.
+                    Some(definition)
+                        if !self
+                            .local
+                            .into_iter()
+                            .any(|local| local.contains_key(reference))
+                            && matches!(
+                                construct(definition),
+                                Some(("container", _))
+                                    | Some(("bitfield", _))
+                                    | Some(("mapper", _))
+                            ) =>
+                    {
+                        if self.shared.insert(reference) {
+                            self.pending.push(reference);
+                        }
+                        PrimitiveType::Shared(reference.to_snake_case().into()).into()
+                    }
+                    Some(definition) => return self.field_type(name, definition, siblings),
+                    None => self.unsupported(name, format!("the unknown type `{}`", reference)),
+                },
+            },
+        };
+        Some(field_type)
+    }
+
+    fn construct(
+        &mut self,
+        name: &str,
+        kind: &str,
+        arguments: &'a Value,
+        siblings: &Fields,
+    ) -> Option<FieldType> {
+        let field_type = match kind {
+            "container" => {
+                let type_name = self.type_name(name);
+                let fields = self.container(&type_name, arguments);
+                FieldType::CustomType(custom_struct(type_name, fields))
+            }
+            "bitfield" => {
+                let type_name = self.type_name(name);
+                FieldType::CustomType(self.bit_field(type_name, arguments))
+            }
+            "mapper" => {
+                let type_name = self.type_name(name);
+                match self.mapper(type_name, arguments, siblings) {
+                    Ok(mapper) => FieldType::CustomType(mapper),
+                    Err(construct) => self.unsupported(name, construct),
+                }
+            }
+            "switch" => return self.switch(name, arguments, siblings),
+            "option" => match self.field_type(name, arguments, siblings) {
+                Some(field_type) => PrimitiveType::Option(Box::new(field_type)).into(),
+                None => self.unsupported(name, "an option of `void`".to_owned()),
+            },
+            "array" | "buffer" => match self.length(name, arguments, siblings) {
+                Ok(length) => {
+                    let field_type = if kind == "buffer" {
+                        IntegerType::U8.into()
+                    } else {
+                        match self.field_type(name, &arguments["type"], siblings) {
+                            Some(field_type) => field_type,
+                            None => {
+                                return Some(
+                                    self.unsupported(name, "an array of `void`".to_owned()),
+                                )
+                            }
+                        }
+                    };
+                    PrimitiveType::Array {
+                        length,
+                        field_type: Box::new(field_type),
+                    }
+                    .into()
+                }
+                Err(construct) => self.unsupported(name, construct),
+            },
+            "pstring" => match arguments["countType"].as_str() {
+                // Strings are limited to 32767 characters unless the wiki says otherwise.
+                Some("varint") => PrimitiveType::String(32767).into(),
+                _ => self.unsupported(name, format!("a string counted by {}", arguments)),
+            },
+            "entityMetadataLoop" => PrimitiveType::EntityMetadata.into(),
+            kind => self.unsupported(name, format!("a `{}`", kind)),
+        };
+        Some(field_type)
+    }
+
+    fn length(
+        &mut self,
+        name: &str,
+        arguments: &'a Value,
+        siblings: &Fields,
+    ) -> Result<ArrayLength, String> {
+        if arguments.get("rest") == Some(&Value::Bool(true)) {
+            return Ok(ArrayLength::RemainingLength);
+        }
+        if let Some(count_type) = arguments.get("countType") {
+            return match self.field_type(name, count_type, siblings) {
+                Some(FieldType::Integer(integer_type)) => {
+                    Ok(ArrayLength::Prefixed(Box::new(integer_type)))
+                }
+                _ => Err(format!("an array counted by {}", count_type)),
+            };
+        }
+        match arguments.get("count") {
+            Some(Value::Number(count)) => count
+                .as_u64()
+                .map(ArrayLength::FixedLength)
+                .ok_or_else(|| format!("an array of {} elements", count)),
+            Some(Value::String(count)) => {
+                let count_field: FieldName = count.to_snake_case().into();
+                if siblings.types.contains_key(&count_field) {
+                    Ok(ArrayLength::Field(count_field))
+                } else {
+                    Err(format!(
+                        "an array counted by `{}` from outside of its container",
+                        count
+                    ))
+                }
+            }
+            _ => Err(format!("an array without a count in {}", arguments)),
+        }
+    }
+
+    /// Imports a mapper which names each value of an integer, boolean or string as an enum
+    /// of units.
+    fn mapper(
+        &mut self,
+        name: CustomTypeName,
+        arguments: &'a Value,
+        siblings: &Fields,
+    ) -> Result<CustomType, String> {
+        let variant = self
+            .field_type(&name, &arguments["type"], siblings)
+            .ok_or_else(|| "a mapper of `void`".to_owned())?;
+        let mut variants = BTreeMap::new();
+        for (value, variant_name) in arguments["mappings"].as_object().into_iter().flatten() {
+            let literal = literal(&variant, value)
+                .ok_or_else(|| format!("a mapper of {} with the value `{}`", variant, value))?;
+            variants.insert(literal, variant_name.as_str().unwrap_or(value));
+        }
+
+        self.scope.push(name.to_string());
+        let variants = variants
+            .into_iter()
+            .map(|(literal, variant_name)| (literal, custom_unit(self.type_name(variant_name))))
+            .collect();
+        self.scope.pop();
+        Ok(CustomType::Enum {
+            name,
+            attributes: Attributes::default(),
+            variant: Box::new(variant),
+            variants,
+        })
+    }
+
+    /// Imports a switch on an earlier field as a conditional field if only one case holds a
+    /// value, or as an enum keyed by that field otherwise.
+    fn switch(&mut self, name: &str, arguments: &'a Value, siblings: &Fields) -> Option<FieldType> {
+        let compare_to = arguments["compareTo"].as_str().unwrap_or_default();
+        let key: FieldName = compare_to.to_snake_case().into();
+        if compare_to.starts_with('$')
+            || compare_to.contains('/')
+            || !siblings.types.contains_key(&key)
+        {
+            return Some(self.unsupported(
+                name,
+                format!("a switch on `{}` from outside of its container", compare_to),
+            ));
+        }
+
+        let mut cases = Vec::new();
+        for (value, case) in arguments["fields"].as_object().into_iter().flatten() {
+            match case_literal(siblings, &key, value) {
+                Some(literal) => cases.push((value, literal, case)),
+                None => {
+                    return Some(self.unsupported(
+                        name,
+                        format!("a switch on `{}` with the case `{}`", compare_to, value),
+                    ))
+                }
+            }
+        }
+        let is_void = |case: &Value| case == "void";
+        let default = arguments.get("default").filter(|default| !is_void(default));
+        let values: Vec<_> = cases.iter().filter(|(_, _, case)| !is_void(case)).collect();
+
+        let field_type = match (default, values.as_slice(), cases.as_slice()) {
+            (None, [], _) => return None,
+            (None, [(_, literal, case)], _) => FieldType::Conditional {
+                condition: Condition::Equal(key, literal.clone()),
+                field_type: Box::new(self.field_type(name, case, siblings)?),
+            },
+            (Some(default), [], [(_, literal, _)]) => FieldType::Conditional {
+                condition: Condition::NotEqual(key, literal.clone()),
+                field_type: Box::new(self.field_type(name, default, siblings)?),
+            },
+            (None, _, _) => {
+                let type_name = self.type_name(name);
+                self.scope.push(type_name.to_string());
+                let mut variants = BTreeMap::new();
+                for (value, literal, case) in cases.iter() {
+                    // Cases of mappers are already names.
+                    let preferred = if siblings.mappers.contains_key(&key) {
+                        value.to_string()
+                    } else {
+                        format!("{}_{}", *type_name, value)
+                    };
+                    let variant = self.at(PathSegment::Variant(literal.clone()), |importer| {
+                        importer.variant(&preferred, case, siblings)
+                    });
+                    variants.insert(literal.clone(), variant);
+                }
+                self.scope.pop();
+                // Unlike a switch, an enum fails to read values without a case. Mappers name
+                // every value they know of, so only switches on plain integers are reported.
+                if !siblings.mappers.contains_key(&key)
+                    && matches!(siblings.types[&key].unannotated(), FieldType::Integer(_))
+                {
+                    let values: Vec<_> = cases.iter().map(|(value, _, _)| value.as_str()).collect();
+                    self.warning(format!(
+                        "\"{}\" is an enum of the cases {} of `{}`, so other values of `{}` fail \
+                         to read.",
+                        name,
+                        values.join(", "),
+                        compare_to,
+                        compare_to
+                    ));
+                }
+                FieldType::CustomType(CustomType::Enum {
+                    name: type_name,
+                    attributes: Attributes::default(),
+                    variant: Box::new(FieldType::Key(key)),
+                    variants,
+                })
+            }
+            (Some(_), _, _) => self.unsupported(
+                name,
+                format!(
+                    "a switch on `{}` with a default next to other cases",
+                    compare_to
+                ),
+            ),
+        };
+        Some(field_type)
+    }
+
+    fn variant(&mut self, preferred: &str, case: &'a Value, siblings: &Fields) -> CustomType {
+        let name = self.type_name(preferred);
+        match construct(case) {
+            Some(("container", fields)) => {
+                let fields = self.container(&name, fields);
+                custom_struct(name, fields)
+            }
+            _ => match self.field_type(&name, case, siblings) {
+                Some(field_type) => {
+                    let fields = vec![(FieldName::from("value".to_owned()), field_type)];
+                    custom_struct(name, fields.into_iter().collect())
+                }
+                None => custom_unit(name),
+            },
+        }
+    }
+
+    fn bit_field(&mut self, name: CustomTypeName, fields: &'a Value) -> CustomType {
+        let mut bit_fields = IndexMap::new();
+        for field in fields.as_array().into_iter().flatten() {
+            let field_name = field["name"]
+                .as_str()
+                .unwrap_or("anonymous")
+                .to_snake_case();
+            let size = field["size"].as_u64().unwrap_or(0);
+            let signed = field["signed"].as_bool().unwrap_or(false);
+            let width = size as u8;
+            let bit_field = match (size, signed) {
+                (1..=8, true) => BitField::I8(width),
+                (1..=8, false) => BitField::U8(width),
+                (9..=16, true) => BitField::I16(width),
+                (9..=16, false) => BitField::U16(width),
+                (17..=32, true) => BitField::I32(width),
+                (17..=32, false) => BitField::U32(width),
+                (33..=64, true) => BitField::I64(width),
+                (33..=64, false) => BitField::U64(width),
+                _ => {
+                    self.at(PathSegment::Field(field_name.clone().into()), |importer| {
+                        importer.unsupported(&field_name, format!("a bit field of {} bits", size))
+                    });
+                    continue;
+                }
+            };
+            bit_fields.insert(field_name.into(), bit_field);
+        }
+        CustomType::BitField {
+            name,
+            attributes: Attributes::default(),
+            fields: bit_fields,
+        }
+    }
+}
+
+/// Splits a constructed type such as `["array", {..}]` into its kind and arguments.
+fn construct(definition: &Value) -> Option<(&str, &Value)> {
+    match definition.as_array()?.as_slice() {
+        [Value::String(kind), arguments] => Some((kind, arguments)),
+        _ => None,
+    }
+}
+
+/// Collects the fields of a container which switches among its `fields` compare against.
+fn compared_fields(definition: &Value, compared: &mut BTreeSet<FieldName>) {
+    match construct(definition) {
+        Some(("switch", switch)) => {
+            if let Some(compare_to) = switch["compareTo"].as_str() {
+                compared.insert(compare_to.to_snake_case().into());
+            }
+            for case in switch["fields"].as_object().into_iter().flatten() {
+                compared_fields(case.1, compared);
+            }
+        }
+        Some(("option", field_type)) => compared_fields(field_type, compared),
+        Some(("array", array)) => compared_fields(&array["type"], compared),
+        // Anonymous containers share the fields of the enclosing one.
+        Some(("container", Value::Array(fields))) => {
+            for field in fields {
+                if field.get("anon") == Some(&Value::Bool(true)) {
+                    compared_fields(&field["type"], compared);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn integer_type(reference: &str) -> Option<IntegerType> {
+    Some(match reference {
+        "u8" => IntegerType::U8,
+        "i8" => IntegerType::I8,
+        "u16" => IntegerType::U16,
+        "i16" => IntegerType::I16,
+        "u32" => IntegerType::U32,
+        "i32" => IntegerType::I32,
+        "u64" => IntegerType::U64,
+        "i64" => IntegerType::I64,
+        "varint" => IntegerType::VarInt,
+        "varlong" => IntegerType::VarLong,
+        _ => return None,
+    })
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal number.
+fn parse_int(value: &str) -> Option<i64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// The literal a case of a switch or key of a mapper `value` stands for, if `field_type` is
+/// an integer, boolean or string.
+fn literal(field_type: &FieldType, value: &str) -> Option<Literal> {
+    match field_type.unannotated() {
+        FieldType::Primitive(PrimitiveType::Boolean) => value.parse().ok().map(Literal::Boolean),
+        FieldType::Integer(_) => parse_int(value).map(Literal::Int),
+        FieldType::Primitive(PrimitiveType::String(_)) => Some(Literal::String(value.to_owned())),
+        _ => None,
+    }
+}
+
+/// The literal the case `value` of a switch on the field `key` stands for.
+fn case_literal(siblings: &Fields, key: &FieldName, value: &str) -> Option<Literal> {
+    match siblings.mappers.get(key) {
+        Some(mappings) => mappings.get(value).cloned(),
+        None => literal(siblings.types.get(key)?, value),
+    }
+}
+
+fn custom_struct(name: CustomTypeName, fields: IndexMap<FieldName, FieldType>) -> CustomType {
+    CustomType::Struct {
+        name,
+        attributes: Attributes::default(),
+        fields,
+    }
+}
+
+fn custom_unit(name: CustomTypeName) -> CustomType {
+    CustomType::Unit {
+        name,
+        attributes: Attributes::default(),
+    }
+}
@@ -0,0 +1,426 @@
+use super::{DIRECTIONS, STAGES};
+use feather_protocol_spec::*;
+use heck::MixedCase;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// The types of minecraft-data which exported specs use without defining them.
+const NATIVE_TYPES: [&str; 26] = [
+    "varint",
+    "varlong",
+    "pstring",
+    "u8",
+    "i8",
+    "u16",
+    "i16",
+    "u32",
+    "i32",
+    "u64",
+    "i64",
+    "f32",
+    "f64",
+    "bool",
+    "UUID",
+    "buffer",
+    "option",
+    "mapper",
+    "bitfield",
+    "container",
+    "switch",
+    "void",
+    "array",
+    "restBuffer",
+    "nbt",
+    "entityMetadata",
+];
+
+/// A protocol exported to minecraft-data.
+#[derive(Debug)]
+pub struct Export {
+    pub protocol_json: Value,
+    /// A warning for each type `protocol.json` cannot represent, which is exported as the
+    /// rest of the buffer instead.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Exports `protocol` as a `protocol.json`.
+///
+/// Enums become mappers, or switches on a mapper when their variants hold fields, bit fields
+/// and bit flags become bit fields, and shared types become global types. Everything a spec
+/// knows beyond the wire format, such as names of nested types, documentation and the
+/// maximum length of strings, is left out.
+pub fn export(protocol: &Protocol) -> Export {
+    let mut exporter = Exporter::default();
+
+    let mut types = Map::new();
+    for native in NATIVE_TYPES.iter() {
+        types.insert((*native).to_owned(), json!("native"));
+    }
+    types.insert(
+        "string".to_owned(),
+        json!(["pstring", { "countType": "varint" }]),
+    );
+    for shared_type in protocol.shared_types.iter() {
+        let name = shared_type.name();
+        let definition = exporter.at(
+            PathSegment::SharedType(name.to_owned().into()),
+            |exporter| exporter.custom_type(name, shared_type),
+        );
+        types.insert(name.to_mixed_case(), definition);
+    }
+
+    let mut protocol_json = Map::new();
+    protocol_json.insert("types".to_owned(), Value::Object(types));
+    for (stage_name, stage) in STAGES.iter() {
+        let mut directions = Map::new();
+        for (direction_name, direction) in DIRECTIONS.iter() {
+            let mut types = Map::new();
+            let mut mappings = Map::new();
+            let mut switch = Map::new();
+            for (identifier, packet) in protocol.packets.iter().filter(|(identifier, _)| {
+                identifier.stage() == *stage && identifier.direction() == *direction
+            }) {
+                let name = packet.name();
+                let type_name = format!("packet_{}", name);
+                let definition = exporter.at(PathSegment::Packet(identifier.clone()), |exporter| {
+                    exporter.custom_type(name, packet)
+                });
+                types.insert(type_name.clone(), definition);
+                mappings.insert(format!("{:#04x}", *identifier.id()), json!(name));
+                switch.insert(name.to_owned(), json!(type_name));
+            }
+            types.insert(
+                "packet".to_owned(),
+                json!(["container", [
+                    { "name": "name", "type": ["mapper", { "type": "varint", "mappings": mappings }] },
+                    { "name": "params", "type": ["switch", { "compareTo": "name", "fields": switch }] },
+                ]]),
+            );
+            directions.insert((*direction_name).to_owned(), json!({ "types": types }));
+        }
+        protocol_json.insert((*stage_name).to_owned(), Value::Object(directions));
+    }
+
+    Export {
+        protocol_json: Value::Object(protocol_json),
+        diagnostics: exporter.diagnostics,
+    }
+}
+
+#[derive(Default)]
+struct Exporter {
+    /// The field names of the containers enclosing the type being exported, innermost last,
+    /// which switches find the field they compare against in.
+    scope: Vec<Vec<FieldName>>,
+    path: Vec<PathSegment>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Exporter {
+    fn at<T>(&mut self, segment: PathSegment, export: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(segment);
+        let result = export(self);
+        self.path.pop();
+        result
+    }
+
+    /// Reports that `name` is `construct`, which `protocol.json` cannot represent, and exports
+    /// it as the rest of the buffer.
+    fn unsupported(&mut self, name: &str, construct: String) -> Value {
+        let path = SpecPath(self.path.clone());
+        self.diagnostics.push(Diagnostic::warning(
+            path,
+            format!(
+                "\"{}\" is {}, which protocol.json cannot represent, so it is exported as the \
+                 rest of the buffer.",
+                name, construct
+            ),
+        ));
+        json!("restBuffer")
+    }
+
+    fn custom_type(&mut self, name: &str, custom_type: &CustomType) -> Value {
+        match custom_type {
+            CustomType::Struct { fields, .. } => self.container(fields),
+            CustomType::Enum {
+                variant, variants, ..
+            } => self.enumeration(name, variant, variants),
+            CustomType::BitField { fields, .. } => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|(field_name, field)| {
+                        json!({
+                            "name": field_name.to_mixed_case(),
+                            "size": field.width(),
+                            "signed": field.is_signed(),
+                        })
+                    })
+                    .collect();
+                json!(["bitfield", fields])
+            }
+            CustomType::BitFlags {
+                field_type, flags, ..
+            } => self.bit_flags(name, field_type, flags),
+            CustomType::Unit { .. } => json!("void"),
+        }
+    }
+
+    fn container(&mut self, fields: &IndexMap<FieldName, FieldType>) -> Value {
+        self.scope.push(fields.keys().cloned().collect());
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(field_name, field_type)| {
+                let field_type = self.at(PathSegment::Field(field_name.clone()), |exporter| {
+                    exporter.field_type(field_name, field_type)
+                });
+                json!({ "name": field_name.to_mixed_case(), "type": field_type })
+            })
+            .collect();
+        self.scope.pop();
+        json!(["container", fields])
+    }
+
+    fn field_type(&mut self, name: &str, field_type: &FieldType) -> Value {
+        match field_type {
+            FieldType::Integer(integer_type) => json!(integer_name(integer_type)),
+            FieldType::Float(FloatType::F32) => json!("f32"),
+            FieldType::Float(FloatType::F64) => json!("f64"),
+            FieldType::Primitive(primitive_type) => self.primitive_type(name, primitive_type),
+            FieldType::CustomType(custom_type) => self.custom_type(name, custom_type),
+            FieldType::Conditional {
+                condition,
+                field_type,
+            } => {
+                let compare_to = self.compare_to(condition.field());
+                let case = case(condition.literal());
+                let field_type = self.field_type(name, field_type);
+                match condition {
+                    Condition::Equal(..) => json!(["switch", {
+                        "compareTo": compare_to,
+                        "fields": { case: field_type },
+                        "default": "void",
+                    }]),
+                    Condition::NotEqual(..) => json!(["switch", {
+                        "compareTo": compare_to,
+                        "fields": { case: "void" },
+                        "default": field_type,
+                    }]),
+                }
+            }
+            // Documentation and constants are not part of the wire format.
+            FieldType::Annotated { field_type, .. } | FieldType::Const { field_type, .. } => {
+                self.field_type(name, field_type)
+            }
+            FieldType::Key(_) => self.unsupported(name, "a key outside of an enum".to_owned()),
+        }
+    }
+
+    fn primitive_type(&mut self, name: &str, primitive_type: &PrimitiveType) -> Value {
+        match primitive_type {
+            PrimitiveType::Boolean => json!("bool"),
+            PrimitiveType::Uuid => json!("UUID"),
+            // Strings in protocol.json have no maximum length.
+            PrimitiveType::String(_) => json!("string"),
+            PrimitiveType::Nbt => json!("nbt"),
+            PrimitiveType::Option(field_type) => {
+                json!(["option", self.field_type(name, field_type)])
+            }
+            PrimitiveType::Shared(shared_name) => json!(shared_name.to_mixed_case()),
+            PrimitiveType::EntityMetadata => {
+                let path = SpecPath(self.path.clone());
+                self.diagnostics.push(Diagnostic::warning(
+                    path,
+                    format!(
+                        "\"{}\" is entity metadata, whose values a spec leaves to the runtime, so \
+                         it is exported as the native type `entityMetadata`.",
+                        name
+                    ),
+                ));
+                json!("entityMetadata")
+            }
+            PrimitiveType::Array { length, field_type } => {
+                let bytes = **field_type == FieldType::Integer(IntegerType::U8);
+                let mut arguments = Map::new();
+                match length {
+                    ArrayLength::RemainingLength if bytes => return json!("restBuffer"),
+                    ArrayLength::Prefixed(integer_type) => {
+                        arguments.insert("countType".to_owned(), json!(integer_name(integer_type)));
+                    }
+                    ArrayLength::FixedLength(count) => {
+                        arguments.insert("count".to_owned(), json!(count));
+                    }
+                    ArrayLength::Field(field) => {
+                        arguments.insert("count".to_owned(), json!(field.to_mixed_case()));
+                    }
+                    ArrayLength::RemainingLength => {
+                        return self.unsupported(
+                            name,
+                            format!("an array of {} filling the rest of the buffer", field_type),
+                        )
+                    }
+                    ArrayLength::Terminated(integer_type, terminator) => {
+                        return self.unsupported(
+                            name,
+                            format!(
+                                "an array terminated by the {:?} {}",
+                                integer_type, terminator
+                            ),
+                        )
+                    }
+                }
+                if bytes {
+                    json!(["buffer", arguments])
+                } else {
+                    arguments.insert("type".to_owned(), self.field_type(name, field_type));
+                    json!(["array", arguments])
+                }
+            }
+        }
+    }
+
+    /// Exports an enum whose variants are all units as a mapper, one keyed by an earlier field
+    /// as a switch on that field, and any other enum as a container of a mapper and a switch
+    /// on it.
+    fn enumeration(
+        &mut self,
+        name: &str,
+        variant: &FieldType,
+        variants: &BTreeMap<Literal, CustomType>,
+    ) -> Value {
+        if let FieldType::Key(key) = variant {
+            let compare_to = self.compare_to(key);
+            let cases: Map<_, _> = variants
+                .iter()
+                .map(|(literal, variant)| {
+                    let case = case(literal);
+                    let variant = self.at(PathSegment::Variant(literal.clone()), |exporter| {
+                        exporter.custom_type(variant.name(), variant)
+                    });
+                    (case, variant)
+                })
+                .collect();
+            return json!(["switch", { "compareTo": compare_to, "fields": cases }]);
+        }
+
+        let mappings: Map<_, _> = variants
+            .iter()
+            .map(|(literal, variant)| (case(literal), json!(variant.name())))
+            .collect();
+        let mapper = json!(["mapper", {
+            "type": self.field_type(name, variant),
+            "mappings": mappings,
+        }]);
+        if variants
+            .values()
+            .all(|variant| matches!(variant, CustomType::Unit { .. }))
+        {
+            return mapper;
+        }
+
+        self.scope.push(vec![FieldName::from("key".to_owned())]);
+        let cases: Map<_, _> = variants
+            .iter()
+            .map(|(literal, variant)| {
+                let variant_json = self.at(PathSegment::Variant(literal.clone()), |exporter| {
+                    exporter.custom_type(variant.name(), variant)
+                });
+                (variant.name().to_owned(), variant_json)
+            })
+            .collect();
+        self.scope.pop();
+        json!(["container", [
+            { "name": "key", "type": mapper },
+            { "anon": true, "type": ["switch", { "compareTo": "key", "fields": cases }] },
+        ]])
+    }
+
+    /// Exports bit flags as a bit field of a bit for each flag, from the most significant bit
+    /// down, with the bits between the flags grouped into unused fields.
+    fn bit_flags(
+        &mut self,
+        name: &str,
+        field_type: &IntegerType,
+        flags: &BTreeMap<u64, FlagName>,
+    ) -> Value {
+        let bits = match field_type {
+            IntegerType::U8 | IntegerType::I8 => 8,
+            IntegerType::U16 | IntegerType::I16 => 16,
+            IntegerType::U32 | IntegerType::I32 => 32,
+            IntegerType::U64 | IntegerType::I64 => 64,
+            IntegerType::VarInt | IntegerType::VarLong => {
+                return self.unsupported(name, format!("bit flags in a {:?}", field_type))
+            }
+        };
+        if let Some((mask, _)) = flags
+            .iter()
+            .find(|(mask, _)| !mask.is_power_of_two() || mask.trailing_zeros() >= bits)
+        {
+            return self.unsupported(
+                name,
+                format!(
+                    "bit flags with a flag {:#x} which is not a single bit",
+                    mask
+                ),
+            );
+        }
+
+        let mut fields = Vec::new();
+        let mut unused = 0;
+        for bit in (0..bits).rev() {
+            match flags.get(&(1 << bit)) {
+                Some(flag) => {
+                    if unused > 0 {
+                        fields.push(json!({
+                            "name": format!("unused{}", bit + 1),
+                            "size": unused,
+                            "signed": false,
+                        }));
+                        unused = 0;
+                    }
+                    fields
+                        .push(json!({ "name": flag.to_mixed_case(), "size": 1, "signed": false }));
+                }
+                None => unused += 1,
+            }
+        }
+        if unused > 0 {
+            fields.push(json!({ "name": "unused0", "size": unused, "signed": false }));
+        }
+        json!(["bitfield", fields])
+    }
+
+    /// The path from the type being exported to the earlier field `field`, going up a level
+    /// with `../` for each container between them.
+    fn compare_to(&self, field: &FieldName) -> String {
+        let levels = self
+            .scope
+            .iter()
+            .rev()
+            .position(|fields| fields.contains(field))
+            .unwrap_or(0);
+        format!("{}{}", "../".repeat(levels), field.to_mixed_case())
+    }
+}
+
+fn integer_name(integer_type: &IntegerType) -> &'static str {
+    match integer_type {
+        IntegerType::U8 => "u8",
+        IntegerType::I8 => "i8",
+        IntegerType::U16 => "u16",
+        IntegerType::I16 => "i16",
+        IntegerType::U32 => "u32",
+        IntegerType::I32 => "i32",
+        IntegerType::U64 => "u64",
+        IntegerType::I64 => "i64",
+        IntegerType::VarInt => "varint",
+        IntegerType::VarLong => "varlong",
+    }
+}
+
+/// The case of a switch, or key of a mapper, which matches `literal`.
+fn case(literal: &Literal) -> String {
+    match literal {
+        Literal::String(string) => string.clone(),
+        literal => literal.to_string(),
+    }
+}
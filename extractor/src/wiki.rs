@@ -0,0 +1,157 @@
+//! Extracts the packet tables of https://wiki.vg/Protocol from its wikitext.
+//!
+//! Cells spanning several rows or columns are repeated in every row and column they span, so
+//! each table comes out as a plain grid of text, which [`extract`] turns into a protocol.
+
+use crate::create_configuration;
+use parse_wiki_text::{Node, TableRow};
+
+mod extract;
+pub use extract::*;
+
+/// A table with its spans expanded, one `String` per cell.
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
+pub struct Table {
+    pub rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
+pub struct Row {
+    pub cells: Vec<String>,
+}
+
+/// A table whose first cell is "Packet ID", along with the headings of the sections it is in,
+/// outermost first, e.g. `["Play", "Clientbound", "Block Change"]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketTable {
+    pub sections: Vec<String>,
+    pub table: Table,
+}
+
+/// Every packet table of the page, in the order they appear in.
+pub fn packet_tables(wikitext: &str) -> Vec<PacketTable> {
+    let output = create_configuration().parse(wikitext);
+
+    let mut headings: Vec<(u8, String)> = Vec::new();
+    let mut tables = Vec::new();
+    for node in output.nodes.iter() {
+        match node {
+            Node::Heading { level, nodes, .. } => {
+                while matches!(headings.last(), Some((outer, _)) if outer >= level) {
+                    headings.pop();
+                }
+                headings.push((*level, text(nodes)));
+            }
+            Node::Table { rows, .. } => {
+                let table = Table::new(rows);
+                let first_cell = table.rows.first().and_then(|row| row.cells.first());
+                if matches!(first_cell, Some(cell) if cell == "Packet ID") {
+                    tables.push(PacketTable {
+                        sections: headings
+                            .iter()
+                            .map(|(_, heading)| heading.clone())
+                            .collect(),
+                        table,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    tables
+}
+
+impl Table {
+    pub fn new(rows: &[TableRow<'_>]) -> Self {
+        let mut grid: Vec<Vec<Option<String>>> = vec![Vec::new(); rows.len()];
+
+        for (y, row) in rows.iter().enumerate() {
+            let mut x = 0;
+            for cell in row.cells.iter() {
+                // Skip the cells filled by spans from earlier rows.
+                while matches!(grid[y].get(x), Some(Some(_))) {
+                    x += 1;
+                }
+                let attributes = cell.attributes.as_deref().map(text).unwrap_or_default();
+                let row_span = span(&attributes, "rowspan").min(rows.len() - y);
+                let column_span = span(&attributes, "colspan");
+
+                let content = text(&cell.content);
+                for spanned in grid[y..y + row_span].iter_mut() {
+                    if spanned.len() < x + column_span {
+                        spanned.resize(x + column_span, None);
+                    }
+                    for spanned_cell in spanned[x..x + column_span].iter_mut() {
+                        *spanned_cell = Some(content.clone());
+                    }
+                }
+                x += column_span;
+            }
+        }
+
+        let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+        let rows = grid
+            .into_iter()
+            .map(|mut cells| {
+                cells.resize(width, None);
+                Row {
+                    cells: cells.into_iter().map(Option::unwrap_or_default).collect(),
+                }
+            })
+            .collect();
+        Self { rows }
+    }
+}
+
+/// The value of a `rowspan` or `colspan` attribute, quoted or not, which defaults to 1.
+fn span(attributes: &str, name: &str) -> usize {
+    attributes
+        .find(name)
+        .map(|start| &attributes[start + name.len()..])
+        .and_then(|value| value.trim_start().strip_prefix('='))
+        .and_then(|value| {
+            let value = value.trim_start().trim_start_matches(&['"', '\''][..]);
+            let end = value
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(value.len());
+            value[..end].parse().ok()
+        })
+        .filter(|&span| span > 0)
+        .unwrap_or(1)
+}
+
+/// The text the nodes render as, with whitespace collapsed and markup such as links reduced
+/// to the text they show.
+pub fn text(nodes: &[Node<'_>]) -> String {
+    let mut text = String::new();
+    push_text(nodes, &mut text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn push_text(nodes: &[Node<'_>], text: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text { value, .. } => text.push_str(value),
+            Node::CharacterEntity { character, .. } => text.push(*character),
+            Node::Link { text: nodes, .. }
+            | Node::Preformatted { nodes, .. }
+            | Node::Heading { nodes, .. } => push_text(nodes, text),
+            Node::ExternalLink { nodes, .. } => {
+                // The URL comes first, followed by the text shown for it.
+                let mut link = String::new();
+                push_text(nodes, &mut link);
+                text.push_str(link.split_once(' ').map_or(&link[..], |(_, shown)| shown));
+            }
+            // Templates such as `{{Type|VarInt}}` show their last parameter.
+            Node::Template { parameters, .. } => {
+                if let Some(parameter) = parameters.last() {
+                    push_text(&parameter.value, text);
+                }
+            }
+            Node::Tag { name, nodes, .. } if name != "ref" => push_text(nodes, text),
+            Node::StartTag { name, .. } if name == "br" => text.push(' '),
+            Node::ParagraphBreak { .. } => text.push(' '),
+            _ => {}
+        }
+    }
+}
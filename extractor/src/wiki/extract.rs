@@ -0,0 +1,549 @@
+use super::PacketTable;
+use feather_protocol_spec::*;
+use heck::CamelCase;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The shared types which wiki types such as "Position" refer to, as the specs of this
+/// repository define them.
+const SHARED_TYPES: &str = r#"[
+    Struct(name: "chat", fields: {"json": String(32767)}),
+    Struct(name: "identifier", fields: {"name": String(32767)}),
+    BitField(name: "position", fields: {"x": I32(26), "z": I32(26), "y": I16(12)}),
+    Enum(name: "slot", variant: Boolean, variants: {
+        false: Unit(name: "empty"),
+        true: Struct(name: "item", fields: {"id": VarInt, "count": VarInt, "nbt": Nbt}),
+    }),
+]"#;
+
+/// A protocol extracted from the packet tables of wiki.vg.
+#[derive(Debug)]
+pub struct Extract {
+    pub protocol: Protocol,
+    /// A warning for each table, type or condition which could not be read exactly, along
+    /// with what was extracted instead.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The Minecraft and protocol version the page is about, which it gives in the link to the
+/// protocol version numbers, e.g. `[[Protocol version numbers|1.15.2, protocol 578]]`.
+pub fn page_version(wikitext: &str) -> Option<(MinecraftVersion, ProtocolVersion)> {
+    let (before, after) = wikitext.split_once(", protocol ")?;
+    let protocol_version = after
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse::<u64>()
+        .ok()?;
+    let start = before
+        .rfind(|c: char| !c.is_ascii_digit() && c != '.')
+        .map_or(0, |index| index + 1);
+    let version = &before[start..];
+    if version.is_empty() {
+        return None;
+    }
+    Some((version.to_owned().into(), protocol_version.into()))
+}
+
+/// Builds a protocol from the packet tables of the page, which is about `version`.
+///
+/// The stage and direction of a packet come from the sections its table is in, its name from
+/// the heading right above it and its fields from the "Field Name" and "Field Type" columns.
+/// Fields spanning several "Field Name" columns are nested into structs, and the notes of a
+/// field become its documentation.
+pub fn extract(tables: &[PacketTable], version: ProtocolVersion) -> Extract {
+    let mut extractor = Extractor::default();
+
+    let mut packets = BTreeMap::new();
+    let mut packet_names = BTreeSet::new();
+    for packet_table in tables {
+        let (identifier, heading) = match identify(packet_table) {
+            Ok(identified) => identified,
+            Err(message) => {
+                extractor.diagnostics.push(Diagnostic::warning(
+                    SpecPath::default(),
+                    format!(
+                        "The table in \"{}\" is left out, as {}.",
+                        packet_table.sections.join(" / "),
+                        message
+                    ),
+                ));
+                continue;
+            }
+        };
+        if packets.contains_key(&identifier) {
+            extractor.diagnostics.push(Diagnostic::warning(
+                SpecPath(vec![PathSegment::Packet(identifier)]),
+                format!(
+                    "The table in \"{}\" is left out, as an earlier table has the same ID.",
+                    packet_table.sections.join(" / ")
+                ),
+            ));
+            continue;
+        }
+
+        // Packets named after their direction, such as "Keep Alive (clientbound)", drop it.
+        let short_name = match heading.rfind(" (") {
+            Some(index) if heading.ends_with(')') => &heading[..index],
+            _ => heading,
+        };
+        let mut name = snake_case(short_name);
+        if !packet_names.insert((identifier.direction(), identifier.stage(), name.clone())) {
+            name = snake_case(heading);
+            packet_names.insert((identifier.direction(), identifier.stage(), name.clone()));
+        }
+
+        extractor.path = vec![PathSegment::Packet(identifier.clone())];
+        extractor.type_names.clear();
+        let packet = extractor.packet(packet_table, name, heading);
+        packets.insert(identifier, packet);
+    }
+
+    let shared_types: Vec<CustomType> =
+        ron::de::from_str(SHARED_TYPES).expect("the shared types are valid");
+    let shared_types = shared_types
+        .into_iter()
+        .filter(|shared_type| extractor.shared.contains(shared_type.name()))
+        .collect();
+
+    Extract {
+        protocol: Protocol {
+            version,
+            packets,
+            shared_types,
+        },
+        diagnostics: extractor.diagnostics,
+    }
+}
+
+/// The identifier of the packet in a table, and the heading naming it.
+fn identify(packet_table: &PacketTable) -> Result<(PacketIdentifier, &str), String> {
+    let sections = &packet_table.sections;
+    let stage = sections
+        .iter()
+        .find_map(|section| section.parse::<PacketStage>().ok())
+        .ok_or("it is not in the section of a stage")?;
+    let direction = sections
+        .iter()
+        .find_map(|section| match section.as_str() {
+            "Clientbound" => Some(PacketDirection::Client),
+            "Serverbound" => Some(PacketDirection::Server),
+            _ => None,
+        })
+        .ok_or("it is not in a Clientbound or Serverbound section")?;
+    let heading = sections.last().ok_or("it has no heading")?;
+
+    let id = packet_table
+        .table
+        .rows
+        .get(1)
+        .and_then(|row| row.cells.first())
+        .ok_or("it has no rows")?;
+    let id = id
+        .strip_prefix("0x")
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| format!("its packet ID \"{}\" is not hexadecimal", id))?;
+
+    Ok((PacketIdentifier(direction, stage, id.into()), heading))
+}
+
+/// A row of a packet table: the name and wiki type of the field at each level of nesting,
+/// outermost first, and the notes on the innermost field.
+#[derive(Clone)]
+struct Line<'a> {
+    levels: Vec<(&'a str, &'a str)>,
+    notes: &'a str,
+}
+
+#[derive(Default)]
+struct Extractor {
+    /// The shared types referred to.
+    shared: BTreeSet<&'static str>,
+    /// The names of the custom types in the current packet, which have to be generated as
+    /// distinct Rust types.
+    type_names: BTreeSet<String>,
+    path: Vec<PathSegment>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Extractor {
+    fn warning(&mut self, message: String) {
+        let path = SpecPath(self.path.clone());
+        self.diagnostics.push(Diagnostic::warning(path, message));
+    }
+
+    fn at<T>(&mut self, segment: PathSegment, extract: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(segment);
+        let extracted = extract(self);
+        self.path.pop();
+        extracted
+    }
+
+    fn packet(&mut self, packet_table: &PacketTable, name: String, heading: &str) -> CustomType {
+        let rows = &packet_table.table.rows;
+        let header = &rows[0].cells;
+        let columns = |title: &str| -> Vec<usize> {
+            (0..header.len())
+                .filter(|&index| header[index] == title)
+                .collect()
+        };
+        let (name_columns, type_columns) = (columns("Field Name"), columns("Field Type"));
+        let notes_column = columns("Notes").first().copied();
+        if name_columns.len() != type_columns.len() {
+            self.warning(format!(
+                "The table has {} \"Field Name\" but {} \"Field Type\" columns, so only the \
+                 outermost fields are extracted.",
+                name_columns.len(),
+                type_columns.len()
+            ));
+        }
+        let depth = name_columns.len().min(type_columns.len()).max(1);
+
+        let mut lines = Vec::new();
+        for row in rows[1..].iter() {
+            let mut levels: Vec<(&str, &str)> = Vec::new();
+            for (name_column, type_column) in name_columns.iter().zip(type_columns.iter()) {
+                let level = (
+                    row.cells[*name_column].as_str(),
+                    row.cells[*type_column].as_str(),
+                );
+                // A field spanning several columns only has one level.
+                if levels.last() != Some(&level) {
+                    levels.push(level);
+                }
+            }
+            levels.truncate(depth);
+            if levels.is_empty() || levels[0].0.is_empty() || levels[0].0 == "no fields" {
+                continue;
+            }
+            let notes = notes_column.map_or("", |column| row.cells[column].as_str());
+            lines.push(Line { levels, notes });
+        }
+
+        self.type_names.insert(name.to_camel_case());
+        let fields = self.fields(&name, &lines, 0);
+        CustomType::Struct {
+            name: name.into(),
+            attributes: Attributes {
+                wiki: Some(anchor(heading)),
+                ..Attributes::default()
+            },
+            fields,
+        }
+    }
+
+    /// The fields at `depth` of the lines of a struct.
+    fn fields(
+        &mut self,
+        struct_name: &str,
+        lines: &[Line],
+        depth: usize,
+    ) -> IndexMap<FieldName, FieldType> {
+        let mut fields = IndexMap::new();
+        let mut index = 0;
+        while index < lines.len() {
+            let (wiki_name, wiki_type) = lines[index].levels[depth];
+            // The rows of a field with nested fields repeat its name and type.
+            let end = if lines[index].levels.len() > depth + 1 {
+                lines[index..]
+                    .iter()
+                    .position(|line| line.levels.get(depth) != Some(&(wiki_name, wiki_type)))
+                    .map_or(lines.len(), |offset| index + offset)
+            } else {
+                index + 1
+            };
+            let is_last = end == lines.len();
+
+            let mut name = snake_case(wiki_name);
+            if fields.contains_key(&FieldName::from(name.clone())) {
+                let unique = (2..)
+                    .map(|number| format!("{}_{}", name, number))
+                    .find(|unique| !fields.contains_key(&FieldName::from(unique.clone())))
+                    .unwrap();
+                self.warning(format!(
+                    "\"{}.{}\" is used more than once, so the later one is named \"{}\".",
+                    struct_name, name, unique
+                ));
+                name = unique;
+            }
+
+            let group = &lines[index..end];
+            let field_type = self.at(PathSegment::Field(name.clone().into()), |extractor| {
+                if group.len() > 1 || group[0].levels.len() > depth + 1 {
+                    let nested: Vec<_> = group
+                        .iter()
+                        .filter(|line| line.levels.len() > depth + 1)
+                        .cloned()
+                        .collect();
+                    extractor.nested(&name, wiki_type, &nested, depth + 1, &fields, is_last)
+                } else {
+                    let notes = group[0].notes;
+                    let field_type = extractor.field_type(wiki_type, notes, &fields, is_last);
+                    match notes {
+                        "" => field_type,
+                        notes => FieldType::Annotated {
+                            attributes: FieldAttributes {
+                                doc: Some(notes.to_owned()),
+                                ..Default::default()
+                            },
+                            field_type: Box::new(field_type),
+                        },
+                    }
+                }
+            });
+            fields.insert(name.into(), field_type);
+            index = end;
+        }
+        fields
+    }
+
+    /// A field whose rows hold nested fields, which become a struct: usually the elements of
+    /// an array.
+    fn nested(
+        &mut self,
+        name: &str,
+        wiki_type: &str,
+        lines: &[Line],
+        depth: usize,
+        siblings: &IndexMap<FieldName, FieldType>,
+        is_last: bool,
+    ) -> FieldType {
+        let mut struct_name = name.to_owned();
+        if !self.type_names.insert(struct_name.to_camel_case()) {
+            struct_name = (2..)
+                .map(|number| format!("{}_{}", name, number))
+                .find(|unique| self.type_names.insert(unique.to_camel_case()))
+                .unwrap();
+        }
+        let fields = self.fields(&struct_name, lines, depth);
+        let nested = FieldType::CustomType(CustomType::Struct {
+            name: struct_name.into(),
+            attributes: Attributes::default(),
+            fields,
+        });
+
+        let (optional, wiki_type) = match strip_word(wiki_type, "Optional") {
+            Some(inner) => (true, inner),
+            None => (false, wiki_type),
+        };
+        let field_type = match wiki_type {
+            "Array" => self.array(nested, siblings, is_last),
+            _ => nested,
+        };
+        if optional {
+            self.optional(field_type, "", siblings)
+        } else {
+            field_type
+        }
+    }
+
+    /// The field type of a wiki type, given the notes on the field and the fields before it.
+    fn field_type(
+        &mut self,
+        wiki_type: &str,
+        notes: &str,
+        siblings: &IndexMap<FieldName, FieldType>,
+        is_last: bool,
+    ) -> FieldType {
+        if let Some(inner) = strip_word(wiki_type, "Optional") {
+            let inner = self.field_type(inner, notes, siblings, is_last);
+            return self.optional(inner, notes, siblings);
+        }
+        if let Some(element) = strip_word(wiki_type, "Array of") {
+            let element = self.field_type(element, "", siblings, is_last);
+            return self.array(element, siblings, is_last);
+        }
+        // The values of enums are only listed in the notes.
+        let wiki_type = wiki_type.strip_suffix(" Enum").unwrap_or(wiki_type);
+        if let Some(max_len) = wiki_type
+            .strip_prefix("String (")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|max_len| max_len.parse().ok())
+        {
+            return PrimitiveType::String(max_len).into();
+        }
+
+        match wiki_type {
+            "Boolean" => PrimitiveType::Boolean.into(),
+            "Byte" => IntegerType::I8.into(),
+            "Unsigned Byte" | "Angle" => IntegerType::U8.into(),
+            "Short" => IntegerType::I16.into(),
+            "Unsigned Short" => IntegerType::U16.into(),
+            "Int" => IntegerType::I32.into(),
+            "Long" => IntegerType::I64.into(),
+            "VarInt" => IntegerType::VarInt.into(),
+            "VarLong" => IntegerType::VarLong.into(),
+            "Float" => FloatType::F32.into(),
+            "Double" => FloatType::F64.into(),
+            "String" => PrimitiveType::String(32767).into(),
+            "UUID" => PrimitiveType::Uuid.into(),
+            "NBT Tag" | "NBT" => PrimitiveType::Nbt.into(),
+            "Entity Metadata" => PrimitiveType::EntityMetadata.into(),
+            "Byte Array" => self.array(IntegerType::U8.into(), siblings, is_last),
+            "Chat" => self.shared("chat"),
+            "Identifier" => self.shared("identifier"),
+            "Position" => self.shared("position"),
+            "Slot" => self.shared("slot"),
+            _ => {
+                self.warning(format!(
+                    "\"{}\" is not a type the extractor knows, so it is extracted as the rest of \
+                     the buffer.",
+                    wiki_type
+                ));
+                rest_of_buffer()
+            }
+        }
+    }
+
+    fn shared(&mut self, name: &'static str) -> FieldType {
+        self.shared.insert(name);
+        PrimitiveType::Shared(name.to_owned().into()).into()
+    }
+
+    /// An array of `element`, whose length the wiki gives in the field before it, such as
+    /// "Count" or "Length", or which takes up the rest of the packet when it comes last.
+    fn array(
+        &mut self,
+        element: FieldType,
+        siblings: &IndexMap<FieldName, FieldType>,
+        is_last: bool,
+    ) -> FieldType {
+        let length_field = siblings.last().filter(|(name, field_type)| {
+            matches!(field_type.unannotated(), FieldType::Integer(_))
+                && ["count", "length", "size"].iter().any(|suffix| {
+                    name.as_str() == *suffix || name.ends_with(&format!("_{}", suffix))
+                })
+        });
+        let length = match length_field {
+            Some((name, _)) => ArrayLength::Field(name.clone()),
+            None if is_last => ArrayLength::RemainingLength,
+            None => {
+                self.warning(
+                    "The array has no length field before it and is not the last field, so it \
+                     is extracted as prefixed by its length as a VarInt."
+                        .to_owned(),
+                );
+                ArrayLength::Prefixed(Box::new(IntegerType::VarInt))
+            }
+        };
+        PrimitiveType::Array {
+            length,
+            field_type: Box::new(element),
+        }
+        .into()
+    }
+
+    /// An optional field, which is present when the Boolean field before it is true, or when
+    /// an earlier field has the value its notes name, e.g. "Only if Type is interact at.".
+    fn optional(
+        &mut self,
+        field_type: FieldType,
+        notes: &str,
+        siblings: &IndexMap<FieldName, FieldType>,
+    ) -> FieldType {
+        let condition = match siblings.last() {
+            Some((name, previous)) if is_boolean(previous) => {
+                Some(Condition::Equal(name.clone(), Literal::Boolean(true)))
+            }
+            _ => condition_from_notes(notes, siblings),
+        };
+        match condition {
+            Some(condition) => FieldType::Conditional {
+                condition,
+                field_type: Box::new(field_type),
+            },
+            None => {
+                self.warning(
+                    "The field is optional, but neither follows a Boolean nor names the value \
+                     it depends on, so it is extracted as prefixed by a Boolean."
+                        .to_owned(),
+                );
+                PrimitiveType::Option(Box::new(field_type)).into()
+            }
+        }
+    }
+}
+
+fn is_boolean(field_type: &FieldType) -> bool {
+    matches!(
+        field_type.unannotated(),
+        FieldType::Primitive(PrimitiveType::Boolean)
+    )
+}
+
+/// Reads conditions such as "Only if Type is interact at." from the notes of a field, where
+/// the value is a number, a Boolean or a value listed as "2: interact at" in the notes of the
+/// field it refers to.
+fn condition_from_notes(
+    notes: &str,
+    siblings: &IndexMap<FieldName, FieldType>,
+) -> Option<Condition> {
+    let notes = notes.to_lowercase();
+    let condition = notes.split("only if ").nth(1)?;
+    let condition = condition.split(&['.', ';', ','][..]).next()?;
+    let (field, value) = condition.split_once(" is ")?;
+    let (name, field_type) = siblings.get_key_value(&FieldName::from(snake_case(field)))?;
+
+    let literal = match value.trim() {
+        "true" if is_boolean(field_type) => Literal::Boolean(true),
+        "false" if is_boolean(field_type) => Literal::Boolean(false),
+        value => match value.parse() {
+            Ok(int) => Literal::Int(int),
+            Err(_) => {
+                let doc = field_type.doc()?.to_lowercase();
+                let mut matching = enum_values(&doc).filter(|(_, name)| *name == value);
+                let (int, _) = matching.next()?;
+                Literal::Int(int)
+            }
+        },
+    };
+    Some(Condition::Equal(name.clone(), literal))
+}
+
+/// The values listed as "0: interact, 1: attack" in the notes of an enum.
+fn enum_values(notes: &str) -> impl Iterator<Item = (i64, &str)> {
+    notes.split(&[',', ';'][..]).filter_map(|item| {
+        let (value, name) = item.split_once(':')?;
+        let name = name.trim().trim_end_matches('.');
+        Some((value.trim().parse().ok()?, name))
+    })
+}
+
+fn rest_of_buffer() -> FieldType {
+    PrimitiveType::Array {
+        length: ArrayLength::RemainingLength,
+        field_type: Box::new(IntegerType::U8.into()),
+    }
+    .into()
+}
+
+/// Strips a leading word, such as "Optional", off a wiki type.
+fn strip_word<'a>(wiki_type: &'a str, word: &str) -> Option<&'a str> {
+    wiki_type.strip_prefix(word)?.strip_prefix(' ')
+}
+
+/// The name of a packet or field, e.g. `entity_ids` for "Entity IDs".
+fn snake_case(wiki_name: &str) -> String {
+    wiki_name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// The anchor MediaWiki gives a heading, e.g. `Keep_Alive_.28clientbound.29`.
+fn anchor(heading: &str) -> String {
+    let mut anchor = String::new();
+    for c in heading.chars() {
+        match c {
+            ' ' => anchor.push('_'),
+            c if c.is_ascii_alphanumeric() || "-_.:".contains(c) => anchor.push(c),
+            c => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    anchor.push_str(&format!(".{:02X}", byte));
+                }
+            }
+        }
+    }
+    anchor
+}
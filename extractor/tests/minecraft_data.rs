@@ -0,0 +1,417 @@
+use anyhow::Result;
+use feather_protocol_extractor::minecraft_data::{self, Import};
+use feather_protocol_spec::*;
+use serde_json::json;
+
+fn import() -> Result<Import> {
+    let protocol_json = serde_json::from_str(include_str!("./protocol.json"))?;
+    minecraft_data::import(&protocol_json, 578.into())
+}
+
+fn packet(protocol: &Protocol, name: &str) -> IndexMap<FieldName, FieldType> {
+    match protocol.packets_named(name, PacketFilter::default())[0].1 {
+        CustomType::Struct { fields, .. } => fields.clone(),
+        _ => unreachable!(),
+    }
+}
+
+fn field_type(ron: &str) -> FieldType {
+    ron::de::from_str(ron).unwrap()
+}
+
+#[test]
+fn imports_packets() -> Result<()> {
+    let protocol = import()?.protocol;
+
+    assert_eq!(*protocol.version, 578);
+    assert_eq!(protocol.packets.len(), 16);
+    let identifier = PacketIdentifier(PacketDirection::Server, PacketStage::Handshaking, 0.into());
+    assert_eq!(protocol.packets[&identifier].name(), "set_protocol");
+    let identifier = PacketIdentifier(PacketDirection::Client, PacketStage::Play, 0x0c.into());
+    assert_eq!(protocol.packets[&identifier].name(), "boss_bar");
+
+    let set_protocol = packet(&protocol, "set_protocol");
+    assert_eq!(
+        set_protocol
+            .keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "protocol_version",
+            "server_host",
+            "server_port",
+            "next_state"
+        ]
+    );
+    assert_eq!(set_protocol[1], field_type("String(32767)"));
+
+    let update_attributes = packet(&protocol, "entity_update_attributes");
+    assert_eq!(
+        update_attributes[1],
+        field_type(
+            r#"Array(length: Prefixed(I32), field_type: Struct(name: "properties", fields: {
+                "key": String(32767),
+                "value": F64,
+            }))"#
+        )
+    );
+    assert_eq!(
+        packet(&protocol, "entity_metadata")[1],
+        field_type("EntityMetadata")
+    );
+    assert_eq!(
+        packet(&protocol, "custom_payload")[1],
+        field_type("Array(length: RemainingLength, field_type: U8)")
+    );
+
+    let diagnostics = protocol.diagnostics();
+    assert!(
+        diagnostics.iter().all(|d| !d.is_error()),
+        "{:?}",
+        diagnostics
+    );
+    Ok(())
+}
+
+#[test]
+fn imports_shared_types() -> Result<()> {
+    let protocol = import()?.protocol;
+
+    assert_eq!(
+        packet(&protocol, "block_change")[0],
+        field_type(r#"Shared("position")"#)
+    );
+    let shared_types: Vec<_> = protocol.shared_types.iter().map(|t| t.name()).collect();
+    assert_eq!(shared_types, ["position", "slot"]);
+    assert_eq!(
+        protocol.shared_types[0],
+        ron::de::from_str(
+            r#"BitField(name: "position", fields: {"x": I32(26), "z": I32(26), "y": I16(12)})"#
+        )?
+    );
+    // The fields of the anonymous switch are only there when the slot is present.
+    match &protocol.shared_types[1] {
+        CustomType::Struct { fields, .. } => {
+            assert_eq!(
+                fields.keys().map(|name| name.as_str()).collect::<Vec<_>>(),
+                ["present", "item_id", "item_count", "nbt_data"]
+            );
+            assert_eq!(
+                fields[1],
+                field_type(r#"Conditional(condition: Equal("present", true), field_type: VarInt)"#)
+            );
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn imports_switches() -> Result<()> {
+    let protocol = import()?.protocol;
+
+    let map = packet(&protocol, "map");
+    assert_eq!(
+        map[2],
+        field_type(r#"Conditional(condition: NotEqual("columns", 0), field_type: I8)"#)
+    );
+    let use_entity = packet(&protocol, "use_entity");
+    assert_eq!(
+        use_entity[2],
+        field_type(r#"Conditional(condition: Equal("mouse", 2), field_type: F32)"#)
+    );
+
+    // A switch on a mapper keeps the integer and names the variants after the mappings.
+    let boss_bar = packet(&protocol, "boss_bar");
+    assert_eq!(boss_bar[1], field_type("VarInt"));
+    assert_eq!(
+        boss_bar[2],
+        field_type(
+            r#"Enum(name: "health", variant: Key("action"), variants: {
+                0: Struct(name: "add", fields: {"value": F32}),
+                1: Unit(name: "remove"),
+                2: Struct(name: "update_health", fields: {"health": F32}),
+            })"#
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn reports_unsupported_constructs() -> Result<()> {
+    let import = import()?;
+
+    let reported: Vec<_> = import
+        .diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.path.to_string())
+        .collect();
+    assert_eq!(
+        reported,
+        [
+            "(Client, Play, 0x24).data",
+            "(Client, Play, 0x34).data.name",
+            "(Client, Play, 0x5A).equipments",
+            "(Server, Play, 0x0E).hand",
+            "shared_types.slot.nbt_data",
+        ]
+    );
+    assert!(import.diagnostics[0].message.contains("a `particleData`"));
+    assert!(import.diagnostics[1]
+        .message
+        .contains("a switch on `../action`"));
+    assert!(import.diagnostics[3]
+        .message
+        .contains("other values of `mouse` fail to read"));
+    assert!(import.diagnostics[4]
+        .message
+        .contains("the native type `optionalNbt`"));
+    assert!(import
+        .diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity == Severity::Warning));
+
+    // Each of them is imported as the rest of the buffer.
+    let equipment_list = packet(&import.protocol, "entity_equipment_list");
+    assert_eq!(
+        equipment_list[1],
+        field_type("Array(length: RemainingLength, field_type: U8)")
+    );
+    Ok(())
+}
+
+#[test]
+fn packets_come_from_the_packet_mapper() {
+    let protocol_json = serde_json::json!({
+        "types": {},
+        "play": { "toClient": { "types": { "packet": ["container", []] } } },
+    });
+    let error = minecraft_data::import(&protocol_json, 578.into()).unwrap_err();
+    assert_eq!(error.to_string(), "Reading the packets of play.toClient");
+}
+
+const EXPORTED: &str = r#"(
+    version: 578,
+    packets: {
+        (Client, Play, 11): Struct(name: "block_change", fields: {
+            "location": Shared("position"),
+            "block_id": VarInt,
+        }),
+        (Client, Play, 12): Struct(name: "boss_bar", fields: {
+            "entity_uuid": Uuid,
+            "action": Enum(name: "action", variant: VarInt, variants: {
+                0: Struct(name: "add", fields: {
+                    "health": F32,
+                    "flags": BitFlags(name: "flags", field_type: U8, flags: {
+                        1: "darken_sky",
+                        4: "create_fog",
+                    }),
+                }),
+                1: Unit(name: "remove"),
+            }),
+        }),
+        (Server, Play, 14): Struct(name: "use_entity", fields: {
+            "target": VarInt,
+            "mouse": VarInt,
+            "x": Conditional(condition: Equal("mouse", 2), field_type: F32),
+            "interaction": Enum(name: "interaction", variant: Key("mouse"), variants: {
+                0: Struct(name: "interact", fields: {"hand": Shared("hand")}),
+                1: Unit(name: "attack"),
+            }),
+        }),
+        (Server, Play, 15): Struct(name: "keep_alive", fields: {
+            "keep_alive_id": Annotated(attributes: (doc: "Echoed back."), field_type: I64),
+        }),
+    },
+    shared_types: [
+        BitField(name: "position", fields: {"x": I32(26), "z": I32(26), "y": I16(12)}),
+        Enum(name: "hand", variant: VarInt, variants: {
+            0: Unit(name: "main_hand"),
+            1: Unit(name: "off_hand"),
+        }),
+    ],
+)"#;
+
+fn exported() -> Result<(Protocol, minecraft_data::Export)> {
+    let protocol: Protocol = ron::de::from_str(EXPORTED)?;
+    protocol.validate()?;
+    let export = minecraft_data::export(&protocol);
+    Ok((protocol, export))
+}
+
+#[test]
+fn exports_packets_and_shared_types() -> Result<()> {
+    let (_, export) = exported()?;
+    let protocol_json = &export.protocol_json;
+    assert!(export.diagnostics.is_empty(), "{:?}", export.diagnostics);
+
+    assert_eq!(
+        protocol_json.pointer("/play/toClient/types/packet/1/0/type/1/mappings"),
+        Some(&json!({ "0x0b": "block_change", "0x0c": "boss_bar" }))
+    );
+    assert_eq!(
+        protocol_json.pointer("/play/toServer/types/packet/1/1/type/1/fields/keep_alive"),
+        Some(&json!("packet_keep_alive"))
+    );
+    assert_eq!(
+        protocol_json["play"]["toServer"]["types"]["packet_keep_alive"],
+        json!(["container", [{ "name": "keepAliveId", "type": "i64" }]])
+    );
+    assert_eq!(
+        protocol_json["play"]["toClient"]["types"]["packet_block_change"],
+        json!(["container", [
+            { "name": "location", "type": "position" },
+            { "name": "blockId", "type": "varint" },
+        ]])
+    );
+    assert_eq!(
+        protocol_json["types"]["position"],
+        json!(["bitfield", [
+            { "name": "x", "size": 26, "signed": true },
+            { "name": "z", "size": 26, "signed": true },
+            { "name": "y", "size": 12, "signed": true },
+        ]])
+    );
+    assert_eq!(
+        protocol_json["types"]["hand"],
+        json!(["mapper", { "type": "varint", "mappings": { "0": "main_hand", "1": "off_hand" } }])
+    );
+    assert_eq!(protocol_json["types"]["varint"], json!("native"));
+    Ok(())
+}
+
+#[test]
+fn exports_enums_as_mappers_and_switches() -> Result<()> {
+    let (_, export) = exported()?;
+    let types = &export.protocol_json["play"]["toServer"]["types"];
+
+    assert_eq!(
+        types.pointer("/packet_use_entity/1/2/type"),
+        Some(
+            &json!(["switch", { "compareTo": "mouse", "fields": { "2": "f32" }, "default": "void" }])
+        )
+    );
+    assert_eq!(
+        types.pointer("/packet_use_entity/1/3/type"),
+        Some(&json!(["switch", {
+            "compareTo": "mouse",
+            "fields": {
+                "0": ["container", [{ "name": "hand", "type": "hand" }]],
+                "1": "void",
+            },
+        }]))
+    );
+
+    // An enum with its own discriminant and fields in its variants switches on a mapper.
+    let types = &export.protocol_json["play"]["toClient"]["types"];
+    assert_eq!(
+        types.pointer("/packet_boss_bar/1/1/type"),
+        Some(&json!(["container", [
+            {
+                "name": "key",
+                "type": ["mapper", { "type": "varint", "mappings": { "0": "add", "1": "remove" } }],
+            },
+            {
+                "anon": true,
+                "type": ["switch", {
+                    "compareTo": "key",
+                    "fields": {
+                        "add": ["container", [
+                            { "name": "health", "type": "f32" },
+                            {
+                                "name": "flags",
+                                "type": ["bitfield", [
+                                    { "name": "unused3", "size": 5, "signed": false },
+                                    { "name": "createFog", "size": 1, "signed": false },
+                                    { "name": "unused1", "size": 1, "signed": false },
+                                    { "name": "darkenSky", "size": 1, "signed": false },
+                                ]],
+                            },
+                        ]],
+                        "remove": "void",
+                    },
+                }],
+            },
+        ]]))
+    );
+    Ok(())
+}
+
+#[test]
+fn exported_specs_import_again() -> Result<()> {
+    let (protocol, export) = exported()?;
+    let imported = minecraft_data::import(&export.protocol_json, 578.into())?;
+    assert!(
+        imported.diagnostics.is_empty(),
+        "{:?}",
+        imported.diagnostics
+    );
+    let imported = imported.protocol;
+
+    assert_eq!(imported.shared_types.len(), protocol.shared_types.len());
+    for shared_type in &protocol.shared_types {
+        assert!(
+            imported.shared_types.contains(shared_type),
+            "{:?}",
+            shared_type
+        );
+    }
+    assert_eq!(
+        packet(&imported, "block_change"),
+        packet(&protocol, "block_change")
+    );
+    assert_eq!(
+        packet(&imported, "keep_alive")[0],
+        field_type("I64"),
+        "documentation is not exported"
+    );
+    assert_eq!(
+        packet(&imported, "use_entity")[2],
+        packet(&protocol, "use_entity")[2]
+    );
+    Ok(())
+}
+
+#[test]
+fn reports_what_protocol_json_cannot_represent() -> Result<()> {
+    let protocol: Protocol = ron::de::from_str(
+        r#"(version: 578, packets: {
+            (Client, Play, 0): Struct(name: "entity_metadata", fields: {
+                "metadata": EntityMetadata,
+                "tags": Array(length: Terminated(U8, 0), field_type: VarInt),
+                "flags": BitFlags(name: "flags", field_type: VarInt, flags: {1: "on_fire"}),
+            }),
+        }, shared_types: [])"#,
+    )?;
+    let export = minecraft_data::export(&protocol);
+
+    let reported: Vec<_> = export
+        .diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.path.to_string())
+        .collect();
+    assert_eq!(
+        reported,
+        [
+            "(Client, Play, 0x00).metadata",
+            "(Client, Play, 0x00).tags",
+            "(Client, Play, 0x00).flags",
+        ]
+    );
+    assert!(export.diagnostics[0]
+        .message
+        .contains("native type `entityMetadata`"));
+    assert!(export.diagnostics[1]
+        .message
+        .contains("terminated by the U8 0"));
+    assert!(export.diagnostics[2]
+        .message
+        .contains("bit flags in a VarInt"));
+    assert_eq!(
+        export
+            .protocol_json
+            .pointer("/play/toClient/types/packet_entity_metadata/1/1/type"),
+        Some(&json!("restBuffer"))
+    );
+    Ok(())
+}
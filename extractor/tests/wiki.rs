@@ -0,0 +1,266 @@
+use anyhow::Result;
+use feather_protocol_extractor::read_wiki_page;
+use feather_protocol_extractor::wiki::{extract, packet_tables, page_version, PacketTable};
+use feather_protocol_spec::*;
+
+fn tables() -> Result<Vec<PacketTable>> {
+    let wikitext = read_wiki_page("tests/Protocol.wikitext")?;
+    Ok(packet_tables(&wikitext))
+}
+
+fn table<'a>(tables: &'a [PacketTable], name: &str) -> Vec<Vec<&'a str>> {
+    let packet_table = tables
+        .iter()
+        .find(|packet_table| packet_table.sections.last().unwrap() == name)
+        .unwrap();
+    packet_table
+        .table
+        .rows
+        .iter()
+        .map(|row| row.cells.iter().map(String::as_str).collect())
+        .collect()
+}
+
+fn packet(protocol: &Protocol, name: &str) -> IndexMap<FieldName, FieldType> {
+    match protocol.packets_named(name, PacketFilter::default())[0].1 {
+        CustomType::Struct { fields, .. } => fields
+            .iter()
+            .map(|(name, field_type)| (name.clone(), field_type.unannotated().clone()))
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+fn field_type(ron: &str) -> FieldType {
+    ron::de::from_str(ron).unwrap()
+}
+
+#[test]
+fn reads_wikitext_and_api_responses() -> Result<()> {
+    let wikitext = read_wiki_page("tests/Protocol.wikitext")?;
+    assert_eq!(read_wiki_page("tests/Protocol.json")?, wikitext);
+    assert!(wikitext.starts_with("{{Box"));
+    assert!(read_wiki_page("tests/protocol.json").is_err());
+    Ok(())
+}
+
+#[test]
+fn finds_packet_tables_in_their_sections() -> Result<()> {
+    let tables = tables()?;
+    let sections: Vec<_> = tables
+        .iter()
+        .map(|packet_table| packet_table.sections.join(" / "))
+        .collect();
+    assert_eq!(
+        sections[..4],
+        [
+            "Handshaking / Serverbound / Handshake",
+            "Status / Clientbound / Response",
+            "Status / Clientbound / Pong",
+            "Status / Serverbound / Request",
+        ]
+    );
+    assert_eq!(sections.len(), 21);
+    assert_eq!(sections[20], "Play / Serverbound / Player Digging");
+    Ok(())
+}
+
+#[test]
+fn expands_spans() -> Result<()> {
+    let tables = tables()?;
+    assert_eq!(
+        table(&tables, "Handshake")[..3],
+        [
+            vec![
+                "Packet ID",
+                "State",
+                "Bound To",
+                "Field Name",
+                "Field Type",
+                "Notes"
+            ],
+            vec![
+                "0x00",
+                "Handshaking",
+                "Server",
+                "Protocol Version",
+                "VarInt",
+                "See protocol version numbers (currently 578 in Minecraft 1.15.2).",
+            ],
+            vec![
+                "0x00",
+                "Handshaking",
+                "Server",
+                "Server Address",
+                "String (255)",
+                "Hostname or IP, e.g. localhost or 127.0.0.1, that was used to connect. \
+                 The Notchian server does not use this information.",
+            ],
+        ]
+    );
+    assert_eq!(
+        table(&tables, "Request")[1],
+        [
+            "0x00",
+            "Status",
+            "Server",
+            "no fields",
+            "no fields",
+            "no fields"
+        ]
+    );
+
+    let tab_complete = table(&tables, "Tab-Complete (clientbound)");
+    let fields: Vec<_> = tab_complete.iter().map(|row| &row[3..7]).collect();
+    assert_eq!(
+        fields,
+        [
+            ["Field Name", "Field Name", "Field Type", "Field Type"],
+            ["ID", "ID", "VarInt", "VarInt"],
+            ["Start", "Start", "VarInt", "VarInt"],
+            ["Length", "Length", "VarInt", "VarInt"],
+            ["Count", "Count", "VarInt", "VarInt"],
+            ["Matches", "Match", "Array", "String (32767)"],
+            ["Matches", "Has tooltip", "Array", "Boolean"],
+            ["Matches", "Tooltip", "Array", "Optional Chat"],
+        ]
+    );
+    assert!(tab_complete
+        .iter()
+        .skip(1)
+        .all(|row| row[..3] == ["0x11", "Play", "Client"]));
+    Ok(())
+}
+
+#[test]
+fn reduces_markup_to_text() -> Result<()> {
+    let tables = tables()?;
+    let block_change = table(&tables, "Block Change");
+    assert_eq!(block_change[1][4], "Position");
+    assert!(block_change[2][5].contains("as given in the global palette. See"));
+    assert_eq!(table(&tables, "Player Digging")[3][4], "Byte Enum");
+    assert_eq!(
+        table(&tables, "Plugin Message (clientbound)")[2][5],
+        "Any data, depending on the channel. minecraft: channels are documented here."
+    );
+    Ok(())
+}
+
+#[test]
+fn reads_the_version_of_the_page() -> Result<()> {
+    let wikitext = read_wiki_page("tests/Protocol.wikitext")?;
+    assert_eq!(
+        page_version(&wikitext),
+        Some(("1.15.2".to_owned().into(), 578.into()))
+    );
+    Ok(())
+}
+
+#[test]
+fn extracts_packets() -> Result<()> {
+    let extracted = extract(&tables()?, 578.into());
+    let protocol = extracted.protocol;
+
+    assert_eq!(*protocol.version, 578);
+    assert_eq!(protocol.packets.len(), 21);
+    let identifier = PacketIdentifier(PacketDirection::Server, PacketStage::Handshaking, 0.into());
+    assert_eq!(protocol.packets[&identifier].name(), "handshake");
+    for &(direction, id) in &[
+        (PacketDirection::Client, 0x21),
+        (PacketDirection::Server, 0x0f),
+    ] {
+        let identifier = PacketIdentifier(direction, PacketStage::Play, id.into());
+        assert_eq!(protocol.packets[&identifier].name(), "keep_alive");
+    }
+    let identifier = PacketIdentifier(PacketDirection::Client, PacketStage::Play, 0x21.into());
+    assert_eq!(
+        protocol.packets[&identifier].wiki(),
+        Some("Keep_Alive_.28clientbound.29")
+    );
+    assert!(packet(&protocol, "request").is_empty());
+
+    assert_eq!(
+        packet(&protocol, "handshake")
+            .keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "protocol_version",
+            "server_address",
+            "server_port",
+            "next_state"
+        ]
+    );
+    let plugin_message = packet(&protocol, "plugin_message");
+    assert_eq!(plugin_message[0], field_type(r#"Shared("identifier")"#));
+    assert_eq!(
+        plugin_message[1],
+        field_type("Array(length: RemainingLength, field_type: U8)")
+    );
+    assert_eq!(packet(&protocol, "entity_head_look")[1], field_type("U8"));
+    let player_digging = packet(&protocol, "player_digging");
+    assert_eq!(player_digging[1], field_type(r#"Shared("position")"#));
+    assert_eq!(player_digging[2], field_type("I8"));
+
+    let diagnostics = protocol.diagnostics();
+    assert!(
+        diagnostics.iter().all(|d| !d.is_error()),
+        "{:?}",
+        diagnostics
+    );
+    Ok(())
+}
+
+#[test]
+fn extracts_nested_fields_and_conditions() -> Result<()> {
+    let extracted = extract(&tables()?, 578.into());
+    let protocol = extracted.protocol;
+
+    let tab_complete = packet(&protocol, "tab_complete");
+    let matches = match &tab_complete[4] {
+        FieldType::Primitive(PrimitiveType::Array { length, field_type }) => {
+            assert_eq!(*length, ArrayLength::Field("count".to_owned().into()));
+            field_type
+        }
+        other => panic!("{:?}", other),
+    };
+    let fields = match matches.as_ref() {
+        FieldType::CustomType(CustomType::Struct { name, fields, .. }) => {
+            assert_eq!(name.as_str(), "matches");
+            fields
+        }
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(fields[0].unannotated(), &field_type("String(32767)"));
+    assert_eq!(fields[1].unannotated(), &field_type("Boolean"));
+    assert_eq!(
+        fields[2].unannotated(),
+        &field_type(
+            r#"Conditional(condition: Equal("has_tooltip", true), field_type: Shared("chat"))"#
+        )
+    );
+    assert_eq!(
+        packet(&protocol, "destroy_entities")[1],
+        field_type(r#"Array(length: Field("count"), field_type: VarInt)"#)
+    );
+
+    let interact_entity = packet(&protocol, "interact_entity");
+    assert_eq!(
+        interact_entity[2],
+        field_type(r#"Conditional(condition: Equal("type", 2), field_type: F32)"#)
+    );
+    assert_eq!(interact_entity[5], field_type("Option(VarInt)"));
+    assert!(extracted
+        .diagnostics
+        .iter()
+        .any(|d| d.path.to_string() == "(Server, Play, 0x0E).hand"));
+    Ok(())
+}
+
+#[test]
+fn extracts_the_shared_types_used() -> Result<()> {
+    let protocol = extract(&tables()?, 578.into()).protocol;
+    let names: Vec<_> = protocol.shared_types.iter().map(CustomType::name).collect();
+    assert_eq!(names, ["chat", "identifier", "position"]);
+    Ok(())
+}
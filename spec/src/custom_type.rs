@@ -1,31 +1,50 @@
 use crate::primitives::*;
+use crate::VersionRange;
 use indexmap::IndexMap;
-use serde::{de, Deserialize, Deserializer, Serialize};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::BTreeMap;
 use std::fmt;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A type generated into its own Rust item.
+///
+/// Every custom type can carry [`Attributes`], such as documentation or the protocol versions
+/// it is in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum CustomType {
     Enum {
         name: CustomTypeName,
+        #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+        attributes: Attributes,
         variant: Box<FieldType>,
+        #[serde(deserialize_with = "literal_keys::deserialize")]
         variants: BTreeMap<Literal, CustomType>,
     },
     Struct {
         name: CustomTypeName,
+        #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+        attributes: Attributes,
         fields: IndexMap<FieldName, FieldType>,
     },
     BitField {
         name: CustomTypeName,
+        #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+        attributes: Attributes,
         fields: IndexMap<FieldName, BitField>,
     },
     BitFlags {
         name: CustomTypeName,
+        #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+        attributes: Attributes,
         field_type: Box<IntegerType>,
         flags: BTreeMap<u64, FlagName>,
     },
     Unit {
         name: CustomTypeName,
+        #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+        attributes: Attributes,
     },
 }
 
@@ -39,9 +58,127 @@ impl CustomType {
             CustomType::Unit { name, .. } => name,
         }
     }
+
+    pub fn attributes(&self) -> &Attributes {
+        match self {
+            CustomType::Enum { attributes, .. }
+            | CustomType::Struct { attributes, .. }
+            | CustomType::BitField { attributes, .. }
+            | CustomType::BitFlags { attributes, .. }
+            | CustomType::Unit { attributes, .. } => attributes,
+        }
+    }
+
+    pub fn attributes_mut(&mut self) -> &mut Attributes {
+        match self {
+            CustomType::Enum { attributes, .. }
+            | CustomType::Struct { attributes, .. }
+            | CustomType::BitField { attributes, .. }
+            | CustomType::BitFlags { attributes, .. }
+            | CustomType::Unit { attributes, .. } => attributes,
+        }
+    }
+
+    pub fn doc(&self) -> Option<&str> {
+        self.attributes().doc.as_deref()
+    }
+
+    pub fn wiki(&self) -> Option<&str> {
+        self.attributes().wiki.as_deref()
+    }
+
+    pub fn versions(&self) -> VersionRange {
+        self.attributes().versions()
+    }
+}
+
+/// What a custom type carries besides its definition.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Attributes {
+    /// An explanation of what the type means, which is emitted as rustdoc.
+    #[schemars(with = "String")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    /// The anchor of the section of the type on https://wiki.vg/Protocol.
+    #[schemars(with = "String")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub wiki: Option<String>,
+    /// In a spec covering several protocol versions, the first version a packet, enum variant
+    /// or shared type is in.
+    #[schemars(with = "u64")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub since: Option<ProtocolVersion>,
+    /// The last protocol version it is in.
+    #[schemars(with = "u64")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub until: Option<ProtocolVersion>,
+}
+
+impl Attributes {
+    pub fn is_empty(&self) -> bool {
+        *self == Attributes::default()
+    }
+
+    pub fn versions(&self) -> VersionRange {
+        VersionRange {
+            since: self.since,
+            until: self.until,
+        }
+    }
+}
+
+/// What a struct field carries besides its type: an explanation of what it means, a default,
+/// constraints on the values it accepts when read and the protocol versions it is in.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FieldAttributes {
+    #[schemars(with = "String")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    /// The anchor of the field on https://wiki.vg/Protocol.
+    #[schemars(with = "String")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub wiki: Option<String>,
+    /// The value generated constructors and `Default` impls give the field.
+    #[schemars(with = "Literal")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub default: Option<Literal>,
+    /// The smallest value of a number, or of the numbers in an array or option.
+    #[schemars(with = "i64")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub min: Option<i64>,
+    /// The largest value of a number, or of the numbers in an array or option.
+    #[schemars(with = "i64")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub max: Option<i64>,
+    /// The maximum length of an array, or of a string in characters,
+    /// which defaults to the `n` of `String(n)`.
+    #[schemars(with = "u64")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<u64>,
+    /// The first protocol version the field is in.
+    #[schemars(with = "u64")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub since: Option<ProtocolVersion>,
+    /// The last protocol version the field is in.
+    #[schemars(with = "u64")]
+    #[serde(default, with = "plain_option", skip_serializing_if = "Option::is_none")]
+    pub until: Option<ProtocolVersion>,
+}
+
+impl FieldAttributes {
+    pub fn is_empty(&self) -> bool {
+        *self == FieldAttributes::default()
+    }
+
+    pub fn versions(&self) -> VersionRange {
+        VersionRange {
+            since: self.since,
+            until: self.until,
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum BitField {
     Boolean,
     I8(u8),
@@ -54,14 +191,43 @@ pub enum BitField {
     U64(u8),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+impl BitField {
+    /// The number of bits occupied by this field.
+    pub fn width(&self) -> u8 {
+        match self {
+            BitField::Boolean => 1,
+            BitField::I8(width)
+            | BitField::U8(width)
+            | BitField::I16(width)
+            | BitField::U16(width)
+            | BitField::I32(width)
+            | BitField::U32(width)
+            | BitField::I64(width)
+            | BitField::U64(width) => *width,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            BitField::I8(_) | BitField::I16(_) | BitField::I32(_) | BitField::I64(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum ArrayLength {
     RemainingLength,
     FixedLength(u64),
     Prefixed(Box<IntegerType>),
+    /// The length is the value of an earlier integer field of the enclosing struct.
+    Field(FieldName),
+    /// Elements are read until the next value of the integer type equals the terminator,
+    /// which is consumed. No element may start with the terminator.
+    Terminated(Box<IntegerType>, i64),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum IntegerType {
     U8,
     I8,
@@ -75,12 +241,12 @@ pub enum IntegerType {
     VarLong,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum FloatType {
     F32,
     F64,
 }
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum PrimitiveType {
     Boolean,
     Uuid,
@@ -92,6 +258,9 @@ pub enum PrimitiveType {
     },
     Option(Box<FieldType>),
     Shared(CustomTypeName),
+    /// The entity metadata format: `(index, type, value)` entries, where the value depends on
+    /// the type ID, terminated by an index of 0xFF.
+    EntityMetadata,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -100,6 +269,213 @@ pub enum FieldType {
     Float(FloatType),
     Integer(IntegerType),
     CustomType(CustomType),
+    /// A field which is only present on the wire when `condition` holds.
+    Conditional {
+        condition: Condition,
+        field_type: Box<FieldType>,
+    },
+    /// The value of an earlier field of an enclosing struct. Only valid as the
+    /// `variant` of an enum whose discriminant is not written in front of it.
+    Key(FieldName),
+    /// A struct field with attributes around its type. Only valid directly as the type of a
+    /// struct field.
+    Annotated {
+        #[serde(default, skip_serializing_if = "FieldAttributes::is_empty")]
+        attributes: FieldAttributes,
+        field_type: Box<FieldType>,
+    },
+    /// A field which always holds `value`. It is not stored in the generated struct, but
+    /// written automatically and checked when read. Only valid directly as the type of a struct field.
+    Const {
+        field_type: Box<FieldType>,
+        value: Literal,
+    },
+}
+
+impl FieldType {
+    /// The type of a struct field without the attributes around it.
+    pub fn unannotated(&self) -> &FieldType {
+        match self {
+            FieldType::Annotated { field_type, .. } => field_type,
+            field_type => field_type,
+        }
+    }
+
+    /// The attributes of a struct field, which are empty unless it is `Annotated`.
+    pub fn attributes(&self) -> &FieldAttributes {
+        match self {
+            FieldType::Annotated { attributes, .. } => attributes,
+            _ => &NO_FIELD_ATTRIBUTES,
+        }
+    }
+
+    pub fn doc(&self) -> Option<&str> {
+        self.attributes().doc.as_deref()
+    }
+
+    pub fn wiki(&self) -> Option<&str> {
+        self.attributes().wiki.as_deref()
+    }
+
+    /// The value a struct field defaults to, if any.
+    pub fn default_value(&self) -> Option<&Literal> {
+        self.attributes().default.as_ref()
+    }
+
+    pub fn min(&self) -> Option<i64> {
+        self.attributes().min
+    }
+
+    pub fn max(&self) -> Option<i64> {
+        self.attributes().max
+    }
+
+    pub fn max_len(&self) -> Option<u64> {
+        self.attributes().max_len
+    }
+
+    /// The protocol versions a struct field is in.
+    pub fn versions(&self) -> VersionRange {
+        self.attributes().versions()
+    }
+}
+
+static NO_FIELD_ATTRIBUTES: FieldAttributes = FieldAttributes {
+    doc: None,
+    wiki: None,
+    default: None,
+    min: None,
+    max: None,
+    max_len: None,
+    since: None,
+    until: None,
+};
+
+/// Compares an earlier field of the enclosing struct against a literal. A member of a bit
+/// field is compared by naming both, as in `Equal("flags.has_redirect", true)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Condition {
+    Equal(FieldName, Literal),
+    NotEqual(FieldName, Literal),
+}
+
+impl Condition {
+    /// The field compared, including the member of a bit field if it names one.
+    pub fn field(&self) -> &FieldName {
+        match self {
+            Condition::Equal(field, _) => field,
+            Condition::NotEqual(field, _) => field,
+        }
+    }
+
+    /// The field of the enclosing struct the condition refers to.
+    pub fn struct_field(&self) -> FieldName {
+        let field = self.field();
+        FieldName::from(field.split('.').next().unwrap_or(field).to_owned())
+    }
+
+    /// The member of a bit field compared, if the condition names one.
+    pub fn member(&self) -> Option<&str> {
+        self.field().split_once('.').map(|(_, member)| member)
+    }
+
+    pub fn literal(&self) -> &Literal {
+        match self {
+            Condition::Equal(_, literal) => literal,
+            Condition::NotEqual(_, literal) => literal,
+        }
+    }
+}
+
+/// Every form a field type can be written in: the shorthands for primitives, numbers and
+/// custom types, and the externally tagged forms written by `Serialize`.
+#[derive(Deserialize, JsonSchema)]
+#[schemars(rename = "FieldType")]
+enum FieldForm {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    VarInt,
+    VarLong,
+    F32,
+    F64,
+    Boolean,
+    Uuid,
+    String(u16),
+    Nbt,
+    Array {
+        length: ArrayLength,
+        field_type: Box<FieldType>,
+    },
+    Option(Box<FieldType>),
+    Shared(CustomTypeName),
+    EntityMetadata,
+    Enum {
+        name: CustomTypeName,
+        variant: Box<FieldType>,
+        #[serde(deserialize_with = "literal_keys::deserialize")]
+        variants: BTreeMap<Literal, CustomType>,
+        #[serde(default)]
+        attributes: Attributes,
+    },
+    Struct {
+        name: CustomTypeName,
+        fields: IndexMap<FieldName, FieldType>,
+        #[serde(default)]
+        attributes: Attributes,
+    },
+    BitField {
+        name: CustomTypeName,
+        fields: IndexMap<FieldName, BitField>,
+        #[serde(default)]
+        attributes: Attributes,
+    },
+    BitFlags {
+        name: CustomTypeName,
+        field_type: Box<IntegerType>,
+        flags: BTreeMap<u64, FlagName>,
+        #[serde(default)]
+        attributes: Attributes,
+    },
+    Unit {
+        name: CustomTypeName,
+        #[serde(default)]
+        attributes: Attributes,
+    },
+    Conditional {
+        condition: Condition,
+        field_type: Box<FieldType>,
+    },
+    Key(FieldName),
+    Annotated {
+        #[serde(default)]
+        attributes: FieldAttributes,
+        field_type: Box<FieldType>,
+    },
+    Const {
+        field_type: Box<FieldType>,
+        value: Literal,
+    },
+    // The externally tagged forms written by `Serialize`.
+    Primitive(PrimitiveType),
+    Float(FloatType),
+    Integer(IntegerType),
+    CustomType(CustomType),
+}
+
+impl JsonSchema for FieldType {
+    fn schema_name() -> String {
+        FieldForm::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        FieldForm::json_schema(gen)
+    }
 }
 
 impl<'de> Deserialize<'de> for FieldType {
@@ -107,122 +483,157 @@ impl<'de> Deserialize<'de> for FieldType {
     where
         D: Deserializer<'de>,
     {
-
-        #[derive(Deserialize)]
-        enum Field {
-            U8,
-            I8,
-            U16,
-            I16,
-            U32,
-            I32,
-            U64,
-            I64,
-            VarInt,
-            VarLong,
-            F32,
-            F64,
-            Boolean,
-            Uuid,
-            String(u16),
-            Nbt,
-            Array {
-                length: ArrayLength,
-                field_type: Box<FieldType>,
-            },
-            Option(Box<FieldType>),
-            Shared(CustomTypeName),
-            Enum {
-                name: CustomTypeName,
-                variant: Box<FieldType>,
-                variants: BTreeMap<Literal, CustomType>,
-            },
-            Struct {
-                name: CustomTypeName,
-                fields: IndexMap<FieldName, FieldType>,
-            },
-            BitField {
-                name: CustomTypeName,
-                fields: IndexMap<FieldName, BitField>,
-            },
-            BitFlags {
-                name: CustomTypeName,
-                field_type: Box<IntegerType>,
-                flags: BTreeMap<u64, FlagName>,
-            },
-            Unit {
-                name: CustomTypeName,
-            },
-        }
-
-        let field = <Field as Deserialize>::deserialize(deserializer)?;
+        let field = <FieldForm as Deserialize>::deserialize(deserializer)?;
         Ok(match field {
-            Field::U8 => FieldType::Integer(IntegerType::U8),
-            Field::I8 => FieldType::Integer(IntegerType::I8),
-            Field::U16 => FieldType::Integer(IntegerType::U16),
-            Field::I16 => FieldType::Integer(IntegerType::I16),
-            Field::U32 => FieldType::Integer(IntegerType::U32),
-            Field::I32 => FieldType::Integer(IntegerType::I32),
-            Field::U64 => FieldType::Integer(IntegerType::U64),
-            Field::I64 => FieldType::Integer(IntegerType::I64),
-            Field::VarInt => FieldType::Integer(IntegerType::VarInt),
-            Field::VarLong => FieldType::Integer(IntegerType::VarLong),
-            Field::F32 => FieldType::Float(FloatType::F32),
-            Field::F64 => FieldType::Float(FloatType::F64),
-            Field::Boolean => FieldType::Primitive(PrimitiveType::Boolean),
-            Field::Uuid => FieldType::Primitive(PrimitiveType::Uuid),
-            Field::String(n) => FieldType::Primitive(PrimitiveType::String(n)),
-            Field::Nbt => FieldType::Primitive(PrimitiveType::Nbt),
-            Field::Array {
+            FieldForm::U8 => FieldType::Integer(IntegerType::U8),
+            FieldForm::I8 => FieldType::Integer(IntegerType::I8),
+            FieldForm::U16 => FieldType::Integer(IntegerType::U16),
+            FieldForm::I16 => FieldType::Integer(IntegerType::I16),
+            FieldForm::U32 => FieldType::Integer(IntegerType::U32),
+            FieldForm::I32 => FieldType::Integer(IntegerType::I32),
+            FieldForm::U64 => FieldType::Integer(IntegerType::U64),
+            FieldForm::I64 => FieldType::Integer(IntegerType::I64),
+            FieldForm::VarInt => FieldType::Integer(IntegerType::VarInt),
+            FieldForm::VarLong => FieldType::Integer(IntegerType::VarLong),
+            FieldForm::F32 => FieldType::Float(FloatType::F32),
+            FieldForm::F64 => FieldType::Float(FloatType::F64),
+            FieldForm::Boolean => FieldType::Primitive(PrimitiveType::Boolean),
+            FieldForm::Uuid => FieldType::Primitive(PrimitiveType::Uuid),
+            FieldForm::String(n) => FieldType::Primitive(PrimitiveType::String(n)),
+            FieldForm::Nbt => FieldType::Primitive(PrimitiveType::Nbt),
+            FieldForm::Array {
                 length,
                 field_type,
             } => FieldType::Primitive(PrimitiveType::Array {
                 length,
                 field_type,
             }),
-            Field::Option(inner) => FieldType::Primitive(PrimitiveType::Option(inner)),
-            Field::Shared(name) => FieldType::Primitive(PrimitiveType::Shared(name)),
-            Field::Enum {
+            FieldForm::Option(inner) => FieldType::Primitive(PrimitiveType::Option(inner)),
+            FieldForm::Shared(name) => FieldType::Primitive(PrimitiveType::Shared(name)),
+            FieldForm::EntityMetadata => FieldType::Primitive(PrimitiveType::EntityMetadata),
+            FieldForm::Enum {
                 name,
                 variant,
                 variants,
+                attributes,
             } => FieldType::CustomType(CustomType::Enum {
                 name,
                 variant,
                 variants,
+                attributes,
             }),
-            Field::Struct {
+            FieldForm::Struct {
                 name,
                 fields,
+                attributes,
             } => FieldType::CustomType(CustomType::Struct {
                 name,
-                fields
+                fields,
+                attributes,
             }),
-            Field::BitField {
+            FieldForm::BitField {
                 name,
                 fields,
+                attributes,
             } => FieldType::CustomType(CustomType::BitField {
                 name,
-                fields
+                fields,
+                attributes,
             }),
-            Field::BitFlags {
+            FieldForm::BitFlags {
                 name,
                 field_type,
                 flags,
+                attributes,
             } => FieldType::CustomType(CustomType::BitFlags {
                 name,
                 field_type,
-                flags
+                flags,
+                attributes,
             }),
-            Field::Unit {
+            FieldForm::Unit {
                 name,
+                attributes,
             } => FieldType::CustomType(CustomType::Unit {
-                name
+                name,
+                attributes,
             }),
+            FieldForm::Conditional {
+                condition,
+                field_type,
+            } => FieldType::Conditional {
+                condition,
+                field_type,
+            },
+            FieldForm::Key(field) => FieldType::Key(field),
+            FieldForm::Annotated {
+                attributes,
+                field_type,
+            } => FieldType::Annotated {
+                attributes,
+                field_type,
+            },
+            FieldForm::Const { field_type, value } => FieldType::Const { field_type, value },
+            FieldForm::Primitive(primitive_type) => FieldType::Primitive(primitive_type),
+            FieldForm::Float(float_type) => FieldType::Float(float_type),
+            FieldForm::Integer(integer_type) => FieldType::Integer(integer_type),
+            FieldForm::CustomType(custom_type) => FieldType::CustomType(custom_type),
         })
     }
 }
 
+/// Renders the wire type of a field, naming custom types instead of spelling them out.
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldType::Integer(integer_type) => write!(f, "{:?}", integer_type),
+            FieldType::Float(float_type) => write!(f, "{:?}", float_type),
+            FieldType::Primitive(PrimitiveType::Array { length, field_type }) => match length {
+                ArrayLength::RemainingLength => write!(f, "Array(RemainingLength, {})", field_type),
+                ArrayLength::FixedLength(length) => write!(f, "Array({}, {})", length, field_type),
+                ArrayLength::Prefixed(integer_type) => {
+                    write!(f, "Array(Prefixed({:?}), {})", integer_type, field_type)
+                }
+                ArrayLength::Field(field) => write!(f, "Array(Field({}), {})", **field, field_type),
+                ArrayLength::Terminated(integer_type, terminator) => write!(
+                    f,
+                    "Array(Terminated({:?}, {}), {})",
+                    integer_type, terminator, field_type
+                ),
+            },
+            FieldType::Primitive(PrimitiveType::Option(field_type)) => {
+                write!(f, "Option({})", field_type)
+            }
+            FieldType::Primitive(PrimitiveType::Shared(name)) => write!(f, "Shared({})", **name),
+            FieldType::Primitive(primitive_type) => write!(f, "{:?}", primitive_type),
+            FieldType::CustomType(custom_type) => {
+                let kind = match custom_type {
+                    CustomType::Enum { .. } => "Enum",
+                    CustomType::Struct { .. } => "Struct",
+                    CustomType::BitField { .. } => "BitField",
+                    CustomType::BitFlags { .. } => "BitFlags",
+                    CustomType::Unit { .. } => "Unit",
+                };
+                write!(f, "{}({})", kind, custom_type.name())
+            }
+            FieldType::Conditional {
+                condition,
+                field_type,
+            } => match condition {
+                Condition::Equal(field, literal) => {
+                    write!(f, "{} if {} == {}", field_type, **field, literal)
+                }
+                Condition::NotEqual(field, literal) => {
+                    write!(f, "{} if {} != {}", field_type, **field, literal)
+                }
+            },
+            FieldType::Key(field) => write!(f, "Key({})", **field),
+            FieldType::Annotated { field_type, .. } => write!(f, "{}", field_type),
+            FieldType::Const { field_type, value } => write!(f, "{} = {}", field_type, value),
+        }
+    }
+}
+
 impl From<IntegerType> for FieldType {
     fn from(integer_type: IntegerType) -> FieldType {
         FieldType::Integer(integer_type)
@@ -241,10 +652,197 @@ impl From<PrimitiveType> for FieldType {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize)]
+/// Writes optional strings such as `doc` without wrapping them in `Some(..)`.
+mod plain_option {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(Some)
+    }
+}
+
+/// Reads the keys of enum variants, which are strings in formats such as JSON even when they
+/// are numbers or booleans. String keys are read as strings, and only take the type of the
+/// discriminant of their enum once the packets and shared types around them have been read.
+pub(crate) mod literal_keys {
+    use super::{CustomType, FieldName, FieldType, Literal, PacketIdentifier, PrimitiveType};
+    use serde::de::{self, Deserializer, Visitor};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use std::fmt;
+
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct LiteralKey(Literal);
+
+    impl<'de> Deserialize<'de> for LiteralKey {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(LiteralKeyVisitor)
+        }
+    }
+
+    struct LiteralKeyVisitor;
+
+    impl<'de> Visitor<'de> for LiteralKeyVisitor {
+        type Value = LiteralKey;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a string, boolean or integer")
+        }
+
+        fn visit_bool<E: de::Error>(self, v: bool) -> Result<LiteralKey, E> {
+            Ok(LiteralKey(Literal::Boolean(v)))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<LiteralKey, E> {
+            Ok(LiteralKey(Literal::Int(v)))
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<LiteralKey, E> {
+            if v > i64::MAX as u64 {
+                return Err(E::invalid_value(de::Unexpected::Unsigned(v), &self));
+            }
+            Ok(LiteralKey(Literal::Int(v as i64)))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<LiteralKey, E> {
+            Ok(LiteralKey(Literal::String(v.to_owned())))
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BTreeMap<Literal, CustomType>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let variants = BTreeMap::<LiteralKey, CustomType>::deserialize(deserializer)?;
+        Ok(variants.into_iter().map(|(key, variant)| (key.0, variant)).collect())
+    }
+
+    pub fn deserialize_packets<'de, D>(
+        deserializer: D,
+    ) -> Result<BTreeMap<PacketIdentifier, CustomType>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut packets = BTreeMap::<PacketIdentifier, CustomType>::deserialize(deserializer)?;
+        for packet in packets.values_mut() {
+            type_keys(packet, &BTreeMap::new());
+        }
+        Ok(packets)
+    }
+
+    pub fn deserialize_shared_types<'de, D>(deserializer: D) -> Result<Vec<CustomType>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut shared_types = Vec::<CustomType>::deserialize(deserializer)?;
+        for shared_type in &mut shared_types {
+            type_keys(shared_type, &BTreeMap::new());
+        }
+        Ok(shared_types)
+    }
+
+    /// Converts the string keys of the enums in `custom_type` which are keyed by a number or
+    /// boolean. `key_fields` holds the number and boolean fields of the enclosing structs,
+    /// which enums keyed by a `Key` take the type of.
+    fn type_keys(custom_type: &mut CustomType, key_fields: &BTreeMap<FieldName, FieldType>) {
+        match custom_type {
+            CustomType::Enum {
+                variant, variants, ..
+            } => {
+                let discriminant = match &**variant {
+                    FieldType::Key(field) => key_fields.get(field),
+                    discriminant => Some(discriminant),
+                };
+                if let Some(discriminant) = discriminant {
+                    *variants = std::mem::take(variants)
+                        .into_iter()
+                        .map(|(key, variant)| (type_key(key, discriminant), variant))
+                        .collect();
+                }
+                for variant in variants.values_mut() {
+                    type_keys(variant, key_fields);
+                }
+            }
+            CustomType::Struct { fields, .. } => {
+                let mut key_fields = key_fields.clone();
+                for (field_name, field_type) in fields.iter_mut() {
+                    type_field_keys(field_type, &key_fields);
+                    match field_type.unannotated() {
+                        key_type @ FieldType::Integer(_)
+                        | key_type @ FieldType::Primitive(PrimitiveType::Boolean) => {
+                            key_fields.insert(field_name.clone(), key_type.clone())
+                        }
+                        _ => key_fields.remove(field_name),
+                    };
+                }
+            }
+            CustomType::BitField { .. } | CustomType::BitFlags { .. } | CustomType::Unit { .. } => {}
+        }
+    }
+
+    fn type_field_keys(field_type: &mut FieldType, key_fields: &BTreeMap<FieldName, FieldType>) {
+        match field_type {
+            FieldType::CustomType(custom_type) => type_keys(custom_type, key_fields),
+            FieldType::Primitive(PrimitiveType::Array { field_type, .. })
+            | FieldType::Primitive(PrimitiveType::Option(field_type))
+            | FieldType::Conditional { field_type, .. }
+            | FieldType::Annotated { field_type, .. }
+            | FieldType::Const { field_type, .. } => type_field_keys(field_type, key_fields),
+            _ => {}
+        }
+    }
+
+    /// A string key of an enum with the given discriminant as the literal it stands for.
+    fn type_key(key: Literal, discriminant: &FieldType) -> Literal {
+        match (key, discriminant) {
+            (Literal::String(key), FieldType::Integer(_)) => match key.parse() {
+                Ok(int) => Literal::Int(int),
+                Err(_) => Literal::String(key),
+            },
+            (Literal::String(key), FieldType::Primitive(PrimitiveType::Boolean)) => {
+                match key.as_str() {
+                    "true" => Literal::Boolean(true),
+                    "false" => Literal::Boolean(false),
+                    _ => Literal::String(key),
+                }
+            }
+            (key, _) => key,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum Literal {
     String(String),
     Boolean(bool),
     Int(i64),
 }
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::String(string) => write!(f, "{:?}", string),
+            Literal::Boolean(boolean) => write!(f, "{}", boolean),
+            Literal::Int(int) => write!(f, "{}", int),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{source, CustomTypeName, FieldName, Literal, PacketIdentifier};
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A 1-based line and column in a spec source file.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Packet(PacketIdentifier),
+    SharedType(CustomTypeName),
+    Field(FieldName),
+    Variant(Literal),
+}
+
+/// The path from the root of a spec to the packet, shared type or field a diagnostic is about.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpecPath(pub Vec<PathSegment>);
+
+impl fmt::Display for SpecPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for segment in self.0.iter() {
+            match segment {
+                PathSegment::Packet(identifier) => write!(
+                    f,
+                    "({:?}, {:?}, {:#04X})",
+                    identifier.direction(),
+                    identifier.stage(),
+                    *identifier.id()
+                )?,
+                PathSegment::SharedType(name) => write!(f, "shared_types.{}", **name)?,
+                PathSegment::Field(name) => write!(f, ".{}", **name)?,
+                PathSegment::Variant(literal) => write!(f, "[{}]", literal)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A problem found while validating a spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub path: SpecPath,
+    /// Where `path` is defined, once the diagnostic has been located in the source.
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub fn error(path: SpecPath, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            path,
+            location: None,
+        }
+    }
+
+    pub fn warning(path: SpecPath, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+            path,
+            location: None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Renders this diagnostic the way rustc does, quoting the line of `source` it points at.
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let mut rendered = format!("{}: {}\n", self.severity, self.message);
+        match self.location {
+            Some(location) => {
+                let line_number = location.line.to_string();
+                let gutter = " ".repeat(line_number.len());
+                let line = source.lines().nth(location.line - 1).unwrap_or("");
+                rendered += &format!(
+                    "{}--> {}:{}:{}\n",
+                    gutter, file_name, location.line, location.column
+                );
+                rendered += &format!("{} |\n", gutter);
+                rendered += &format!("{} | {}\n", line_number, line);
+                rendered += &format!(
+                    "{} | {}^\n",
+                    gutter,
+                    " ".repeat(location.column.saturating_sub(1))
+                );
+                if !self.path.0.is_empty() {
+                    rendered += &format!("{} = note: in `{}`\n", gutter, self.path);
+                }
+            }
+            None => {
+                rendered += &format!(" --> {}\n", file_name);
+                if !self.path.0.is_empty() {
+                    rendered += &format!("  = note: in `{}`\n", self.path);
+                }
+            }
+        }
+        rendered
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Fills in the location of each diagnostic from the RON `source` it was found in.
+pub fn locate(diagnostics: &mut [Diagnostic], source: &str) {
+    if let Some(root) = source::parse(source) {
+        // Paths start at the protocol, which is nested in the root of a full spec file.
+        let protocol = root.field("protocol").unwrap_or(&root);
+        for diagnostic in diagnostics.iter_mut() {
+            let node = if diagnostic.path.0.is_empty() { &root } else { protocol };
+            diagnostic.location = Some(node.locate(&diagnostic.path.0));
+        }
+    }
+}
+
+/// The error diagnostics of a spec which failed validation.
+#[derive(Debug, Error)]
+pub struct ValidationError(pub Vec<Diagnostic>);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages: Vec<_> = self
+            .0
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        write!(f, "{}", messages.join("\n"))
+    }
+}
@@ -0,0 +1,532 @@
+use crate::*;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A single structural difference between two protocols.
+///
+/// Packets are matched by direction, stage and name, so a packet which keeps its
+/// name but changes its ID is reported as moved. Paths refer to the new protocol,
+/// or to the old one for things which were removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// The Minecraft version, major version or protocol version of the spec changed.
+    VersionChanged {
+        path: String,
+        old_version: String,
+        new_version: String,
+    },
+    PacketAdded {
+        path: String,
+        name: String,
+    },
+    PacketRemoved {
+        path: String,
+        name: String,
+    },
+    PacketMoved {
+        path: String,
+        name: String,
+        old_id: u64,
+        new_id: u64,
+    },
+    SharedTypeAdded {
+        path: String,
+    },
+    SharedTypeRemoved {
+        path: String,
+    },
+    FieldAdded {
+        path: String,
+        field_type: String,
+    },
+    FieldRemoved {
+        path: String,
+        field_type: String,
+    },
+    FieldRetyped {
+        path: String,
+        old_type: String,
+        new_type: String,
+    },
+    /// The fields present in both versions of a struct are in a different order.
+    FieldsReordered {
+        path: String,
+        old_order: Vec<String>,
+        new_order: Vec<String>,
+    },
+    DiscriminantRetyped {
+        path: String,
+        old_type: String,
+        new_type: String,
+    },
+    VariantAdded {
+        path: String,
+        name: String,
+    },
+    VariantRemoved {
+        path: String,
+        name: String,
+    },
+    VariantRenamed {
+        path: String,
+        old_name: String,
+        new_name: String,
+    },
+    FlagAdded {
+        path: String,
+        bits: u64,
+        name: String,
+    },
+    FlagRemoved {
+        path: String,
+        bits: u64,
+        name: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::VersionChanged {
+                path,
+                old_version,
+                new_version,
+            } => write!(f, "~ {}: {} -> {}", path, old_version, new_version),
+            Change::PacketAdded { path, name } => write!(f, "+ packet {} {}", path, name),
+            Change::PacketRemoved { path, name } => write!(f, "- packet {} {}", path, name),
+            Change::PacketMoved {
+                path,
+                name,
+                old_id,
+                new_id,
+            } => write!(
+                f,
+                "~ packet {} {} moved from {:#04X} to {:#04X}",
+                path, name, old_id, new_id
+            ),
+            Change::SharedTypeAdded { path } => write!(f, "+ shared type {}", path),
+            Change::SharedTypeRemoved { path } => write!(f, "- shared type {}", path),
+            Change::FieldAdded { path, field_type } => {
+                write!(f, "+ field {}: {}", path, field_type)
+            }
+            Change::FieldRemoved { path, field_type } => {
+                write!(f, "- field {}: {}", path, field_type)
+            }
+            Change::FieldRetyped {
+                path,
+                old_type,
+                new_type,
+            } => write!(f, "~ field {}: {} -> {}", path, old_type, new_type),
+            Change::FieldsReordered {
+                path,
+                old_order,
+                new_order,
+            } => write!(
+                f,
+                "~ fields of {} reordered: {} -> {}",
+                path,
+                old_order.join(", "),
+                new_order.join(", ")
+            ),
+            Change::DiscriminantRetyped {
+                path,
+                old_type,
+                new_type,
+            } => write!(
+                f,
+                "~ discriminant of {}: {} -> {}",
+                path, old_type, new_type
+            ),
+            Change::VariantAdded { path, name } => write!(f, "+ variant {} {}", path, name),
+            Change::VariantRemoved { path, name } => write!(f, "- variant {} {}", path, name),
+            Change::VariantRenamed {
+                path,
+                old_name,
+                new_name,
+            } => write!(f, "~ variant {}: {} -> {}", path, old_name, new_name),
+            Change::FlagAdded { path, bits, name } => {
+                write!(f, "+ flag {} {:#X} {}", path, bits, name)
+            }
+            Change::FlagRemoved { path, bits, name } => {
+                write!(f, "- flag {} {:#X} {}", path, bits, name)
+            }
+        }
+    }
+}
+
+/// Lists the differences from the spec `old` to `new`: the versions it is for, followed by
+/// the structural differences of its protocol.
+pub fn diff_minecraft(old: &Minecraft, new: &Minecraft) -> Vec<Change> {
+    let versions = [
+        ("version", old.version.to_string(), new.version.to_string()),
+        (
+            "major_version",
+            old.major_version.to_string(),
+            new.major_version.to_string(),
+        ),
+        (
+            "protocol.version",
+            old.protocol.version.to_string(),
+            new.protocol.version.to_string(),
+        ),
+    ];
+    let mut changes: Vec<_> = versions
+        .iter()
+        .filter(|(_, old_version, new_version)| old_version != new_version)
+        .map(|(path, old_version, new_version)| Change::VersionChanged {
+            path: (*path).to_owned(),
+            old_version: old_version.clone(),
+            new_version: new_version.clone(),
+        })
+        .collect();
+    changes.extend(diff(&old.protocol, &new.protocol));
+    changes
+}
+
+/// Lists the structural differences from `old` to `new`.
+pub fn diff(old: &Protocol, new: &Protocol) -> Vec<Change> {
+    let mut differ = Differ {
+        path: Vec::new(),
+        changes: Vec::new(),
+    };
+
+    let old_packets = packets_by_name(old);
+    let new_packets = packets_by_name(new);
+
+    for (key, (identifier, packet)) in old_packets.iter() {
+        if !new_packets.contains_key(key) {
+            let path = differ.path_to(PathSegment::Packet(identifier.clone()));
+            differ.changes.push(Change::PacketRemoved {
+                path,
+                name: packet.name().to_owned(),
+            });
+        }
+    }
+    for (key, (identifier, packet)) in new_packets.iter() {
+        match old_packets.get(key) {
+            None => {
+                let path = differ.path_to(PathSegment::Packet(identifier.clone()));
+                differ.changes.push(Change::PacketAdded {
+                    path,
+                    name: packet.name().to_owned(),
+                });
+            }
+            Some((old_identifier, old_packet)) => {
+                differ.at(PathSegment::Packet(identifier.clone()), |differ| {
+                    if old_identifier.id() != identifier.id() {
+                        let path = differ.path();
+                        differ.changes.push(Change::PacketMoved {
+                            path,
+                            name: packet.name().to_owned(),
+                            old_id: *old_identifier.id(),
+                            new_id: *identifier.id(),
+                        });
+                    }
+                    differ.custom_type(old_packet, packet);
+                });
+            }
+        }
+    }
+
+    let old_shared: BTreeMap<_, _> = old
+        .shared_types
+        .iter()
+        .map(|shared_type| (shared_type.name(), shared_type))
+        .collect();
+    let new_shared: BTreeMap<_, _> = new
+        .shared_types
+        .iter()
+        .map(|shared_type| (shared_type.name(), shared_type))
+        .collect();
+    for name in old_shared.keys() {
+        if !new_shared.contains_key(name) {
+            let path = differ.path_to(PathSegment::SharedType((*name).to_owned().into()));
+            differ.changes.push(Change::SharedTypeRemoved { path });
+        }
+    }
+    for (name, shared_type) in new_shared.iter() {
+        let segment = PathSegment::SharedType((*name).to_owned().into());
+        match old_shared.get(name) {
+            None => {
+                let path = differ.path_to(segment);
+                differ.changes.push(Change::SharedTypeAdded { path });
+            }
+            Some(old_shared_type) => differ.at(segment, |differ| {
+                differ.custom_type(old_shared_type, shared_type)
+            }),
+        }
+    }
+
+    differ.changes
+}
+
+type PacketKey = (PacketDirection, PacketStage, String);
+
+fn packets_by_name(protocol: &Protocol) -> BTreeMap<PacketKey, (PacketIdentifier, &CustomType)> {
+    protocol
+        .packets
+        .iter()
+        .map(|(identifier, packet)| {
+            (
+                (
+                    identifier.direction(),
+                    identifier.stage(),
+                    packet.name().to_owned(),
+                ),
+                (identifier.clone(), packet),
+            )
+        })
+        .collect()
+}
+
+struct Differ {
+    path: Vec<PathSegment>,
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn at(&mut self, segment: PathSegment, diff: impl FnOnce(&mut Self)) {
+        self.path.push(segment);
+        diff(self);
+        self.path.pop();
+    }
+
+    fn path(&self) -> String {
+        SpecPath(self.path.clone()).to_string()
+    }
+
+    fn path_to(&self, segment: PathSegment) -> String {
+        let mut path = self.path.clone();
+        path.push(segment);
+        SpecPath(path).to_string()
+    }
+
+    fn custom_type(&mut self, old: &CustomType, new: &CustomType) {
+        match (old, new) {
+            (
+                CustomType::Struct {
+                    fields: old_fields, ..
+                },
+                CustomType::Struct { fields, .. },
+            ) => self.fields(old_fields, fields, FieldType::to_string, Self::field_type),
+            (
+                CustomType::BitField {
+                    fields: old_fields, ..
+                },
+                CustomType::BitField { fields, .. },
+            ) => self.fields(
+                old_fields,
+                fields,
+                |bit_field| format!("{:?}", bit_field),
+                |differ, old, new| {
+                    let path = differ.path();
+                    differ.changes.push(Change::FieldRetyped {
+                        path,
+                        old_type: format!("{:?}", old),
+                        new_type: format!("{:?}", new),
+                    });
+                },
+            ),
+            (
+                CustomType::Enum {
+                    variant: old_variant,
+                    variants: old_variants,
+                    ..
+                },
+                CustomType::Enum {
+                    variant, variants, ..
+                },
+            ) => {
+                if old_variant != variant {
+                    let path = self.path();
+                    self.changes.push(Change::DiscriminantRetyped {
+                        path,
+                        old_type: old_variant.to_string(),
+                        new_type: variant.to_string(),
+                    });
+                }
+                for (literal, old_variant) in old_variants {
+                    if !variants.contains_key(literal) {
+                        let path = self.path_to(PathSegment::Variant(literal.clone()));
+                        self.changes.push(Change::VariantRemoved {
+                            path,
+                            name: old_variant.name().to_owned(),
+                        });
+                    }
+                }
+                for (literal, variant) in variants {
+                    let segment = PathSegment::Variant(literal.clone());
+                    match old_variants.get(literal) {
+                        None => {
+                            let path = self.path_to(segment);
+                            self.changes.push(Change::VariantAdded {
+                                path,
+                                name: variant.name().to_owned(),
+                            });
+                        }
+                        Some(old_variant) => self.at(segment, |differ| {
+                            if old_variant.name() != variant.name() {
+                                let path = differ.path();
+                                differ.changes.push(Change::VariantRenamed {
+                                    path,
+                                    old_name: old_variant.name().to_owned(),
+                                    new_name: variant.name().to_owned(),
+                                });
+                            }
+                            differ.custom_type(old_variant, variant);
+                        }),
+                    }
+                }
+            }
+            (
+                CustomType::BitFlags {
+                    field_type: old_field_type,
+                    flags: old_flags,
+                    ..
+                },
+                CustomType::BitFlags {
+                    field_type, flags, ..
+                },
+            ) => {
+                if old_field_type != field_type {
+                    let path = self.path();
+                    self.changes.push(Change::FieldRetyped {
+                        path,
+                        old_type: format!("{:?}", old_field_type),
+                        new_type: format!("{:?}", field_type),
+                    });
+                }
+                for (bits, name) in old_flags {
+                    if flags.get(bits) != Some(name) {
+                        let path = self.path();
+                        self.changes.push(Change::FlagRemoved {
+                            path,
+                            bits: *bits,
+                            name: name.to_string(),
+                        });
+                    }
+                }
+                for (bits, name) in flags {
+                    if old_flags.get(bits) != Some(name) {
+                        let path = self.path();
+                        self.changes.push(Change::FlagAdded {
+                            path,
+                            bits: *bits,
+                            name: name.to_string(),
+                        });
+                    }
+                }
+            }
+            (CustomType::Unit { .. }, CustomType::Unit { .. }) => {}
+            _ => {
+                let path = self.path();
+                self.changes.push(Change::FieldRetyped {
+                    path,
+                    old_type: FieldType::CustomType(old.clone()).to_string(),
+                    new_type: FieldType::CustomType(new.clone()).to_string(),
+                });
+            }
+        }
+    }
+
+    /// Compares the fields of two structs or bit fields by name.
+    fn fields<T: PartialEq>(
+        &mut self,
+        old_fields: &IndexMap<FieldName, T>,
+        fields: &IndexMap<FieldName, T>,
+        describe: impl Fn(&T) -> String,
+        mut changed: impl FnMut(&mut Self, &T, &T),
+    ) {
+        for (field_name, old_type) in old_fields {
+            if !fields.contains_key(field_name) {
+                let path = self.path_to(PathSegment::Field(field_name.clone()));
+                self.changes.push(Change::FieldRemoved {
+                    path,
+                    field_type: describe(old_type),
+                });
+            }
+        }
+        for (field_name, field_type) in fields {
+            let segment = PathSegment::Field(field_name.clone());
+            match old_fields.get(field_name) {
+                None => {
+                    let path = self.path_to(segment);
+                    self.changes.push(Change::FieldAdded {
+                        path,
+                        field_type: describe(field_type),
+                    });
+                }
+                Some(old_type) if old_type != field_type => {
+                    self.at(segment, |differ| changed(differ, old_type, field_type))
+                }
+                Some(_) => {}
+            }
+        }
+
+        let old_order: Vec<_> = old_fields
+            .keys()
+            .filter(|field_name| fields.contains_key(*field_name))
+            .map(|field_name| field_name.to_string())
+            .collect();
+        let new_order: Vec<_> = fields
+            .keys()
+            .filter(|field_name| old_fields.contains_key(*field_name))
+            .map(|field_name| field_name.to_string())
+            .collect();
+        if old_order != new_order {
+            let path = self.path();
+            self.changes.push(Change::FieldsReordered {
+                path,
+                old_order,
+                new_order,
+            });
+        }
+    }
+
+    /// Compares two versions of a field's type, descending into nested custom types
+    /// as long as the shape around them is unchanged.
+    fn field_type(&mut self, old: &FieldType, new: &FieldType) {
+        // Changes to documentation and defaults are not structural.
+        let (old, new) = (old.unannotated(), new.unannotated());
+        match (old, new) {
+            (FieldType::CustomType(old), FieldType::CustomType(new))
+                if std::mem::discriminant(old) == std::mem::discriminant(new) =>
+            {
+                self.custom_type(old, new)
+            }
+            (
+                FieldType::Primitive(PrimitiveType::Array {
+                    length: old_length,
+                    field_type: old,
+                }),
+                FieldType::Primitive(PrimitiveType::Array { length, field_type }),
+            ) if old_length == length => self.field_type(old, field_type),
+            (
+                FieldType::Primitive(PrimitiveType::Option(old)),
+                FieldType::Primitive(PrimitiveType::Option(new)),
+            ) => self.field_type(old, new),
+            (
+                FieldType::Conditional {
+                    condition: old_condition,
+                    field_type: old,
+                },
+                FieldType::Conditional {
+                    condition,
+                    field_type,
+                },
+            ) if old_condition == condition => self.field_type(old, field_type),
+            _ if old != new => {
+                let path = self.path();
+                self.changes.push(Change::FieldRetyped {
+                    path,
+                    old_type: old.to_string(),
+                    new_type: new.to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::*;
+
+/// The packets an edit gave another ID, from their old identifier to their new one.
+pub type Renumbering = BTreeMap<PacketIdentifier, PacketIdentifier>;
+
+impl Protocol {
+    /// Adds a packet, moving the packets of its direction and stage from its ID on one ID up.
+    pub fn insert_packet(
+        &mut self,
+        identifier: PacketIdentifier,
+        packet: CustomType,
+    ) -> Renumbering {
+        let renumbering = self.shift(&identifier, *identifier.id(), 1);
+        self.packets.insert(identifier, packet);
+        renumbering
+    }
+
+    /// Removes a packet, moving the packets of its direction and stage after it one ID down.
+    pub fn remove_packet(
+        &mut self,
+        identifier: &PacketIdentifier,
+    ) -> Result<(CustomType, Renumbering), String> {
+        let packet = self.packets.remove(identifier).ok_or_else(|| {
+            format!(
+                "There is no packet {} to remove.",
+                SpecPath(vec![PathSegment::Packet(identifier.clone())])
+            )
+        })?;
+        let renumbering = self.shift(identifier, *identifier.id() + 1, -1);
+        Ok((packet, renumbering))
+    }
+
+    /// Gives a packet another ID within its direction and stage, closing the gap it leaves
+    /// and making room for it at the new ID.
+    pub fn move_packet(
+        &mut self,
+        identifier: &PacketIdentifier,
+        id: PacketId,
+    ) -> Result<Renumbering, String> {
+        let (packet, removed) = self.remove_packet(identifier)?;
+        let moved = PacketIdentifier(identifier.direction(), identifier.stage(), id);
+        let inserted = self.insert_packet(moved.clone(), packet);
+
+        // Follow each packet through both edits.
+        let mut renumbering = Renumbering::new();
+        for (old, new) in removed.iter() {
+            let new = inserted.get(new).unwrap_or(new);
+            renumbering.insert(old.clone(), new.clone());
+        }
+        for (old, new) in inserted {
+            if !removed.values().any(|removed| *removed == old) {
+                renumbering.insert(old, new);
+            }
+        }
+        renumbering.insert(identifier.clone(), moved);
+        renumbering.retain(|old, new| old != new);
+        Ok(renumbering)
+    }
+
+    /// Adds `delta` to the IDs of the packets in the direction and stage of `identifier`
+    /// from `from` on.
+    fn shift(&mut self, identifier: &PacketIdentifier, from: u64, delta: i64) -> Renumbering {
+        let shifted: Vec<_> = self
+            .packets
+            .keys()
+            .filter(|other| {
+                other.direction() == identifier.direction()
+                    && other.stage() == identifier.stage()
+                    && *other.id() >= from
+            })
+            .cloned()
+            .collect();
+
+        let mut renumbering = Renumbering::new();
+        let mut packets = Vec::new();
+        for old in shifted {
+            let packet = self.packets.remove(&old).unwrap();
+            let id = (*old.id() as i64 + delta) as u64;
+            let new = PacketIdentifier(old.direction(), old.stage(), id.into());
+            renumbering.insert(old, new.clone());
+            packets.push((new, packet));
+        }
+        self.packets.extend(packets);
+        renumbering
+    }
+}
@@ -0,0 +1,581 @@
+//! The file formats a spec can be written in, chosen by file extension.
+//!
+//! RON is the canonical format. JSON and YAML renditions go through the same serde model,
+//! except that map keys are always written as strings there: booleans as `"true"` and
+//! `"false"`, and packet identifiers as the RON tuple `"(Client, Play, 0)"`. Integers are
+//! left to the format, which writes them as strings where it has to.
+//!
+//! TOML is not supported, as its tables are unordered and the order of struct fields matters.
+
+use crate::{source, Renumbering, Spec};
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Error as _, Impossible, Serialize, Serializer};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ron,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// The format of a file by its extension, where files without one are RON.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Format> {
+        match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+            None | Some("ron") => Some(Format::Ron),
+            Some("json") => Some(Format::Json),
+            Some("yaml") | Some("yml") => Some(Format::Yaml),
+            Some(_) => None,
+        }
+    }
+
+    pub fn from_str<T: DeserializeOwned>(self, source: &str) -> Result<T, String> {
+        match self {
+            Format::Ron => ron::de::from_str(source).map_err(|e| e.to_string()),
+            Format::Json => serde_json::from_str(source).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::from_str(source).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn to_string<T: Serialize>(self, value: &T) -> Result<String, String> {
+        match self {
+            Format::Ron => {
+                ron::ser::to_string_pretty(value, Default::default()).map_err(|e| e.to_string())
+            }
+            Format::Json => {
+                serde_json::to_string_pretty(&StringKeys(value)).map_err(|e| e.to_string())
+            }
+            Format::Yaml => serde_yaml::to_string(&StringKeys(value)).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Formats a spec written in this format. Maps are sorted by key, everything else keeps
+    /// its order. Comments are kept in RON, and YAML with comments is refused instead of
+    /// losing them.
+    pub fn format(self, source: &str) -> Result<String, String> {
+        self.rewrite(source, &Spec::parse(source, self)?, &Renumbering::new())
+    }
+
+    /// Renders `spec`, an edited version of the spec in `source`, keeping the comments of
+    /// `source` as [`Format::format`] does.
+    pub fn rewrite(
+        self,
+        source: &str,
+        spec: &Spec,
+        renumbering: &Renumbering,
+    ) -> Result<String, String> {
+        let canonical = spec.to_string(self)?;
+        let mut formatted = match self {
+            Format::Ron => source::keep_comments(source, &canonical, renumbering)
+                .ok_or_else(|| "the spec is not well-formed RON".to_owned())?,
+            Format::Json => canonical,
+            Format::Yaml => {
+                if source.lines().any(|line| line.trim_start().starts_with('#')) {
+                    return Err("comments in YAML specs would be lost".to_owned());
+                }
+                canonical
+            }
+        };
+        // The file keeps its final newline, which the serializers leave out.
+        if source.ends_with('\n') && !formatted.ends_with('\n') {
+            formatted.push('\n');
+        }
+        Ok(formatted)
+    }
+}
+
+/// Serializes a value with every map key in it written by [`MapKey`].
+struct StringKeys<'a, T: ?Sized>(&'a T);
+
+impl<T: Serialize + ?Sized> Serialize for StringKeys<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(StringKeysSerializer(serializer))
+    }
+}
+
+/// Forwards to the wrapped serializer or compound, wrapping every nested value in [`StringKeys`].
+struct StringKeysSerializer<S>(S);
+
+impl<S: Serializer> Serializer for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = StringKeysSerializer<S::SerializeSeq>;
+    type SerializeTuple = StringKeysSerializer<S::SerializeTuple>;
+    type SerializeTupleStruct = StringKeysSerializer<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = StringKeysSerializer<S::SerializeTupleVariant>;
+    type SerializeMap = StringKeysSerializer<S::SerializeMap>;
+    type SerializeStruct = StringKeysSerializer<S::SerializeStruct>;
+    type SerializeStructVariant = StringKeysSerializer<S::SerializeStructVariant>;
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.0.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.0.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.0.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.0.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_none()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.0.serialize_some(&StringKeys(value))
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_newtype_struct(name, &StringKeys(value))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.0
+            .serialize_newtype_variant(name, variant_index, variant, &StringKeys(value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        self.0.serialize_seq(len).map(StringKeysSerializer)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        self.0.serialize_tuple(len).map(StringKeysSerializer)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.0.serialize_tuple_struct(name, len).map(StringKeysSerializer)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        self.0
+            .serialize_tuple_variant(name, variant_index, variant, len)
+            .map(StringKeysSerializer)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        self.0.serialize_map(len).map(StringKeysSerializer)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        self.0.serialize_struct(name, len).map(StringKeysSerializer)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        self.0
+            .serialize_struct_variant(name, variant_index, variant, len)
+            .map(StringKeysSerializer)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+impl<S: ser::SerializeSeq> ser::SerializeSeq for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.0.serialize_element(&StringKeys(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+impl<S: ser::SerializeTuple> ser::SerializeTuple for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.0.serialize_element(&StringKeys(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+impl<S: ser::SerializeTupleStruct> ser::SerializeTupleStruct for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.0.serialize_field(&StringKeys(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+impl<S: ser::SerializeTupleVariant> ser::SerializeTupleVariant for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.0.serialize_field(&StringKeys(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+impl<S: ser::SerializeMap> ser::SerializeMap for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), S::Error> {
+        self.0.serialize_key(&MapKey(key))
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.0.serialize_value(&StringKeys(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+impl<S: ser::SerializeStruct> ser::SerializeStruct for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), S::Error> {
+        self.0.serialize_field(key, &StringKeys(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+impl<S: ser::SerializeStructVariant> ser::SerializeStructVariant for StringKeysSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), S::Error> {
+        self.0.serialize_field(key, &StringKeys(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.0.end()
+    }
+}
+
+/// A map key, written as a string unless it is a number or a string already.
+struct MapKey<'a, T: ?Sized>(&'a T);
+
+impl<T: Serialize + ?Sized> Serialize for MapKey<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(MapKeySerializer(serializer))
+    }
+}
+
+struct MapKeySerializer<S>(S);
+
+fn unsupported_key<E: ser::Error>() -> E {
+    E::custom("map keys must be numbers, strings, booleans, unit variants or tuples of those")
+}
+
+impl<S: Serializer> Serializer for MapKeySerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Impossible<S::Ok, S::Error>;
+    type SerializeTuple = TupleKey<S>;
+    type SerializeTupleStruct = TupleKey<S>;
+    type SerializeTupleVariant = Impossible<S::Ok, S::Error>;
+    type SerializeMap = Impossible<S::Ok, S::Error>;
+    type SerializeStruct = Impossible<S::Ok, S::Error>;
+    type SerializeStructVariant = Impossible<S::Ok, S::Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.0.serialize_str(if v { "true" } else { "false" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.0.serialize_u64(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.0.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.0.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Ok(TupleKey {
+            serializer: self.0,
+            elements: Vec::new(),
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        Err(unsupported_key())
+    }
+}
+
+/// A tuple key, written as a string of its RON form.
+struct TupleKey<S> {
+    serializer: S,
+    elements: Vec<String>,
+}
+
+impl<S: Serializer> ser::SerializeTuple for TupleKey<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        let element = ron::ser::to_string(&value).map_err(S::Error::custom)?;
+        self.elements.push(element);
+        Ok(())
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.serializer
+            .serialize_str(&format!("({})", self.elements.join(", ")))
+    }
+}
+
+impl<S: Serializer> ser::SerializeTupleStruct for TupleKey<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        ser::SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        ser::SerializeTuple::end(self)
+    }
+}
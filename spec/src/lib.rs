@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -12,21 +13,58 @@ pub use custom_type::*;
 mod validation;
 pub use validation::*;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+mod diagnostic;
+pub use diagnostic::*;
+
+mod source;
+
+mod diff;
+pub use diff::*;
+
+mod patch;
+pub use patch::*;
+
+mod schema;
+pub use schema::*;
+
+mod format;
+pub use format::*;
+
+mod query;
+pub use query::*;
+
+mod edit;
+pub use edit::*;
+
+mod versions;
+pub use versions::*;
+
+mod reference;
+pub use reference::*;
+
+/// The version of the spec format read by this crate.
+pub const SPEC_VERSION: u64 = 1;
+
+/// The root of a spec file: a protocol along with the Minecraft versions using it.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Minecraft {
+    /// The version of the spec format, which has to be [`SPEC_VERSION`].
+    pub spec_version: u64,
     pub version: MinecraftVersion,
     pub major_version: MinecraftVersion,
     pub protocol: Protocol,
 }
 /// Defines all packets and field types for a protocol version.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Protocol {
     pub version: ProtocolVersion,
     /// The set of all packets defined for this protocol.
     ///
     /// Keys in this map are (packet_direction, packet_stage, packet_id, packet_name).
+    #[serde(deserialize_with = "literal_keys::deserialize_packets")]
     pub packets: BTreeMap<PacketIdentifier, CustomType>,
 
     /// Types which are shared across multiple packets.
+    #[serde(deserialize_with = "literal_keys::deserialize_shared_types")]
     pub shared_types: Vec<CustomType>,
 }
\ No newline at end of file
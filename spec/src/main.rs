@@ -1,38 +1,405 @@
 use std::fs;
 use std::env;
-use anyhow::{Result, anyhow};
-use feather_protocol_spec::{Protocol, Validation};
-use std::io::{Write, Seek, SeekFrom};
-
-fn main() -> Result<()> {
-    verify()?;
-    Ok(())
-}
-
-fn verify() -> Result<()> {
-    let path = env::args()
-        .skip(1)
-        .next()
-        .ok_or(anyhow!("Specify a file path to verify."))?;
-    let mut file = fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .append(false)
-        .open(&path)?;
-
-    let protocol: Protocol = ron::de::from_reader(&file)
-        .map_err(|e| anyhow!("{}", e))?;
-
-    protocol.validate()?;
-
-    let protocol_ser = ron::ser::to_string_pretty(&protocol, Default::default())?;
-    
-    file.seek(SeekFrom::Start(0))?;
-    let buf = protocol_ser.as_bytes();
-
-    file.write_all(buf)?;
-    file.set_len(buf.len() as u64)?;
-
-    Ok(())
-}
\ No newline at end of file
+use std::process;
+use std::path::Path;
+use anyhow::{Result, anyhow, bail};
+use feather_protocol_spec::{
+    diff_minecraft, layout, locate, reference, schema, Format, Markup, Minecraft, PacketFilter,
+    PacketIdentifier, PathSegment, Protocol, Renumbering, Spec, SpecPath, Validation,
+};
+
+const USAGE: &str = "usage:
+    feather-protocol-spec [verify] <path>
+    feather-protocol-spec fmt [--check] <path>...
+    feather-protocol-spec diff <old> <new> [--json]
+    feather-protocol-spec list <path> [--direction <direction>] [--stage <stage>]
+    feather-protocol-spec show <path> (<name> | <direction> <stage> <id>) [--direction <direction>] [--stage <stage>]
+    feather-protocol-spec uses <path> <shared type> [--direction <direction>] [--stage <stage>]
+    feather-protocol-spec insert <path> <direction> <stage> <id> <packet>
+    feather-protocol-spec remove <path> <direction> <stage> <id>
+    feather-protocol-spec move <path> <direction> <stage> <id> <new id>
+    feather-protocol-spec convert <input> <output>
+    feather-protocol-spec docs <path> [--html]
+    feather-protocol-spec schema";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("verify") => verify(&args[1..]),
+        Some("fmt") => format_specs(&args[1..]),
+        Some("diff") => diff_specs(&args[1..]),
+        Some("list") => list(&args[1..]),
+        Some("show") => show(&args[1..]),
+        Some("uses") => uses(&args[1..]),
+        Some("insert") => insert(&args[1..]),
+        Some("remove") => remove(&args[1..]),
+        Some("move") => move_packet(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("docs") => docs(&args[1..]),
+        Some("schema") => print_schema(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        _ => verify(&args),
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Validates the spec at the given path. A patch is validated as the protocol it resolves to.
+/// Returns whether the spec is free of errors.
+fn verify(args: &[String]) -> Result<bool> {
+    let path = match args {
+        [path] => path,
+        _ => bail!("Specify a file path to verify.\n{}", USAGE),
+    };
+    let format = format_of(path)?;
+    let source = fs::read_to_string(path)?;
+    let spec = Spec::parse(&source, format).map_err(|e| anyhow!("{}: {}", path, e))?;
+
+    let diagnostics = match &spec {
+        Spec::Minecraft(minecraft) => {
+            let mut diagnostics = minecraft.diagnostics();
+            if format == Format::Ron {
+                locate(&mut diagnostics, &source);
+            }
+            diagnostics
+        }
+        Spec::Patch(_) => spec.clone().resolve(directory(path))?.diagnostics(),
+    };
+    for diagnostic in diagnostics.iter() {
+        eprintln!("{}", diagnostic.render(path, &source));
+    }
+
+    let errors = diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    if warnings > 0 {
+        eprintln!("warning: `{}` generated {} {}", path, warnings, plural(warnings, "warning"));
+    }
+    if errors > 0 {
+        eprintln!(
+            "error: could not verify `{}` due to {} previous {}",
+            path,
+            errors,
+            plural(errors, "error")
+        );
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Rewrites specs in canonical form, keeping their comments. With `--check`, nothing is
+/// written and the specs which are not in canonical form are listed instead.
+/// Returns whether every spec was, or now is, in canonical form.
+fn format_specs(args: &[String]) -> Result<bool> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<_> = args.iter().filter(|arg| *arg != "--check").collect();
+    if paths.is_empty() {
+        bail!("Specify the file paths to format.\n{}", USAGE);
+    }
+
+    let mut formatted = true;
+    for path in paths {
+        let source = fs::read_to_string(path)?;
+        let canonical = format_of(path)?
+            .format(&source)
+            .map_err(|e| anyhow!("{}: {}", path, e))?;
+        if canonical == source {
+            continue;
+        }
+        if check {
+            eprintln!("`{}` is not formatted", path);
+            formatted = false;
+        } else {
+            fs::write(path, canonical)?;
+        }
+    }
+
+    Ok(formatted)
+}
+
+/// Prints the structural differences between two specs, one per line or as a JSON array.
+fn diff_specs(args: &[String]) -> Result<bool> {
+    let json = args.iter().any(|arg| arg == "--json");
+    let paths: Vec<_> = args.iter().filter(|arg| *arg != "--json").collect();
+    let (old_path, new_path) = match paths.as_slice() {
+        [old_path, new_path] => (old_path, new_path),
+        _ => bail!("Specify the two file paths to compare.\n{}", USAGE),
+    };
+    let old = Minecraft::load(old_path)?;
+    let new = Minecraft::load(new_path)?;
+
+    let changes = diff_minecraft(&old, &new);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else {
+        for change in changes.iter() {
+            println!("{}", change);
+        }
+        eprintln!("{} {}", changes.len(), plural(changes.len(), "change"));
+    }
+
+    Ok(true)
+}
+
+/// Prints the identifier and name of every packet passing the filter.
+fn list(args: &[String]) -> Result<bool> {
+    let (filter, args) = packet_filter(args)?;
+    let path = match args.as_slice() {
+        [path] => path,
+        _ => bail!("Specify a file path to list the packets of.\n{}", USAGE),
+    };
+    let minecraft = Minecraft::load(path)?;
+
+    for (identifier, packet) in minecraft.protocol.packets_matching(filter) {
+        println!("{} {}", packet_path(identifier), packet.name());
+    }
+
+    Ok(true)
+}
+
+/// Prints the layout of the packets or shared type with a name, or of a packet by identifier.
+fn show(args: &[String]) -> Result<bool> {
+    let (filter, args) = packet_filter(args)?;
+    let minecraft = match args.first() {
+        Some(path) => Minecraft::load(path)?,
+        None => bail!("Specify a file path and a packet or shared type.\n{}", USAGE),
+    };
+    let protocol = &minecraft.protocol;
+
+    let mut found = Vec::new();
+    match &args[1..] {
+        [name] => {
+            for (identifier, packet) in protocol.packets_named(name, filter) {
+                found.push((packet_path(identifier), packet));
+            }
+            if let Some(shared_type) = protocol.shared_type(name) {
+                let segment = PathSegment::SharedType(name.to_string().into());
+                found.push((SpecPath(vec![segment]).to_string(), shared_type));
+            }
+        }
+        [direction, stage, id] => {
+            let identifier = packet_identifier(direction, stage, id)?;
+            if let Some(packet) = protocol.packets.get(&identifier) {
+                found.push((packet_path(&identifier), packet));
+            }
+        }
+        _ => bail!("Specify a packet or shared type to show.\n{}", USAGE),
+    }
+    if found.is_empty() {
+        bail!("There is no such packet or shared type.");
+    }
+
+    for (path, custom_type) in found {
+        println!("{} {}", path, custom_type.name());
+        for line in layout(custom_type) {
+            let indent = "    ".repeat(line.depth + 1);
+            if line.wire_type.is_empty() {
+                println!("{}{}", indent, line.label);
+            } else {
+                println!("{}{}: {}", indent, line.label, line.wire_type);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Prints every field which refers to a shared type.
+fn uses(args: &[String]) -> Result<bool> {
+    let (filter, args) = packet_filter(args)?;
+    let (path, name) = match args.as_slice() {
+        [path, name] => (path, name),
+        _ => bail!("Specify a file path and a shared type.\n{}", USAGE),
+    };
+    let minecraft = Minecraft::load(path)?;
+    if minecraft.protocol.shared_type(name).is_none() {
+        bail!("There is no shared type \"{}\".", name);
+    }
+
+    for found in minecraft.protocol.uses(name, filter) {
+        println!("{}: {} in {}", found.path, found.field_type, found.owner);
+    }
+
+    Ok(true)
+}
+
+/// Takes `--direction` and `--stage` options out of the arguments.
+fn packet_filter(args: &[String]) -> Result<(PacketFilter, Vec<&String>)> {
+    let mut filter = PacketFilter::default();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--direction" | "--stage" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("`{}` needs a value.\n{}", arg, USAGE))?;
+                if arg == "--direction" {
+                    filter.direction = Some(value.parse().map_err(|e: String| anyhow!(e))?);
+                } else {
+                    filter.stage = Some(value.parse().map_err(|e: String| anyhow!(e))?);
+                }
+            }
+            _ => rest.push(arg),
+        }
+    }
+    Ok((filter, rest))
+}
+
+/// Adds a packet, given in the format of the spec, and renumbers the packets after it.
+fn insert(args: &[String]) -> Result<bool> {
+    let (path, identifier, packet) = match args {
+        [path, direction, stage, id, packet] => {
+            (path, packet_identifier(direction, stage, id)?, packet)
+        }
+        _ => bail!("Specify a file path, a packet identifier and the packet.\n{}", USAGE),
+    };
+    let packet = format_of(path)?
+        .from_str(packet)
+        .map_err(|e| anyhow!("The packet is malformed: {}", e))?;
+    edit(path, |protocol| Ok(protocol.insert_packet(identifier, packet)))
+}
+
+/// Removes a packet and renumbers the packets after it.
+fn remove(args: &[String]) -> Result<bool> {
+    let identifier = match args {
+        [_, direction, stage, id] => packet_identifier(direction, stage, id)?,
+        _ => bail!("Specify a file path and a packet identifier.\n{}", USAGE),
+    };
+    edit(&args[0], |protocol| {
+        protocol.remove_packet(&identifier).map(|(_, renumbering)| renumbering)
+    })
+}
+
+/// Gives a packet another ID and renumbers the packets in between.
+fn move_packet(args: &[String]) -> Result<bool> {
+    let (identifier, id) = match args {
+        [_, direction, stage, id, new_id] => {
+            (packet_identifier(direction, stage, id)?, packet_id(new_id)?)
+        }
+        _ => bail!("Specify a file path, a packet identifier and its new ID.\n{}", USAGE),
+    };
+    edit(&args[0], |protocol| protocol.move_packet(&identifier, id.into()))
+}
+
+/// Edits the protocol of the spec at the given path and rewrites it, keeping its comments,
+/// then lists the renumbered packets and verifies the result.
+fn edit(
+    path: &str,
+    edit: impl FnOnce(&mut Protocol) -> Result<Renumbering, String>,
+) -> Result<bool> {
+    let format = format_of(path)?;
+    let source = fs::read_to_string(path)?;
+    let spec = Spec::parse(&source, format).map_err(|e| anyhow!("{}: {}", path, e))?;
+    let mut minecraft = match spec {
+        Spec::Minecraft(minecraft) => minecraft,
+        Spec::Patch(patch) => {
+            let base = Path::new(path).parent().unwrap_or_else(|| Path::new("")).join(&patch.base);
+            bail!(
+                "`{}` is a patch, but editing commands need a full spec. Edit its base `{}` \
+                 instead.",
+                path,
+                base.display()
+            )
+        }
+    };
+
+    let renumbering = edit(&mut minecraft.protocol).map_err(|e| anyhow!(e))?;
+    let spec = Spec::Minecraft(minecraft);
+    let rewritten = format
+        .rewrite(&source, &spec, &renumbering)
+        .map_err(|e| anyhow!("{}: {}", path, e))?;
+    fs::write(path, rewritten)?;
+
+    for (old, new) in renumbering.iter() {
+        println!("{} -> {}", packet_path(old), packet_path(new));
+    }
+    verify(&[path.to_owned()])
+}
+
+/// Parses a packet identifier from its direction, stage and ID.
+fn packet_identifier(direction: &str, stage: &str, id: &str) -> Result<PacketIdentifier> {
+    Ok(PacketIdentifier(
+        direction.parse().map_err(|e: String| anyhow!(e))?,
+        stage.parse().map_err(|e: String| anyhow!(e))?,
+        packet_id(id)?.into(),
+    ))
+}
+
+/// Parses a packet ID, in decimal or in hexadecimal with a `0x` prefix.
+fn packet_id(id: &str) -> Result<u64> {
+    match id.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => id.parse(),
+    }
+    .map_err(|_| anyhow!("`{}` is not a packet ID", id))
+}
+
+fn packet_path(identifier: &PacketIdentifier) -> String {
+    SpecPath(vec![PathSegment::Packet(identifier.clone())]).to_string()
+}
+
+/// Rewrites a spec in the format of the output path. Patches stay patches.
+fn convert(args: &[String]) -> Result<bool> {
+    let (input, output) = match args {
+        [input, output] => (input, output),
+        _ => bail!("Specify the file paths to convert from and to.\n{}", USAGE),
+    };
+    let spec = Spec::read(input)?;
+    let spec_ser = spec
+        .to_string(format_of(output)?)
+        .map_err(|e| anyhow!("{}: {}", output, e))?;
+    fs::write(output, spec_ser)?;
+
+    Ok(true)
+}
+
+/// Prints a reference of the protocol of a spec, in Markdown or with `--html` in HTML.
+fn docs(args: &[String]) -> Result<bool> {
+    let html = args.iter().any(|arg| arg == "--html");
+    let paths: Vec<_> = args.iter().filter(|arg| *arg != "--html").collect();
+    let path = match paths.as_slice() {
+        [path] => path,
+        _ => bail!("Specify a file path to document.\n{}", USAGE),
+    };
+    let minecraft = Minecraft::load(path)?;
+
+    let markup = if html { Markup::Html } else { Markup::Markdown };
+    print!("{}", reference(&minecraft.protocol, markup));
+    Ok(true)
+}
+
+/// Prints the JSON Schema of spec files.
+fn print_schema(args: &[String]) -> Result<bool> {
+    if !args.is_empty() {
+        bail!("`schema` takes no arguments.\n{}", USAGE);
+    }
+    println!("{}", serde_json::to_string_pretty(&schema())?);
+    Ok(true)
+}
+
+fn format_of(path: &str) -> Result<Format> {
+    Format::from_path(path).ok_or_else(|| anyhow!("`{}` is not a .ron, .json or .yaml file", path))
+}
+
+fn directory(path: &str) -> &Path {
+    Path::new(path).parent().unwrap_or_else(|| Path::new(""))
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        noun.to_owned()
+    } else {
+        format!("{}s", noun)
+    }
+}
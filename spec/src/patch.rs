@@ -0,0 +1,215 @@
+use crate::*;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// A protocol described as a set of changes to another spec.
+///
+/// Changes are applied in the order of the fields below: packets are removed,
+/// then renumbered, then replaced or added; shared types are removed, then
+/// replaced or added by name.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProtocolPatch {
+    /// The version of the spec format, which has to be [`SPEC_VERSION`].
+    pub spec_version: u64,
+    /// The spec this one is derived from, relative to the patch file. It may be a patch itself.
+    pub base: String,
+    pub version: MinecraftVersion,
+    pub major_version: MinecraftVersion,
+    pub protocol_version: ProtocolVersion,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_packets: Vec<PacketIdentifier>,
+    /// Moves packets of the base, keyed by their ID there, to a new ID.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub renumber: BTreeMap<PacketIdentifier, PacketId>,
+    /// Packets which replace the packet with the same (renumbered) identifier, or are added.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        deserialize_with = "literal_keys::deserialize_packets"
+    )]
+    pub packets: BTreeMap<PacketIdentifier, CustomType>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_shared_types: Vec<CustomTypeName>,
+    /// Shared types which replace the shared type with the same name, or are added.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "literal_keys::deserialize_shared_types"
+    )]
+    pub shared_types: Vec<CustomType>,
+}
+
+/// Either form a spec file can take.
+#[derive(Debug, Clone)]
+pub enum Spec {
+    Minecraft(Minecraft),
+    Patch(ProtocolPatch),
+}
+
+/// The root fields of a spec which tell a patch apart from a full spec.
+#[derive(Deserialize)]
+struct Base {
+    #[serde(default)]
+    base: Option<serde::de::IgnoredAny>,
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("could not read `{}`: {}", .0.display(), .1)]
+    Io(PathBuf, #[source] io::Error),
+    #[error("`{}` is not a .ron, .json or .yaml file", .0.display())]
+    Format(PathBuf),
+    #[error("could not parse `{}`: {}", .0.display(), .1)]
+    Parse(PathBuf, String),
+    #[error("could not apply a patch to `{}`: {}", .0.display(), .1)]
+    Patch(PathBuf, String),
+    #[error("`{}` is its own base", .0.display())]
+    Cycle(PathBuf),
+}
+
+impl Spec {
+    /// Parses a spec, which is a patch if it names a `base`.
+    pub fn parse(source: &str, format: Format) -> Result<Spec, String> {
+        let is_patch = match format {
+            Format::Ron => match source::parse(source) {
+                Some(root) => root.field("base").is_some(),
+                None => false,
+            },
+            _ => format
+                .from_str::<Base>(source)
+                .map(|root| root.base.is_some())
+                .unwrap_or(false),
+        };
+        if is_patch {
+            format.from_str(source).map(Spec::Patch)
+        } else {
+            format.from_str(source).map(Spec::Minecraft)
+        }
+    }
+
+    /// Reads the spec at `path`, in the format of its extension.
+    pub fn read(path: impl AsRef<Path>) -> Result<Spec, LoadError> {
+        let path = path.as_ref();
+        let format = Format::from_path(path).ok_or_else(|| LoadError::Format(path.to_owned()))?;
+        let source = fs::read_to_string(path).map_err(|e| LoadError::Io(path.to_owned(), e))?;
+        Spec::parse(&source, format).map_err(|e| LoadError::Parse(path.to_owned(), e))
+    }
+
+    pub fn to_string(&self, format: Format) -> Result<String, String> {
+        match self {
+            Spec::Minecraft(minecraft) => format.to_string(minecraft),
+            Spec::Patch(patch) => format.to_string(patch),
+        }
+    }
+
+    /// Resolves this spec into a full one, loading the base of a patch relative to `directory`.
+    pub fn resolve(self, directory: &Path) -> Result<Minecraft, LoadError> {
+        self.resolve_from(directory, &mut Vec::new())
+    }
+
+    fn resolve_from(
+        self,
+        directory: &Path,
+        visited: &mut Vec<PathBuf>,
+    ) -> Result<Minecraft, LoadError> {
+        match self {
+            Spec::Minecraft(minecraft) => Ok(minecraft),
+            Spec::Patch(patch) => {
+                let base_path = directory.join(&patch.base);
+                let canonical = base_path
+                    .canonicalize()
+                    .unwrap_or_else(|_| base_path.clone());
+                if visited.contains(&canonical) {
+                    return Err(LoadError::Cycle(base_path));
+                }
+                visited.push(canonical);
+
+                let base = Spec::read(&base_path)?
+                    .resolve_from(base_path.parent().unwrap_or_else(|| Path::new("")), visited)?;
+                patch
+                    .apply(base)
+                    .map_err(|message| LoadError::Patch(base_path, message))
+            }
+        }
+    }
+}
+
+impl Minecraft {
+    /// Reads the spec at `path`, resolving it against its base if it is a patch.
+    pub fn load(path: impl AsRef<Path>) -> Result<Minecraft, LoadError> {
+        let path = path.as_ref();
+        Spec::read(path)?.resolve(path.parent().unwrap_or_else(|| Path::new("")))
+    }
+}
+
+impl ProtocolPatch {
+    /// Applies this patch to the spec it is based on.
+    pub fn apply(self, base: Minecraft) -> Result<Minecraft, String> {
+        let mut protocol = base.protocol;
+        protocol.version = self.protocol_version;
+
+        for identifier in self.remove_packets.iter() {
+            if protocol.packets.remove(identifier).is_none() {
+                return Err(format!(
+                    "There is no packet {} to remove.",
+                    SpecPath(vec![PathSegment::Packet(identifier.clone())])
+                ));
+            }
+        }
+
+        let mut renumbered = Vec::new();
+        for (identifier, id) in self.renumber.iter() {
+            let packet = protocol.packets.remove(identifier).ok_or_else(|| {
+                format!(
+                    "There is no packet {} to renumber.",
+                    SpecPath(vec![PathSegment::Packet(identifier.clone())])
+                )
+            })?;
+            let identifier = PacketIdentifier(identifier.direction(), identifier.stage(), *id);
+            renumbered.push((identifier, packet));
+        }
+        for (identifier, packet) in renumbered {
+            if let Some(existing) = protocol.packets.get(&identifier) {
+                return Err(format!(
+                    "Packet \"{}\" is renumbered to {}, which is taken by \"{}\".",
+                    packet.name(),
+                    SpecPath(vec![PathSegment::Packet(identifier.clone())]),
+                    existing.name()
+                ));
+            }
+            protocol.packets.insert(identifier, packet);
+        }
+
+        protocol.packets.extend(self.packets);
+
+        for name in self.remove_shared_types.iter() {
+            let index = protocol
+                .shared_types
+                .iter()
+                .position(|shared_type| shared_type.name() == name.as_str())
+                .ok_or_else(|| format!("There is no shared type \"{}\" to remove.", **name))?;
+            protocol.shared_types.remove(index);
+        }
+
+        for shared_type in self.shared_types {
+            match protocol
+                .shared_types
+                .iter_mut()
+                .find(|existing| existing.name() == shared_type.name())
+            {
+                Some(existing) => *existing = shared_type,
+                None => protocol.shared_types.push(shared_type),
+            }
+        }
+
+        Ok(Minecraft {
+            spec_version: self.spec_version,
+            version: self.version,
+            major_version: self.major_version,
+            protocol,
+        })
+    }
+}
@@ -1,6 +1,9 @@
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Copy, Serialize, Deserialize, JsonSchema)]
 pub enum PacketStage {
     Handshaking = 0,
     Status = 1,
@@ -8,12 +11,40 @@ pub enum PacketStage {
     Play = 3,
 }
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Copy, Serialize, Deserialize, JsonSchema)]
 pub enum PacketDirection {
     Client = 0,
     Server = 1,
 }
 
+/// Reads a direction by name, ignoring case.
+impl std::str::FromStr for PacketDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "client" => Ok(PacketDirection::Client),
+            "server" => Ok(PacketDirection::Server),
+            _ => Err(format!("`{}` is not a packet direction", s)),
+        }
+    }
+}
+
+/// Reads a stage by name, ignoring case.
+impl std::str::FromStr for PacketStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "handshaking" => Ok(PacketStage::Handshaking),
+            "status" => Ok(PacketStage::Status),
+            "login" => Ok(PacketStage::Login),
+            "play" => Ok(PacketStage::Play),
+            _ => Err(format!("`{}` is not a packet stage", s)),
+        }
+    }
+}
+
 macro_rules! wrap {
     ($outer:ident, $inner:ident) => {
         impl std::ops::Deref for $outer {
@@ -31,31 +62,31 @@ macro_rules! wrap {
     };
 }
 
-#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct PacketId(u64);
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct PacketName(String);
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct MinecraftVersion(String);
 
-#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct ProtocolVersion(u64);
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct FlagName(String);
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Hash)]
 #[serde(transparent)]
 pub struct FieldName(String);
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Hash)]
 #[serde(transparent)]
 pub struct CustomTypeName(String);
 
@@ -68,7 +99,7 @@ wrap!(FlagName, String);
 wrap!(CustomTypeName, String);
 
 
-#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Serialize, JsonSchema)]
 pub struct PacketIdentifier(pub PacketDirection, pub PacketStage, pub PacketId);
 
 impl PacketIdentifier {
@@ -83,4 +114,51 @@ impl PacketIdentifier {
     pub fn id(&self) -> PacketId {
         self.2
     }
-}
\ No newline at end of file
+}
+
+/// Reads a `(direction, stage, id)` tuple, or the same tuple in a string where map keys have
+/// to be strings.
+impl<'de> Deserialize<'de> for PacketIdentifier {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct IdentifierVisitor;
+
+        impl<'de> Visitor<'de> for IdentifierVisitor {
+            type Value = PacketIdentifier;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a (direction, stage, id) tuple")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let direction = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let stage = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let id = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                Ok(PacketIdentifier(direction, stage, id))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let (direction, stage, id) = ron::de::from_str(v).map_err(|_| {
+                    de::Error::invalid_value(de::Unexpected::Str(v), &self)
+                })?;
+                Ok(PacketIdentifier(direction, stage, id))
+            }
+        }
+
+        deserializer.deserialize_any(IdentifierVisitor)
+    }
+}
@@ -0,0 +1,214 @@
+use crate::*;
+
+/// Narrows packets down to a direction and a stage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacketFilter {
+    pub direction: Option<PacketDirection>,
+    pub stage: Option<PacketStage>,
+}
+
+impl PacketFilter {
+    pub fn matches(&self, identifier: &PacketIdentifier) -> bool {
+        let direction = self.direction.unwrap_or_else(|| identifier.direction());
+        let stage = self.stage.unwrap_or_else(|| identifier.stage());
+        direction == identifier.direction() && stage == identifier.stage()
+    }
+}
+
+/// A field which refers to a shared type, see [`Protocol::uses`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Use {
+    /// The name of the packet or shared type the field is in.
+    pub owner: String,
+    pub path: SpecPath,
+    /// The wire type of the field, which may wrap the shared type in arrays and options.
+    pub field_type: String,
+}
+
+/// One line of the flattened layout of a custom type, see [`layout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutLine {
+    pub depth: usize,
+    /// A field name, or the key and name of an enum variant.
+    pub label: String,
+    /// Empty for unit variants.
+    pub wire_type: String,
+}
+
+impl Protocol {
+    /// The packets passing `filter`, ordered by identifier.
+    pub fn packets_matching(
+        &self,
+        filter: PacketFilter,
+    ) -> impl Iterator<Item = (&PacketIdentifier, &CustomType)> {
+        self.packets
+            .iter()
+            .filter(move |(identifier, _)| filter.matches(identifier))
+    }
+
+    /// The packets passing `filter` which are called `name`. Packets in different directions
+    /// or stages may share a name.
+    pub fn packets_named(
+        &self,
+        name: &str,
+        filter: PacketFilter,
+    ) -> Vec<(&PacketIdentifier, &CustomType)> {
+        self.packets_matching(filter)
+            .filter(|(_, packet)| packet.name() == name)
+            .collect()
+    }
+
+    pub fn shared_type(&self, name: &str) -> Option<&CustomType> {
+        self.shared_types
+            .iter()
+            .find(|shared_type| shared_type.name() == name)
+    }
+
+    /// Every field whose type refers to the shared type `name`, in the packets passing
+    /// `filter` and in the other shared types.
+    pub fn uses(&self, name: &str, filter: PacketFilter) -> Vec<Use> {
+        let mut finder = Finder {
+            name,
+            owner: String::new(),
+            path: Vec::new(),
+            uses: Vec::new(),
+        };
+        for (identifier, packet) in self.packets_matching(filter) {
+            finder.owner = packet.name().to_owned();
+            finder.at(PathSegment::Packet(identifier.clone()), |finder| {
+                finder.custom_type(packet)
+            });
+        }
+        for shared_type in self.shared_types.iter() {
+            finder.owner = shared_type.name().to_owned();
+            let segment = PathSegment::SharedType(shared_type.name().to_owned().into());
+            finder.at(segment, |finder| finder.custom_type(shared_type));
+        }
+        finder.uses
+    }
+}
+
+struct Finder<'a> {
+    name: &'a str,
+    owner: String,
+    path: Vec<PathSegment>,
+    uses: Vec<Use>,
+}
+
+impl Finder<'_> {
+    fn at(&mut self, segment: PathSegment, find: impl FnOnce(&mut Self)) {
+        self.path.push(segment);
+        find(self);
+        self.path.pop();
+    }
+
+    fn custom_type(&mut self, custom_type: &CustomType) {
+        match custom_type {
+            CustomType::Struct { fields, .. } => {
+                for (name, field_type) in fields.iter() {
+                    self.at(PathSegment::Field(name.clone()), |finder| {
+                        finder.field_type(field_type, &field_type.to_string())
+                    });
+                }
+            }
+            CustomType::Enum {
+                variant, variants, ..
+            } => {
+                self.field_type(variant, &variant.to_string());
+                for (literal, variant) in variants.iter() {
+                    self.at(PathSegment::Variant(literal.clone()), |finder| {
+                        finder.custom_type(variant)
+                    });
+                }
+            }
+            CustomType::BitField { .. } | CustomType::BitFlags { .. } | CustomType::Unit { .. } => {}
+        }
+    }
+
+    /// Looks for the shared type in the type of the field at the current path, whose
+    /// whole type is `wire_type`.
+    fn field_type(&mut self, field_type: &FieldType, wire_type: &str) {
+        match field_type {
+            FieldType::Primitive(PrimitiveType::Shared(name)) if name.as_str() == self.name => {
+                self.uses.push(Use {
+                    owner: self.owner.clone(),
+                    path: SpecPath(self.path.clone()),
+                    field_type: wire_type.to_owned(),
+                })
+            }
+            FieldType::Primitive(PrimitiveType::Array { field_type, .. })
+            | FieldType::Primitive(PrimitiveType::Option(field_type))
+            | FieldType::Conditional { field_type, .. }
+            | FieldType::Annotated { field_type, .. }
+            | FieldType::Const { field_type, .. } => self.field_type(field_type, wire_type),
+            FieldType::CustomType(custom_type) => self.custom_type(custom_type),
+            _ => {}
+        }
+    }
+}
+
+/// Flattens a custom type into a line for each field, enum variant and flag, with nested
+/// custom types indented below the field they are in. Shared types are not expanded.
+pub fn layout(custom_type: &CustomType) -> Vec<LayoutLine> {
+    let mut lines = Vec::new();
+    layout_into(custom_type, 0, &mut lines);
+    lines
+}
+
+fn layout_into(custom_type: &CustomType, depth: usize, lines: &mut Vec<LayoutLine>) {
+    let line = |depth, label, wire_type| LayoutLine {
+        depth,
+        label,
+        wire_type,
+    };
+    match custom_type {
+        CustomType::Struct { fields, .. } => {
+            for (name, field_type) in fields.iter() {
+                lines.push(line(depth, name.to_string(), field_type.to_string()));
+                if let Some(nested) = nested_custom_type(field_type) {
+                    layout_into(nested, depth + 1, lines);
+                }
+            }
+        }
+        CustomType::Enum {
+            variant, variants, ..
+        } => {
+            lines.push(line(depth, "variant".to_owned(), variant.to_string()));
+            for (literal, variant) in variants.iter() {
+                let wire_type = match variant {
+                    CustomType::Unit { .. } => String::new(),
+                    variant => FieldType::CustomType(variant.clone()).to_string(),
+                };
+                lines.push(line(depth, format!("{} => {}", literal, variant.name()), wire_type));
+                layout_into(variant, depth + 1, lines);
+            }
+        }
+        CustomType::BitField { fields, .. } => {
+            for (name, bit_field) in fields.iter() {
+                lines.push(line(depth, name.to_string(), format!("{:?}", bit_field)));
+            }
+        }
+        CustomType::BitFlags {
+            field_type, flags, ..
+        } => {
+            lines.push(line(depth, "bits".to_owned(), format!("{:?}", field_type)));
+            for (bits, name) in flags.iter() {
+                lines.push(line(depth, format!("{:#04X}", bits), name.to_string()));
+            }
+        }
+        CustomType::Unit { .. } => {}
+    }
+}
+
+/// The custom type a field holds, possibly in an array, option or condition.
+pub(crate) fn nested_custom_type(field_type: &FieldType) -> Option<&CustomType> {
+    match field_type {
+        FieldType::CustomType(custom_type) => Some(custom_type),
+        FieldType::Primitive(PrimitiveType::Array { field_type, .. })
+        | FieldType::Primitive(PrimitiveType::Option(field_type))
+        | FieldType::Conditional { field_type, .. }
+        | FieldType::Annotated { field_type, .. }
+        | FieldType::Const { field_type, .. } => nested_custom_type(field_type),
+        _ => None,
+    }
+}
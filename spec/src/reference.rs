@@ -0,0 +1,474 @@
+use crate::*;
+use std::fmt::Write;
+
+/// The markup a protocol reference is rendered in, see [`reference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    Markdown,
+    Html,
+}
+
+/// Renders a reference of a protocol in the style of https://wiki.vg/Protocol.
+///
+/// Every packet gets a table, grouped by stage and direction, listing its ID, stage, direction
+/// and fields with their wire types. Nested structs and enums are laid out inline below the
+/// field holding them, and shared types get a table of their own which their uses link to.
+/// Only what the spec says is shown, so the reference describes exactly what the codec does.
+pub fn reference(protocol: &Protocol, markup: Markup) -> String {
+    let mut renderer = Renderer {
+        markup,
+        out: String::new(),
+    };
+    renderer.heading(1, &format!("Protocol {}", *protocol.version), None);
+
+    let stages = [
+        PacketStage::Handshaking,
+        PacketStage::Status,
+        PacketStage::Login,
+        PacketStage::Play,
+    ];
+    for &stage in stages.iter() {
+        let filter = PacketFilter {
+            direction: None,
+            stage: Some(stage),
+        };
+        if protocol.packets_matching(filter).next().is_none() {
+            continue;
+        }
+        renderer.heading(2, &format!("{:?}", stage), None);
+
+        for &direction in [PacketDirection::Client, PacketDirection::Server].iter() {
+            let filter = PacketFilter {
+                direction: Some(direction),
+                stage: Some(stage),
+            };
+            if protocol.packets_matching(filter).next().is_none() {
+                continue;
+            }
+            renderer.heading(3, &format!("{:?}bound", direction), None);
+            for (identifier, packet) in protocol.packets_matching(filter) {
+                renderer.heading(4, packet.name(), None);
+                renderer.description(packet);
+                renderer.packet_table(identifier, &renderer.rows(packet));
+            }
+        }
+    }
+
+    if !protocol.shared_types.is_empty() {
+        renderer.heading(2, "Shared Types", None);
+        for shared_type in protocol.shared_types.iter() {
+            let anchor = shared_anchor(shared_type.name());
+            renderer.heading(3, shared_type.name(), Some(&anchor));
+            renderer.description(shared_type);
+            renderer.table(&[], &renderer.rows(shared_type));
+        }
+    }
+
+    renderer.out
+}
+
+fn shared_anchor(name: &str) -> String {
+    format!("shared-{}", name)
+}
+
+/// A table row for a field, enum variant, bit field or flag, with its cells already marked up.
+struct Row {
+    depth: usize,
+    name: String,
+    wire_type: String,
+    notes: Vec<String>,
+}
+
+struct Renderer {
+    markup: Markup,
+    out: String,
+}
+
+impl Renderer {
+    fn heading(&mut self, level: usize, text: &str, anchor: Option<&str>) {
+        let text = self.escape(text);
+        match self.markup {
+            Markup::Markdown => {
+                if let Some(anchor) = anchor {
+                    writeln!(self.out, "<a id=\"{}\"></a>\n", anchor).unwrap();
+                }
+                writeln!(self.out, "{} {}\n", "#".repeat(level), text).unwrap();
+            }
+            Markup::Html => {
+                let id = anchor
+                    .map(|anchor| format!(" id=\"{}\"", anchor))
+                    .unwrap_or_default();
+                writeln!(self.out, "<h{0}{1}>{2}</h{0}>", level, id, text).unwrap();
+            }
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        match self.markup {
+            Markup::Markdown => writeln!(self.out, "{}\n", text).unwrap(),
+            Markup::Html => writeln!(self.out, "<p>{}</p>", text).unwrap(),
+        }
+    }
+
+    /// The documentation of a packet or shared type, along with its wire type unless it is a
+    /// struct, whose fields the table already shows.
+    fn description(&mut self, custom_type: &CustomType) {
+        if !matches!(custom_type, CustomType::Struct { .. }) {
+            let wire_type = self.custom_type_kind(custom_type);
+            self.paragraph(&format!("Wire type: {}", wire_type));
+        }
+        let notes = self.custom_type_notes(custom_type);
+        if !notes.is_empty() {
+            self.paragraph(&notes.join(" "));
+        }
+    }
+
+    /// A table of the rows of a packet, with its ID, stage and direction spanning all of them.
+    fn packet_table(&mut self, identifier: &PacketIdentifier, rows: &[Row]) {
+        let packet_cells = [
+            self.code(&format!("{:#04x}", *identifier.id())),
+            format!("{:?}", identifier.stage()),
+            format!("{:?}", identifier.direction()),
+        ];
+        self.table(&packet_cells, rows);
+    }
+
+    fn table(&mut self, packet_cells: &[String], rows: &[Row]) {
+        let mut headers = if packet_cells.is_empty() {
+            Vec::new()
+        } else {
+            vec!["Packet ID", "State", "Bound To"]
+        };
+        headers.extend(["Field Name", "Field Type", "Notes"].iter());
+
+        let no_fields = [Row {
+            depth: 0,
+            name: "no fields".to_owned(),
+            wire_type: String::new(),
+            notes: Vec::new(),
+        }];
+        let rows = if rows.is_empty() { &no_fields } else { rows };
+
+        match self.markup {
+            Markup::Markdown => {
+                writeln!(self.out, "| {} |", headers.join(" | ")).unwrap();
+                writeln!(self.out, "|{}", " --- |".repeat(headers.len())).unwrap();
+                for (index, row) in rows.iter().enumerate() {
+                    let mut cells: Vec<String> = match index {
+                        0 => packet_cells.to_vec(),
+                        _ => vec![String::new(); packet_cells.len()],
+                    };
+                    cells.push(format!("{}{}", "&emsp;".repeat(row.depth), row.name));
+                    cells.push(row.wire_type.clone());
+                    cells.push(row.notes.join(" "));
+                    writeln!(self.out, "| {} |", cells.join(" | ")).unwrap();
+                }
+                writeln!(self.out).unwrap();
+            }
+            Markup::Html => {
+                writeln!(self.out, "<table>").unwrap();
+                let headers: String = headers
+                    .iter()
+                    .map(|header| format!("<th>{}</th>", header))
+                    .collect();
+                writeln!(self.out, "<tr>{}</tr>", headers).unwrap();
+                for (index, row) in rows.iter().enumerate() {
+                    let mut cells = String::new();
+                    if index == 0 {
+                        for cell in packet_cells.iter() {
+                            write!(cells, "<td rowspan=\"{}\">{}</td>", rows.len(), cell).unwrap();
+                        }
+                    }
+                    write!(
+                        cells,
+                        "<td>{}{}</td><td>{}</td><td>{}</td>",
+                        "&emsp;".repeat(row.depth),
+                        row.name,
+                        row.wire_type,
+                        row.notes.join(" ")
+                    )
+                    .unwrap();
+                    writeln!(self.out, "<tr>{}</tr>", cells).unwrap();
+                }
+                writeln!(self.out, "</table>").unwrap();
+            }
+        }
+    }
+
+    fn rows(&self, custom_type: &CustomType) -> Vec<Row> {
+        let mut rows = Vec::new();
+        self.custom_type_rows(custom_type, 0, &mut rows);
+        rows
+    }
+
+    fn custom_type_rows(&self, custom_type: &CustomType, depth: usize, rows: &mut Vec<Row>) {
+        match custom_type {
+            CustomType::Struct { fields, .. } => {
+                for (name, field_type) in fields.iter() {
+                    rows.push(Row {
+                        depth,
+                        name: self.escape(name),
+                        wire_type: self.wire_type(field_type),
+                        notes: self.field_notes(field_type),
+                    });
+                    if let Some(nested) = nested_custom_type(field_type) {
+                        self.custom_type_rows(nested, depth + 1, rows);
+                    }
+                }
+            }
+            CustomType::Enum {
+                variant, variants, ..
+            } => {
+                for (literal, variant_type) in variants.iter() {
+                    let literal = self.code(&literal.to_string());
+                    let mut notes = vec![match &**variant {
+                        FieldType::Key(field) => {
+                            format!("When {} is {}.", self.code(field), literal)
+                        }
+                        _ => format!("Written as {}.", literal),
+                    }];
+                    notes.extend(self.custom_type_notes(variant_type));
+                    let wire_type = match variant_type {
+                        CustomType::Unit { .. } => String::new(),
+                        variant_type => self.custom_type_kind(variant_type),
+                    };
+                    rows.push(Row {
+                        depth,
+                        name: self.escape(variant_type.name()),
+                        wire_type,
+                        notes,
+                    });
+                    self.custom_type_rows(variant_type, depth + 1, rows);
+                }
+            }
+            CustomType::BitField { fields, .. } => {
+                let total_width: u32 = fields.values().map(|field| u32::from(field.width())).sum();
+                let mut offset = 0;
+                for (name, field) in fields.iter() {
+                    // The first field occupies the most significant bits.
+                    let width = u32::from(field.width());
+                    offset += width;
+                    let lowest = total_width - offset;
+                    let bits = match width {
+                        1 => format!("Bit {}.", lowest),
+                        _ => format!("Bits {} to {}.", lowest + width - 1, lowest),
+                    };
+                    let wire_type = match field {
+                        BitField::Boolean => "Boolean".to_owned(),
+                        field => {
+                            let integer_type = format!("{:?}", field);
+                            let integer_type = &integer_type[..integer_type.find('(').unwrap()];
+                            format!("{} ({} bits)", integer_type, width)
+                        }
+                    };
+                    rows.push(Row {
+                        depth,
+                        name: self.escape(name),
+                        wire_type,
+                        notes: vec![bits],
+                    });
+                }
+            }
+            CustomType::BitFlags { flags, .. } => {
+                for (mask, name) in flags.iter() {
+                    rows.push(Row {
+                        depth,
+                        name: self.escape(name),
+                        wire_type: "Flag".to_owned(),
+                        notes: vec![format!("Mask {}.", self.code(&format!("{:#04x}", mask)))],
+                    });
+                }
+            }
+            CustomType::Unit { .. } => {}
+        }
+    }
+
+    /// The wire type of a field, linking to the shared types it refers to.
+    fn wire_type(&self, field_type: &FieldType) -> String {
+        match field_type {
+            FieldType::Integer(integer_type) => format!("{:?}", integer_type),
+            FieldType::Float(float_type) => format!("{:?}", float_type),
+            FieldType::Primitive(primitive_type) => match primitive_type {
+                PrimitiveType::Boolean => "Boolean".to_owned(),
+                PrimitiveType::Uuid => "UUID".to_owned(),
+                PrimitiveType::String(max_len) => format!("String ({})", max_len),
+                PrimitiveType::Nbt => "NBT".to_owned(),
+                PrimitiveType::EntityMetadata => "Entity Metadata".to_owned(),
+                PrimitiveType::Option(field_type) => {
+                    format!("Optional {}", self.wire_type(field_type))
+                }
+                PrimitiveType::Shared(name) => {
+                    self.link(name, &format!("#{}", shared_anchor(name)))
+                }
+                PrimitiveType::Array { length, field_type } => {
+                    let element = self.wire_type(field_type);
+                    match length {
+                        ArrayLength::RemainingLength => {
+                            format!("Array of {} (to the end of the packet)", element)
+                        }
+                        ArrayLength::FixedLength(length) => {
+                            format!("Array of {} {}", length, element)
+                        }
+                        ArrayLength::Prefixed(integer_type) => {
+                            format!("Array of {} ({:?} length)", element, integer_type)
+                        }
+                        ArrayLength::Field(field) => {
+                            format!("Array of {} (length in {})", element, self.code(field))
+                        }
+                        ArrayLength::Terminated(integer_type, terminator) => format!(
+                            "Array of {} (ended by the {:?} {})",
+                            element, integer_type, terminator
+                        ),
+                    }
+                }
+            },
+            FieldType::CustomType(custom_type) => self.custom_type_kind(custom_type),
+            FieldType::Conditional { field_type, .. }
+            | FieldType::Annotated { field_type, .. }
+            | FieldType::Const { field_type, .. } => self.wire_type(field_type),
+            FieldType::Key(field) => format!("Key ({})", self.code(field)),
+        }
+    }
+
+    /// The wire type of a custom type, whose fields or variants are in the rows below it.
+    fn custom_type_kind(&self, custom_type: &CustomType) -> String {
+        match custom_type {
+            CustomType::Struct { .. } => "Struct".to_owned(),
+            CustomType::Enum { variant, .. } => match &**variant {
+                FieldType::Key(_) => "Enum".to_owned(),
+                variant => format!("Enum ({})", self.wire_type(variant)),
+            },
+            CustomType::BitField { fields, .. } => {
+                let total_width: u32 = fields.values().map(|field| u32::from(field.width())).sum();
+                let container = match total_width {
+                    0..=8 => "U8",
+                    9..=16 => "U16",
+                    17..=32 => "U32",
+                    _ => "U64",
+                };
+                format!("BitField ({})", container)
+            }
+            CustomType::BitFlags { field_type, .. } => format!("BitFlags ({:?})", field_type),
+            CustomType::Unit { .. } => "Unit".to_owned(),
+        }
+    }
+
+    fn custom_type_notes(&self, custom_type: &CustomType) -> Vec<String> {
+        let mut notes: Vec<_> = custom_type
+            .doc()
+            .map(|doc| self.text(doc))
+            .into_iter()
+            .collect();
+        notes.extend(self.version_note(custom_type.versions()));
+        notes.extend(custom_type.wiki().map(|wiki| self.wiki_link(wiki)));
+        notes
+    }
+
+    /// The documentation, presence, constraints and versions of a struct field.
+    fn field_notes(&self, field_type: &FieldType) -> Vec<String> {
+        let mut notes = Vec::new();
+        match field_type {
+            FieldType::Annotated {
+                attributes:
+                    FieldAttributes {
+                        doc,
+                        wiki,
+                        default,
+                        min,
+                        max,
+                        max_len,
+                        ..
+                    },
+                field_type: inner,
+            } => {
+                notes.extend(doc.as_deref().map(|doc| self.text(doc)));
+                notes.extend(self.field_notes(inner));
+                match (min, max) {
+                    (Some(min), Some(max)) => notes.push(format!("From {} to {}.", min, max)),
+                    (Some(min), None) => notes.push(format!("At least {}.", min)),
+                    (None, Some(max)) => notes.push(format!("At most {}.", max)),
+                    (None, None) => {}
+                }
+                if let Some(max_len) = max_len {
+                    notes.push(format!("At most {} long.", max_len));
+                }
+                if let Some(default) = default {
+                    notes.push(format!("Defaults to {}.", self.code(&default.to_string())));
+                }
+                notes.extend(self.version_note(field_type.versions()));
+                notes.extend(wiki.as_deref().map(|wiki| self.wiki_link(wiki)));
+            }
+            FieldType::Conditional {
+                condition,
+                field_type,
+            } => {
+                let (field, operator) = match condition {
+                    Condition::Equal(field, _) => (field, "is"),
+                    Condition::NotEqual(field, _) => (field, "is not"),
+                };
+                notes.push(format!(
+                    "Only present when {} {} {}.",
+                    self.code(field),
+                    operator,
+                    self.code(&condition.literal().to_string())
+                ));
+                notes.extend(self.field_notes(field_type));
+            }
+            FieldType::Const { value, .. } => {
+                notes.push(format!("Always {}.", self.code(&value.to_string())));
+            }
+            _ => {}
+        }
+        notes
+    }
+
+    fn version_note(&self, versions: VersionRange) -> Option<String> {
+        if versions.is_unbounded() {
+            return None;
+        }
+        Some(format!("In protocol versions {}.", versions))
+    }
+
+    fn wiki_link(&self, anchor: &str) -> String {
+        let href = format!("https://wiki.vg/Protocol#{}", anchor);
+        format!("See {}.", self.link("wiki.vg", &href))
+    }
+
+    fn link(&self, text: &str, href: &str) -> String {
+        match self.markup {
+            Markup::Markdown => format!("[{}]({})", self.escape(text), href),
+            Markup::Html => format!(
+                "<a href=\"{}\">{}</a>",
+                self.escape(href),
+                self.escape(text)
+            ),
+        }
+    }
+
+    fn code(&self, text: &str) -> String {
+        match self.markup {
+            Markup::Markdown => format!("`{}`", self.escape(text)),
+            Markup::Html => format!("<code>{}</code>", self.escape(text)),
+        }
+    }
+
+    /// Documentation written in the spec, which is Markdown already, on a single line.
+    fn text(&self, doc: &str) -> String {
+        let doc = doc.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+        match self.markup {
+            Markup::Markdown => doc.replace('|', "\\|"),
+            Markup::Html => self.escape(&doc),
+        }
+    }
+
+    fn escape(&self, text: &str) -> String {
+        match self.markup {
+            Markup::Markdown => text.replace('|', "\\|"),
+            Markup::Html => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;"),
+        }
+    }
+}
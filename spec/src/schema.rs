@@ -0,0 +1,23 @@
+use crate::{Minecraft, ProtocolPatch};
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+
+/// Either form a spec file can take, see [`crate::Spec`].
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum SpecFile {
+    Minecraft(Minecraft),
+    Patch(ProtocolPatch),
+}
+
+/// A JSON Schema of spec files, generated from the types they are deserialized into.
+///
+/// Map keys are not described, see [`crate::Format`] for how they are written.
+pub fn schema() -> RootSchema {
+    let mut schema = schemars::schema_for!(SpecFile);
+    let metadata = schema.schema.metadata();
+    metadata.title = Some("Spec".to_owned());
+    metadata.description = Some(format!("A feather-protocol spec, version {}.", crate::SPEC_VERSION));
+    schema
+}
@@ -0,0 +1,727 @@
+//! A position-aware reading of RON source, used to point diagnostics at the
+//! line and column of the definition they are about.
+//!
+//! `ron` itself does not expose positions once a value is deserialized, so this
+//! parses the source a second time into an untyped tree. The same tree is used to carry
+//! comments over to the canonical form of a spec.
+
+use crate::{Literal, Location, PacketIdentifier, PathSegment, Renumbering};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Node {
+    pub location: Location,
+    /// Just past the last character of the node.
+    pub end: Location,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum NodeKind {
+    /// `Name(field: value, ...)` or `(field: value, ...)`.
+    Struct(Option<String>, Vec<(String, Location, Node)>),
+    /// `Name(value, ...)` or `(value, ...)`.
+    Tuple(Option<String>, Vec<Node>),
+    Map(Vec<(Node, Node)>),
+    List(Vec<Node>),
+    String(String),
+    Number(String),
+    /// Bare identifiers, including `true`, `false` and unit variants.
+    Ident(String),
+    Char(char),
+}
+
+impl Node {
+    pub fn field(&self, name: &str) -> Option<&Node> {
+        match &self.kind {
+            NodeKind::Struct(_, fields) => fields
+                .iter()
+                .find(|(field, _, _)| field == name)
+                .map(|(_, _, value)| value),
+            _ => None,
+        }
+    }
+
+    fn children(&self) -> Vec<&Node> {
+        match &self.kind {
+            NodeKind::Struct(_, fields) => fields.iter().map(|(_, _, value)| value).collect(),
+            NodeKind::Tuple(_, items) | NodeKind::List(items) => items.iter().collect(),
+            NodeKind::Map(entries) => entries.iter().map(|(_, value)| value).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Finds the first node, in pre-order, which has a map field called `field`.
+    fn find_map(&self, field: &str) -> Option<&[(Node, Node)]> {
+        if let Some(Node {
+            kind: NodeKind::Map(entries),
+            ..
+        }) = self.field(field)
+        {
+            return Some(entries);
+        }
+        self.children()
+            .into_iter()
+            .find_map(|child| child.find_map(field))
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match &self.kind {
+            NodeKind::Number(number) => {
+                parse_integer(number).and_then(|int| if int >= 0 { Some(int as u64) } else { None })
+            }
+            _ => None,
+        }
+    }
+
+    fn matches_literal(&self, literal: &Literal) -> bool {
+        match (&self.kind, literal) {
+            (NodeKind::String(string), Literal::String(literal)) => string == literal,
+            (NodeKind::Ident(ident), Literal::Boolean(literal)) => {
+                ident == if *literal { "true" } else { "false" }
+            }
+            (NodeKind::Number(number), Literal::Int(literal)) => {
+                parse_integer(number) == Some(*literal)
+            }
+            _ => false,
+        }
+    }
+
+    fn matches_identifier(&self, identifier: &PacketIdentifier) -> bool {
+        match &self.kind {
+            NodeKind::Tuple(None, items) if items.len() == 3 => {
+                let direction = format!("{:?}", identifier.direction());
+                let stage = format!("{:?}", identifier.stage());
+                items[0].kind == NodeKind::Ident(direction)
+                    && items[1].kind == NodeKind::Ident(stage)
+                    && items[2].as_u64() == Some(*identifier.id())
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the location of the definition at `path`, as far as it can be followed.
+    pub fn locate(&self, path: &[PathSegment]) -> Location {
+        let mut node = self;
+        let mut location = self.location;
+        for segment in path {
+            let found = match segment {
+                PathSegment::Packet(identifier) => {
+                    node.field("packets")
+                        .and_then(|packets| match &packets.kind {
+                            NodeKind::Map(entries) => entries
+                                .iter()
+                                .find(|(key, _)| key.matches_identifier(identifier))
+                                .map(|(key, value)| (key.location, value)),
+                            _ => None,
+                        })
+                }
+                PathSegment::SharedType(name) => {
+                    node.field("shared_types")
+                        .and_then(|shared_types| match &shared_types.kind {
+                            NodeKind::List(items) => items
+                                .iter()
+                                .find(|item| match item.field("name") {
+                                    Some(Node {
+                                        kind: NodeKind::String(item_name),
+                                        ..
+                                    }) => item_name == &**name,
+                                    _ => false,
+                                })
+                                .map(|item| (item.location, item)),
+                            _ => None,
+                        })
+                }
+                PathSegment::Field(name) => node.find_map("fields").and_then(|fields| {
+                    fields
+                        .iter()
+                        .find(|(key, _)| key.kind == NodeKind::String(name.to_string()))
+                        .map(|(key, value)| (key.location, value))
+                }),
+                PathSegment::Variant(literal) => node.find_map("variants").and_then(|variants| {
+                    variants
+                        .iter()
+                        .find(|(key, _)| key.matches_literal(literal))
+                        .map(|(key, value)| (key.location, value))
+                }),
+            };
+            match found {
+                Some((found_location, found_node)) => {
+                    location = found_location;
+                    node = found_node;
+                }
+                None => break,
+            }
+        }
+        location
+    }
+}
+
+fn parse_integer(number: &str) -> Option<i64> {
+    let number = number.replace('_', "");
+    let (negative, digits) = match number.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, number.strip_prefix('+').unwrap_or(&number)),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(octal) = digits.strip_prefix("0o") {
+        i64::from_str_radix(octal, 8).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Parses RON source into an untyped tree. Returns `None` if the source is malformed,
+/// in which case `ron` reports the actual error.
+pub(crate) fn parse(source: &str) -> Option<Node> {
+    parse_with_comments(source).map(|(node, _)| node)
+}
+
+/// Parses RON source into an untyped tree, along with the comments in it.
+pub(crate) fn parse_with_comments(source: &str) -> Option<(Node, Vec<Comment>)> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        index: 0,
+        location: Location { line: 1, column: 1 },
+        comments: Vec::new(),
+    };
+    parser.skip_trivia();
+    while parser.peek() == Some('#') {
+        // Extension attributes such as `#![enable(implicit_some)]`.
+        while matches!(parser.peek(), Some(c) if c != ']') {
+            parser.bump();
+        }
+        parser.bump();
+        parser.skip_trivia();
+    }
+    let node = parser.value()?;
+    parser.skip_trivia();
+    if parser.peek().is_some() {
+        return None;
+    }
+    Some((node, parser.comments))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Comment {
+    /// The comment as written, including `//` or `/* */`.
+    pub text: String,
+    pub location: Location,
+    /// Whether the comment follows code on the same line.
+    pub trailing: bool,
+}
+
+/// A step from a node to one of its children, by which the same definition is found in two
+/// renditions of a spec. Newtype variants are skipped, so that `VarInt` and
+/// `Integer(VarInt)` lead to the same place.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Step {
+    Field(String),
+    /// The key of a map entry, with numbers in decimal.
+    Key(String),
+    Index(usize),
+}
+
+/// A struct field, map entry or list item, starting at its name or key.
+struct Anchor {
+    path: Vec<Step>,
+    start: Location,
+    end: Location,
+}
+
+fn anchors(root: &Node) -> Vec<Anchor> {
+    fn anchor(node: &Node, path: Vec<Step>, start: Location, anchors: &mut Vec<Anchor>) {
+        anchors.push(Anchor {
+            path: path.clone(),
+            start,
+            end: node.end,
+        });
+        children(node, &path, anchors);
+    }
+
+    fn children(node: &Node, path: &[Step], anchors: &mut Vec<Anchor>) {
+        let child = |step| [path, &[step]].concat();
+        match &node.kind {
+            NodeKind::Struct(_, fields) => {
+                for (name, location, value) in fields {
+                    anchor(value, child(Step::Field(name.clone())), *location, anchors);
+                }
+            }
+            NodeKind::Tuple(_, items) if items.len() == 1 => children(&items[0], path, anchors),
+            NodeKind::Tuple(_, items) | NodeKind::List(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    anchor(item, child(Step::Index(index)), item.location, anchors);
+                }
+            }
+            NodeKind::Map(entries) => {
+                for (key, value) in entries {
+                    anchor(value, child(Step::Key(key_text(key))), key.location, anchors);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut anchors = Vec::new();
+    anchor(root, Vec::new(), root.location, &mut anchors);
+    anchors
+}
+
+fn key_text(key: &Node) -> String {
+    match &key.kind {
+        NodeKind::Number(number) => match parse_integer(number) {
+            Some(int) => int.to_string(),
+            None => number.clone(),
+        },
+        NodeKind::String(string) => format!("{:?}", string),
+        NodeKind::Ident(ident) => ident.clone(),
+        NodeKind::Char(c) => format!("{:?}", c),
+        NodeKind::Tuple(name, items) => format!(
+            "{}({})",
+            name.as_deref().unwrap_or(""),
+            items.iter().map(key_text).collect::<Vec<_>>().join(", ")
+        ),
+        _ => String::new(),
+    }
+}
+
+/// The key of a packet, as [`key_text`] gives it.
+fn identifier_key(identifier: &PacketIdentifier) -> String {
+    format!(
+        "({:?}, {:?}, {})",
+        identifier.direction(),
+        identifier.stage(),
+        *identifier.id()
+    )
+}
+
+/// Where a comment goes relative to the definition at a path.
+enum Placement {
+    /// On its own lines in front of the definition.
+    Before,
+    /// On its own lines in front of the closing bracket of the definition.
+    Closing,
+    /// At the end of the first line of the definition.
+    AfterStart,
+    /// At the end of the line with the closing bracket of the definition.
+    AfterEnd,
+    /// On its own lines after everything else.
+    End,
+}
+
+fn place(comment: &Comment, anchors: &[Anchor]) -> (Vec<Step>, Placement) {
+    if comment.trailing {
+        // A trailing comment belongs to the definition which starts or closes last on its
+        // line before it, and the outermost one of those closing together.
+        let line = comment.location.line;
+        let starts = anchors
+            .iter()
+            .filter(|anchor| anchor.start.line == line && anchor.start < comment.location)
+            .map(|anchor| (anchor.start, anchor, Placement::AfterStart));
+        let ends = anchors
+            .iter()
+            .filter(|anchor| anchor.end.line == line && anchor.end <= comment.location)
+            .map(|anchor| (anchor.end, anchor, Placement::AfterEnd));
+        if let Some((_, anchor, placement)) = starts
+            .chain(ends)
+            .max_by_key(|(location, anchor, _)| (*location, Reverse(anchor.path.len())))
+        {
+            return (anchor.path.clone(), placement);
+        }
+    }
+
+    let next = anchors
+        .iter()
+        .filter(|anchor| anchor.start > comment.location)
+        .min_by_key(|anchor| anchor.start);
+    let closing = anchors
+        .iter()
+        .filter(|anchor| anchor.start < comment.location && anchor.end > comment.location)
+        .filter(|anchor| match next {
+            Some(next) => anchor.end <= next.start,
+            None => true,
+        })
+        .min_by_key(|anchor| anchor.end);
+    match (closing, next) {
+        (Some(anchor), _) => (anchor.path.clone(), Placement::Closing),
+        (None, Some(anchor)) => (anchor.path.clone(), Placement::Before),
+        (None, None) => (Vec::new(), Placement::End),
+    }
+}
+
+/// Carries the comments of `source` over to `canonical`, another rendition of the same spec,
+/// next to the same definitions. Comments on renumbered packets follow the packet.
+/// Returns `None` if either is malformed.
+pub(crate) fn keep_comments(
+    source: &str,
+    canonical: &str,
+    renumbering: &Renumbering,
+) -> Option<String> {
+    let (root, comments) = parse_with_comments(source)?;
+    if comments.is_empty() {
+        return Some(canonical.to_owned());
+    }
+    let mut source_anchors = anchors(&root);
+    let packet_keys: HashMap<_, _> = renumbering
+        .iter()
+        .map(|(old, new)| (identifier_key(old), identifier_key(new)))
+        .collect();
+    for anchor in source_anchors.iter_mut() {
+        if let [Step::Field(protocol), Step::Field(packets), Step::Key(key), ..] =
+            anchor.path.as_mut_slice()
+        {
+            if protocol == "protocol" && packets == "packets" {
+                if let Some(new) = packet_keys.get(key) {
+                    *key = new.clone();
+                }
+            }
+        }
+    }
+    let canonical_anchors: HashMap<_, _> = anchors(&parse(canonical)?)
+        .into_iter()
+        .map(|anchor| (anchor.path, (anchor.start.line, anchor.end.line)))
+        .collect();
+
+    let lines: Vec<&str> = canonical.lines().collect();
+    let mut before: Vec<Vec<String>> = vec![Vec::new(); lines.len() + 1];
+    let mut after: Vec<Vec<&str>> = vec![Vec::new(); lines.len()];
+    for comment in comments.iter() {
+        let (mut path, mut placement) = place(comment, &source_anchors);
+        // A definition which is gone from the canonical rendition takes its comments
+        // to the end of the closest enclosing one.
+        while !canonical_anchors.contains_key(&path) {
+            path.pop();
+            placement = Placement::Closing;
+        }
+        let (start, end) = canonical_anchors[&path];
+        let indent = |line: usize| {
+            let line = lines[line - 1];
+            line[..line.len() - line.trim_start().len()].to_owned()
+        };
+        match placement {
+            Placement::Before => {
+                before[start - 1].push(format!("{}{}", indent(start), comment.text))
+            }
+            Placement::Closing if end > start => {
+                before[end - 1].push(format!("{}    {}", indent(end), comment.text))
+            }
+            Placement::AfterStart => after[start - 1].push(&comment.text),
+            Placement::Closing | Placement::AfterEnd => after[end - 1].push(&comment.text),
+            Placement::End => before[lines.len()].push(comment.text.clone()),
+        }
+    }
+
+    let mut output = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        output.append(&mut before[index]);
+        let mut line = line.to_string();
+        for comment in after[index].iter() {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        output.push(line);
+    }
+    output.append(&mut before[lines.len()]);
+    let mut output = output.join("\n");
+    if canonical.ends_with('\n') {
+        output.push('\n');
+    }
+    Some(output)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    location: Location,
+    comments: Vec<Comment>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.location.line += 1;
+            self.location.column = 1;
+        } else {
+            self.location.column += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> Option<()> {
+        self.skip_trivia();
+        if self.peek() == Some(expected) {
+            self.bump();
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('/'), Some('/')) => {
+                    let start = (self.index, self.location);
+                    while matches!(self.peek(), Some(c) if c != '\n') {
+                        self.bump();
+                    }
+                    self.comment(start);
+                }
+                (Some('/'), Some('*')) => {
+                    let start = (self.index, self.location);
+                    self.bump();
+                    self.bump();
+                    while self.peek().is_some()
+                        && (self.peek(), self.peek_at(1)) != (Some('*'), Some('/'))
+                    {
+                        self.bump();
+                    }
+                    self.bump();
+                    self.bump();
+                    self.comment(start);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Records the comment from `start` up to here, unless it was read before.
+    fn comment(&mut self, (start, location): (usize, Location)) {
+        if self.comments.iter().any(|comment| comment.location == location) {
+            return;
+        }
+        let trailing = self.chars[..start]
+            .iter()
+            .rev()
+            .take_while(|c| **c != '\n')
+            .any(|c| !c.is_whitespace());
+        self.comments.push(Comment {
+            text: self.chars[start..self.index].iter().collect(),
+            location,
+            trailing,
+        });
+    }
+
+    fn value(&mut self) -> Option<Node> {
+        self.skip_trivia();
+        let location = self.location;
+        let kind = match self.peek()? {
+            '(' => self.parenthesized(None)?,
+            '[' => {
+                self.bump();
+                NodeKind::List(self.sequence(']', Self::value)?)
+            }
+            '{' => {
+                self.bump();
+                NodeKind::Map(self.sequence('}', |parser| {
+                    let key = parser.value()?;
+                    parser.eat(':')?;
+                    Some((key, parser.value()?))
+                })?)
+            }
+            '"' => NodeKind::String(self.string()?),
+            '\'' => {
+                self.bump();
+                let c = match self.bump()? {
+                    '\\' => self.escape()?,
+                    c => c,
+                };
+                if self.bump()? != '\'' {
+                    return None;
+                }
+                NodeKind::Char(c)
+            }
+            'r' if self.peek_at(1) == Some('"')
+                || (self.peek_at(1) == Some('#')
+                    && matches!(self.peek_at(2), Some('"') | Some('#'))) =>
+            {
+                NodeKind::String(self.raw_string()?)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.peek() {
+                    let exponent_sign = (c == '-' || c == '+')
+                        && matches!(number.chars().last(), Some('e') | Some('E'))
+                        && !number.starts_with("0x");
+                    if c.is_ascii_alphanumeric()
+                        || c == '.'
+                        || c == '_'
+                        || exponent_sign
+                        || number.is_empty()
+                    {
+                        number.push(c);
+                        self.bump();
+                    } else {
+                        break;
+                    }
+                }
+                NodeKind::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let ident = self.ident()?;
+                self.skip_trivia();
+                if self.peek() == Some('(') {
+                    self.parenthesized(Some(ident))?
+                } else {
+                    NodeKind::Ident(ident)
+                }
+            }
+            _ => return None,
+        };
+        Some(Node {
+            location,
+            end: self.location,
+            kind,
+        })
+    }
+
+    /// Parses `( ... )` as a struct if it starts with `ident:`, or as a tuple otherwise.
+    fn parenthesized(&mut self, name: Option<String>) -> Option<NodeKind> {
+        self.bump();
+        self.skip_trivia();
+        let start = (self.index, self.location);
+        let is_struct = match self.peek() {
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                self.ident()?;
+                self.skip_trivia();
+                let is_struct = self.peek() == Some(':');
+                self.index = start.0;
+                self.location = start.1;
+                is_struct
+            }
+            _ => false,
+        };
+        if is_struct {
+            Some(NodeKind::Struct(
+                name,
+                self.sequence(')', |parser| {
+                    parser.skip_trivia();
+                    let location = parser.location;
+                    let field = parser.ident()?;
+                    parser.eat(':')?;
+                    Some((field, location, parser.value()?))
+                })?,
+            ))
+        } else {
+            Some(NodeKind::Tuple(name, self.sequence(')', Self::value)?))
+        }
+    }
+
+    /// Parses comma separated items up to and including `end`.
+    fn sequence<T>(
+        &mut self,
+        end: char,
+        mut item: impl FnMut(&mut Self) -> Option<T>,
+    ) -> Option<Vec<T>> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.peek() == Some(end) {
+                self.bump();
+                return Some(items);
+            }
+            items.push(item(self)?);
+            self.skip_trivia();
+            match self.peek()? {
+                ',' => {
+                    self.bump();
+                }
+                c if c == end => {}
+                _ => return None,
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        if self.peek() == Some('r') && self.peek_at(1) == Some('#') {
+            self.bump();
+            self.bump();
+        }
+        let mut ident = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                ident.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        if ident.is_empty() {
+            None
+        } else {
+            Some(ident)
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.bump();
+        let mut string = String::new();
+        loop {
+            match self.bump()? {
+                '"' => return Some(string),
+                '\\' => string.push(self.escape()?),
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Option<char> {
+        Some(match self.bump()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            'u' => {
+                self.eat('{')?;
+                let mut hex = String::new();
+                while self.peek()? != '}' {
+                    hex.push(self.bump()?);
+                }
+                self.bump();
+                std::char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+            }
+            c => c,
+        })
+    }
+
+    fn raw_string(&mut self) -> Option<String> {
+        self.bump();
+        let mut hashes = 0;
+        while self.peek() == Some('#') {
+            self.bump();
+            hashes += 1;
+        }
+        if self.bump()? != '"' {
+            return None;
+        }
+        let mut string = String::new();
+        loop {
+            let c = self.bump()?;
+            if c == '"' && (0..hashes).all(|offset| self.peek_at(offset) == Some('#')) {
+                for _ in 0..hashes {
+                    self.bump();
+                }
+                return Some(string);
+            }
+            string.push(c);
+        }
+    }
+}
@@ -1,41 +1,786 @@
 use crate::*;
 
-use anyhow::{bail, Result};
-use std::collections::BTreeMap;
+use anyhow::Result;
+use heck::CamelCase;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub trait Validation {
-    fn validate(&self) -> Result<()>;
+    /// Collects every problem found, warnings included.
+    fn diagnostics(&self) -> Vec<Diagnostic>;
+
+    /// Fails with a [`ValidationError`] if any of the diagnostics is an error.
+    fn validate(&self) -> Result<()> {
+        let diagnostics = self.diagnostics();
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            Err(ValidationError(diagnostics).into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Validation for Minecraft {
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if self.spec_version != SPEC_VERSION {
+            diagnostics.push(Diagnostic::error(
+                SpecPath::default(),
+                format!(
+                    "The spec version {} is not supported, only spec version {} is.",
+                    self.spec_version, SPEC_VERSION
+                ),
+            ));
+        }
+        diagnostics.extend(self.protocol.diagnostics());
+        diagnostics
+    }
 }
 
 impl Validation for Protocol {
-    fn validate(&self) -> Result<()> {
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut shared_types = BTreeMap::new();
+        let mut duplicates = Vec::new();
+        for shared_type in self.shared_types.iter() {
+            if shared_types
+                .insert(shared_type.name(), shared_type)
+                .is_some()
+            {
+                duplicates.push(shared_type.name());
+            }
+        }
+
+        let mut validator = Validator::new(Some(&shared_types));
+        for name in duplicates {
+            validator.at(
+                PathSegment::SharedType(name.to_owned().into()),
+                |validator| {
+                    validator.error(format!(
+                        "The shared type name \"{}\" is used more than once.",
+                        name
+                    ))
+                },
+            );
+        }
+
         let mut packet_names = BTreeMap::new();
         let mut prev: Option<&PacketIdentifier> = None;
         for (identifier, packet) in self.packets.iter() {
-            if let Some(prev_identifier) = prev {
-                if prev_identifier.direction() == identifier.direction()
-                    && prev_identifier.stage() == identifier.stage()
-                    && !(*prev_identifier.id() + 1 == *identifier.id()) {
-                    println!("Skiped from {:#X?} to {:#X?}", prev_identifier, identifier);
+            validator.at(PathSegment::Packet(identifier.clone()), |validator| {
+                if let Some(prev_identifier) = prev {
+                    if prev_identifier.direction() == identifier.direction()
+                        && prev_identifier.stage() == identifier.stage()
+                        && *prev_identifier.id() + 1 != *identifier.id()
+                    {
+                        validator.warning(format!(
+                            "The packet IDs skip from {:#04X} to {:#04X}.",
+                            *prev_identifier.id(),
+                            *identifier.id()
+                        ));
+                    }
                 }
-            }
-            prev = Some(identifier);
-            if let Some(old_identifier) = packet_names.insert(
-                (
-                    identifier.direction(),
-                    identifier.stage(),
-                    packet.name()
-                ),
-                identifier,
-            ) {
-                bail!(
-                    "The packet name \"{}\" for {:#X?} is already used by {:#X?}.",
-                    packet.name(),
+                if let Some(old_identifier) = packet_names.insert(
+                    (identifier.direction(), identifier.stage(), packet.name()),
                     identifier,
-                    old_identifier
+                ) {
+                    validator.error(format!(
+                        "The packet name \"{}\" for {:#X?} is already used by {:#X?}.",
+                        packet.name(),
+                        identifier,
+                        old_identifier
+                    ));
+                }
+                validator.validate_custom_type(packet, &[]);
+                validator
+                    .validate_type_names(&[packet], &format!("the packet \"{}\"", packet.name()));
+            });
+            prev = Some(identifier);
+        }
+        for shared_type in self.shared_types.iter() {
+            validator.at(
+                PathSegment::SharedType(shared_type.name().to_owned().into()),
+                |validator| validator.validate_custom_type(shared_type, &[]),
+            );
+        }
+        let shared_type_refs: Vec<_> = self.shared_types.iter().collect();
+        validator.validate_type_names(&shared_type_refs, "the shared types");
+        validator.validate_shared_type_cycles(&shared_types);
+        validator.diagnostics
+    }
+}
+
+/// Validates a custom type on its own. References to shared types are not checked.
+impl Validation for CustomType {
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator::new(None);
+        validator.validate_custom_type(self, &[]);
+        validator.diagnostics
+    }
+}
+
+/// Fields of enclosing structs which an enum can take its discriminant from, innermost last,
+/// with their documentation.
+type Keys<'a> = [(&'a FieldName, &'a FieldType)];
+
+/// The field of a struct which a field type belongs to.
+struct FieldContext<'a> {
+    struct_name: &'a CustomTypeName,
+    field_name: &'a FieldName,
+    fields: &'a IndexMap<FieldName, FieldType>,
+    index: usize,
+}
+
+struct Validator<'a> {
+    /// The shared types of the protocol, if references to them should be checked.
+    shared_types: Option<&'a BTreeMap<&'a str, &'a CustomType>>,
+    path: Vec<PathSegment>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(shared_types: Option<&'a BTreeMap<&'a str, &'a CustomType>>) -> Self {
+        Validator {
+            shared_types,
+            path: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Runs `validate` with `segment` appended to the current path.
+    fn at<T>(&mut self, segment: PathSegment, validate: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.push(segment);
+        let result = validate(self);
+        self.path.pop();
+        result
+    }
+
+    fn error(&mut self, message: String) {
+        let path = SpecPath(self.path.clone());
+        self.diagnostics.push(Diagnostic::error(path, message));
+    }
+
+    fn warning(&mut self, message: String) {
+        let path = SpecPath(self.path.clone());
+        self.diagnostics.push(Diagnostic::warning(path, message));
+    }
+
+    fn validate_custom_type(&mut self, custom_type: &CustomType, keys: &Keys) {
+        let versions = custom_type.versions();
+        if versions.is_empty() {
+            self.error(format!(
+                "\"{}\" is in no protocol version, as it is {}.",
+                custom_type.name(),
+                versions
+            ));
+        }
+        match custom_type {
+            CustomType::Struct { name, fields, .. } => {
+                let mut keys = keys.to_vec();
+                for (index, (field_name, field_type)) in fields.iter().enumerate() {
+                    let field = FieldContext {
+                        struct_name: name,
+                        field_name,
+                        fields,
+                        index,
+                    };
+                    let attributes = field_type;
+                    let default = field_type.default_value();
+                    let field_type = field_type.unannotated();
+                    self.at(PathSegment::Field(field_name.clone()), |validator| {
+                        let versions = attributes.versions();
+                        if versions.is_empty() {
+                            validator.error(format!(
+                                "\"{}.{}\" is in no protocol version, as it is {}.",
+                                **name, **field_name, versions
+                            ));
+                        }
+                        match field_type {
+                            FieldType::Const { field_type, value } => {
+                                if default.is_some() {
+                                    validator.error(format!(
+                                        "\"{}.{}\" is constant, so it cannot have a default.",
+                                        **name, **field_name
+                                    ));
+                                }
+                                validator.validate_literal(&field, "value", field_type, value);
+                                validator.validate_field_type(&field, field_type, false, &keys)
+                            }
+                            field_type => {
+                                if let Some(default) = default {
+                                    validator.validate_literal(&field, "default", field_type, default);
+                                }
+                                validator.validate_constraints(&field, attributes, field_type);
+                                validator.validate_field_type(&field, field_type, false, &keys)
+                            }
+                        }
+                    });
+                    keys.push((field_name, attributes));
+                }
+            }
+            CustomType::Enum {
+                name,
+                variant,
+                variants,
+                ..
+            } => {
+                let discriminant = match &**variant {
+                    FieldType::Key(key) => {
+                        match keys.iter().rev().find(|(field_name, _)| *field_name == key) {
+                            Some((_, key_type)) => {
+                                let versions = key_type.versions();
+                                if !versions.is_unbounded() {
+                                    self.error(format!(
+                                        "The discriminant of \"{}\" refers to \"{}\", which is \
+                                         only in some protocol versions ({}).",
+                                        **name, **key, versions
+                                    ));
+                                }
+                                Some(key_type.unannotated())
+                            }
+                            None => {
+                                self.error(format!(
+                                    "The discriminant of \"{}\" refers to \"{}\", which is not an \
+                                     earlier field of an enclosing struct.",
+                                    **name, **key
+                                ));
+                                None
+                            }
+                        }
+                    }
+                    variant => Some(variant),
+                };
+                if let Some(discriminant) = discriminant {
+                    if !matches!(
+                        discriminant,
+                        FieldType::Integer(_)
+                            | FieldType::Primitive(PrimitiveType::Boolean)
+                            | FieldType::Primitive(PrimitiveType::String(_))
+                    ) {
+                        self.error(format!(
+                            "The discriminant of \"{}\" is not an integer, boolean or string.",
+                            **name
+                        ));
+                    } else {
+                        for literal in variants.keys() {
+                            if !literal_fits(discriminant, literal) {
+                                self.error(format!(
+                                    "The variant {:?} of \"{}\" does not fit its discriminant type {:?}.",
+                                    literal, **name, discriminant
+                                ));
+                            }
+                        }
+                    }
+                }
+                for (literal, variant) in variants {
+                    self.at(PathSegment::Variant(literal.clone()), |validator| {
+                        validator.validate_custom_type(variant, keys)
+                    });
+                }
+            }
+            CustomType::BitField { name, fields, .. } => {
+                for (field_name, field) in fields {
+                    let (width, bits) = (field.width(), bit_field_size(field));
+                    if width == 0 || width > bits {
+                        self.at(PathSegment::Field(field_name.clone()), |validator| {
+                            validator.error(format!(
+                                "The bit field \"{}.{}\" is {} bits wide, which does not fit into \
+                                 its {} bit type.",
+                                **name, **field_name, width, bits
+                            ))
+                        });
+                    }
+                }
+                let total_width: u32 = fields.values().map(|field| u32::from(field.width())).sum();
+                if ![8, 16, 32, 64].contains(&total_width) {
+                    self.error(format!(
+                        "The fields of the bit field \"{}\" are {} bits wide in total, which is \
+                         not the size of an integer.",
+                        **name, total_width
+                    ));
+                }
+            }
+            CustomType::BitFlags { .. } | CustomType::Unit { .. } => {}
+        }
+    }
+
+    /// Validates the type of the field `field`.
+    fn validate_field_type(
+        &mut self,
+        field: &FieldContext,
+        field_type: &FieldType,
+        in_array: bool,
+        keys: &Keys,
+    ) {
+        match field_type {
+            FieldType::Conditional {
+                condition,
+                field_type,
+            } => {
+                if let Some(referenced) = self.resolve_reference(field, &condition.struct_field())
+                {
+                    let compared = match condition.member() {
+                        Some(member) => self.bit_field_member(field, referenced, member, condition),
+                        None => Some((
+                            literal_fits(referenced, condition.literal()),
+                            referenced.to_string(),
+                        )),
+                    };
+                    if let Some((false, compared_type)) = compared {
+                        self.error(format!(
+                            "The condition of \"{}.{}\" compares \"{}\" to {}, which is not a \
+                             value of its type {}.",
+                            **field.struct_name,
+                            **field.field_name,
+                            **condition.field(),
+                            condition.literal(),
+                            compared_type
+                        ));
+                    }
+                }
+                self.validate_field_type(field, field_type, in_array, keys)
+            }
+            FieldType::Primitive(PrimitiveType::Array { length, field_type }) => {
+                if let ArrayLength::Terminated(integer_type, terminator) = length {
+                    let (min, max) = integer_range(integer_type);
+                    if !(min..=max).contains(&i128::from(*terminator)) {
+                        self.error(format!(
+                            "The terminator {} of \"{}.{}\" does not fit in {:?}.",
+                            terminator, **field.struct_name, **field.field_name, integer_type
+                        ));
+                    }
+                }
+                if let ArrayLength::Field(length_field) = length {
+                    if in_array {
+                        self.error(format!(
+                            "The length of \"{}.{}\" refers to \"{}\", but only arrays which \
+                             are not nested in another array can take their length from a field.",
+                            **field.struct_name, **field.field_name, **length_field
+                        ));
+                    } else {
+                        let length_type = self.resolve_reference(field, length_field);
+                        if !matches!(length_type, None | Some(FieldType::Integer(_))) {
+                            self.error(format!(
+                                "The length of \"{}.{}\" refers to \"{}\", which is not an integer.",
+                                **field.struct_name, **field.field_name, **length_field
+                            ));
+                        }
+                        // The length is written from a single array.
+                        let shared = field.fields.iter().take(field.index).find(|(_, other)| {
+                            array_length_field(other.unannotated()) == Some(length_field)
+                        });
+                        if let Some((other, _)) = shared {
+                            self.error(format!(
+                                "The length of \"{}.{}\" refers to \"{}\", which is already the \
+                                 length of \"{}\".",
+                                **field.struct_name, **field.field_name, **length_field, **other
+                            ));
+                        }
+                    }
+                }
+                self.validate_field_type(field, field_type, true, keys)
+            }
+            FieldType::Primitive(PrimitiveType::Option(field_type)) => {
+                self.validate_field_type(field, field_type, in_array, keys)
+            }
+            FieldType::Primitive(PrimitiveType::String(0)) => self.error(format!(
+                "\"{}.{}\" is a string with a maximum length of 0.",
+                **field.struct_name, **field.field_name
+            )),
+            FieldType::Primitive(PrimitiveType::Shared(name)) => match self.shared_types {
+                Some(shared_types) if !shared_types.contains_key(&***name) => self.error(format!(
+                    "\"{}.{}\" refers to the shared type \"{}\", which does not exist.",
+                    **field.struct_name, **field.field_name, **name
+                )),
+                _ => {}
+            },
+            FieldType::CustomType(custom_type) => self.validate_custom_type(custom_type, keys),
+            FieldType::Key(_) => self.error(format!(
+                "\"{}.{}\" is a key, which is only valid as the discriminant of an enum.",
+                **field.struct_name, **field.field_name
+            )),
+            FieldType::Annotated { .. } => self.error(format!(
+                "\"{}.{}\" is annotated inside of its type, but attributes are only valid \
+                 directly on a struct field.",
+                **field.struct_name, **field.field_name
+            )),
+            FieldType::Const { .. } => self.error(format!(
+                "\"{}.{}\" is constant inside of its type, but constants are only valid \
+                 directly on a struct field.",
+                **field.struct_name, **field.field_name
+            )),
+            FieldType::Primitive(_) | FieldType::Float(_) | FieldType::Integer(_) => {}
+        }
+    }
+
+    /// Checks that the constraints in the `attributes` of `field` apply to its type `field_type`.
+    fn validate_constraints(
+        &mut self,
+        field: &FieldContext,
+        attributes: &FieldType,
+        field_type: &FieldType,
+    ) {
+        let (min, max) = (attributes.min(), attributes.max());
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                self.error(format!(
+                    "The minimum {} of \"{}.{}\" is larger than its maximum {}.",
+                    min, **field.struct_name, **field.field_name, max
+                ));
+            }
+        }
+        match number_type(field_type) {
+            Some(FieldType::Integer(integer_type)) => {
+                let (lowest, highest) = integer_range(integer_type);
+                for bound in min.iter().chain(max.iter()) {
+                    if !(lowest..=highest).contains(&i128::from(*bound)) {
+                        self.error(format!(
+                            "The bound {} of \"{}.{}\" does not fit in {:?}.",
+                            bound, **field.struct_name, **field.field_name, integer_type
+                        ));
+                    }
+                }
+            }
+            Some(_) => {}
+            None if min.is_some() || max.is_some() => self.error(format!(
+                "\"{}.{}\" has a minimum or maximum, but its type {} is not a number.",
+                **field.struct_name, **field.field_name, field_type
+            )),
+            None => {}
+        }
+        if attributes.max_len().is_some() && !has_length(field_type) {
+            self.error(format!(
+                "\"{}.{}\" has a maximum length, but its type {} is not a string or array.",
+                **field.struct_name, **field.field_name, field_type
+            ));
+        }
+    }
+
+    /// Checks that the constant value or default `literal` of `field` is a value of `field_type`.
+    fn validate_literal(
+        &mut self,
+        field: &FieldContext,
+        kind: &str,
+        field_type: &FieldType,
+        literal: &Literal,
+    ) {
+        if !literal_fits(field_type, literal) {
+            self.error(format!(
+                "The {} {} of \"{}.{}\" is not a value of its type {}.",
+                kind, literal, **field.struct_name, **field.field_name, field_type
+            ));
+        }
+    }
+
+    /// Looks up `member` of `referenced`, the bit field `condition` of `field` refers to,
+    /// returning whether the literal of the condition fits it and its type. Members of shared
+    /// bit fields are only looked up if the shared types are known.
+    fn bit_field_member(
+        &mut self,
+        field: &FieldContext,
+        referenced: &FieldType,
+        member: &str,
+        condition: &Condition,
+    ) -> Option<(bool, String)> {
+        let custom_type = match referenced {
+            FieldType::CustomType(custom_type) => Some(custom_type),
+            FieldType::Primitive(PrimitiveType::Shared(name)) => {
+                let shared_types = self.shared_types?;
+                shared_types.get(&***name).copied()
+            }
+            _ => None,
+        };
+        let bit_field = match custom_type {
+            Some(CustomType::BitField { fields, .. }) => fields
+                .iter()
+                .find(|(field_name, _)| ***field_name == *member)
+                .map(|(_, bit_field)| bit_field),
+            _ => None,
+        };
+        match bit_field {
+            Some(bit_field) => Some((
+                bit_field_literal_fits(bit_field, condition.literal()),
+                format!("{:?}", bit_field),
+            )),
+            None => {
+                self.error(format!(
+                    "The condition of \"{}.{}\" compares \"{}\", but {} has no member \"{}\".",
+                    **field.struct_name,
+                    **field.field_name,
+                    **condition.field(),
+                    referenced,
+                    member
+                ));
+                None
+            }
+        }
+    }
+
+    /// Resolves a reference from `field` to an earlier field of the same struct.
+    fn resolve_reference<'f>(
+        &mut self,
+        field: &FieldContext<'f>,
+        referenced: &FieldName,
+    ) -> Option<&'f FieldType> {
+        match field.fields.get_full(referenced) {
+            Some((referenced_index, _, field_type)) if referenced_index < field.index => {
+                let versions = field_type.versions();
+                if !versions.is_unbounded() {
+                    self.error(format!(
+                        "\"{}.{}\" refers to \"{}\", which is only in some protocol versions ({}).",
+                        **field.struct_name, **field.field_name, **referenced, versions
+                    ));
+                }
+                Some(field_type.unannotated())
+            }
+            Some(_) => {
+                self.error(format!(
+                    "\"{}.{}\" refers to \"{}\", which comes after it.",
+                    **field.struct_name, **field.field_name, **referenced
+                ));
+                None
+            }
+            None => {
+                self.error(format!(
+                    "\"{}.{}\" refers to \"{}\", which does not exist.",
+                    **field.struct_name, **field.field_name, **referenced
+                ));
+                None
+            }
+        }
+    }
+}
+
+/// The type of the numbers `field_type` holds, which may be optional or in an array.
+fn number_type(field_type: &FieldType) -> Option<&FieldType> {
+    match field_type {
+        FieldType::Integer(_) | FieldType::Float(_) => Some(field_type),
+        FieldType::Primitive(PrimitiveType::Array { field_type, .. })
+        | FieldType::Primitive(PrimitiveType::Option(field_type))
+        | FieldType::Conditional { field_type, .. } => number_type(field_type),
+        _ => None,
+    }
+}
+
+/// The field holding the length of the array in `field_type`, which may be optional.
+fn array_length_field(field_type: &FieldType) -> Option<&FieldName> {
+    match field_type {
+        FieldType::Primitive(PrimitiveType::Array {
+            length: ArrayLength::Field(length_field),
+            ..
+        }) => Some(length_field),
+        FieldType::Primitive(PrimitiveType::Option(field_type))
+        | FieldType::Conditional { field_type, .. } => array_length_field(field_type),
+        _ => None,
+    }
+}
+
+/// Whether `field_type` is a string or array, which may be optional.
+fn has_length(field_type: &FieldType) -> bool {
+    match field_type {
+        FieldType::Primitive(PrimitiveType::String(_))
+        | FieldType::Primitive(PrimitiveType::Array { .. }) => true,
+        FieldType::Primitive(PrimitiveType::Option(field_type))
+        | FieldType::Conditional { field_type, .. } => has_length(field_type),
+        _ => false,
+    }
+}
+
+/// Whether `literal` is a value of `discriminant`, an enum discriminant or field type.
+fn literal_fits(discriminant: &FieldType, literal: &Literal) -> bool {
+    match (discriminant, literal) {
+        (FieldType::Integer(integer_type), Literal::Int(int)) => {
+            let (min, max) = integer_range(integer_type);
+            (min..=max).contains(&i128::from(*int))
+        }
+        (FieldType::Primitive(PrimitiveType::Boolean), Literal::Boolean(_))
+        | (FieldType::Primitive(PrimitiveType::String(_)), Literal::String(_)) => true,
+        _ => false,
+    }
+}
+
+/// Whether `literal` is a value of the member `bit_field` of a bit field.
+fn bit_field_literal_fits(bit_field: &BitField, literal: &Literal) -> bool {
+    match (bit_field, literal) {
+        (BitField::Boolean, Literal::Boolean(_)) => true,
+        (BitField::Boolean, _) => false,
+        (_, Literal::Int(int)) => {
+            let width = u32::from(bit_field.width());
+            let (min, max) = match width {
+                0 => (0, 0),
+                _ if bit_field.is_signed() => (-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1),
+                _ => (0, (1i128 << width) - 1),
+            };
+            (min..=max).contains(&i128::from(*int))
+        }
+        _ => false,
+    }
+}
+
+fn integer_range(integer_type: &IntegerType) -> (i128, i128) {
+    match integer_type {
+        IntegerType::U8 => (0, u8::MAX.into()),
+        IntegerType::I8 => (i8::MIN.into(), i8::MAX.into()),
+        IntegerType::U16 => (0, u16::MAX.into()),
+        IntegerType::I16 => (i16::MIN.into(), i16::MAX.into()),
+        IntegerType::U32 => (0, u32::MAX.into()),
+        IntegerType::I32 | IntegerType::VarInt => (i32::MIN.into(), i32::MAX.into()),
+        IntegerType::U64 => (0, u64::MAX.into()),
+        IntegerType::I64 | IntegerType::VarLong => (i64::MIN.into(), i64::MAX.into()),
+    }
+}
+
+/// The number of bits of the type a bit field is read into.
+fn bit_field_size(field: &BitField) -> u8 {
+    match field {
+        BitField::Boolean => 1,
+        BitField::I8(_) | BitField::U8(_) => 8,
+        BitField::I16(_) | BitField::U16(_) => 16,
+        BitField::I32(_) | BitField::U32(_) => 32,
+        BitField::I64(_) | BitField::U64(_) => 64,
+    }
+}
+
+impl Validator<'_> {
+    /// Checks that no two custom types in `roots`, which are generated into the same
+    /// module, end up with the same Rust name.
+    fn validate_type_names(&mut self, roots: &[&CustomType], module: &str) {
+        fn collect<'a>(custom_type: &'a CustomType, names: &mut Vec<&'a str>) {
+            names.push(custom_type.name());
+            match custom_type {
+                CustomType::Enum { variants, .. } => {
+                    for variant in variants.values() {
+                        collect(variant, names);
+                    }
+                }
+                CustomType::Struct { fields, .. } => {
+                    for field_type in fields.values() {
+                        collect_field_type(field_type, names);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        fn collect_field_type<'a>(field_type: &'a FieldType, names: &mut Vec<&'a str>) {
+            match field_type {
+                FieldType::CustomType(custom_type) => collect(custom_type, names),
+                FieldType::Primitive(PrimitiveType::Array { field_type, .. })
+                | FieldType::Primitive(PrimitiveType::Option(field_type))
+                | FieldType::Conditional { field_type, .. }
+                | FieldType::Annotated { field_type, .. }
+                | FieldType::Const { field_type, .. } => {
+                    collect_field_type(field_type, names)
+                }
+                _ => {}
+            }
+        }
+
+        let mut names = Vec::new();
+        for root in roots {
+            collect(root, &mut names);
+        }
+        let mut generated = BTreeMap::new();
+        for name in names {
+            if let Some(other) = generated.insert(name.to_camel_case(), name) {
+                self.error(format!(
+                    "The custom types \"{}\" and \"{}\" in {} are both generated as `{}`.",
+                    other,
+                    name,
+                    module,
+                    name.to_camel_case()
+                ));
+            }
+        }
+    }
+
+    /// Checks that no shared type contains itself without an array in between,
+    /// which would make it infinitely large.
+    fn validate_shared_type_cycles(&mut self, shared_types: &BTreeMap<&str, &CustomType>) {
+        fn direct_references<'a>(custom_type: &'a CustomType, references: &mut BTreeSet<&'a str>) {
+            match custom_type {
+                CustomType::Enum { variants, .. } => {
+                    for variant in variants.values() {
+                        direct_references(variant, references);
+                    }
+                }
+                CustomType::Struct { fields, .. } => {
+                    for field_type in fields.values() {
+                        field_type_references(field_type, references);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        fn field_type_references<'a>(
+            field_type: &'a FieldType,
+            references: &mut BTreeSet<&'a str>,
+        ) {
+            match field_type {
+                FieldType::Primitive(PrimitiveType::Shared(name)) => {
+                    references.insert(name);
+                }
+                FieldType::CustomType(custom_type) => direct_references(custom_type, references),
+                FieldType::Primitive(PrimitiveType::Option(field_type))
+                | FieldType::Conditional { field_type, .. }
+                | FieldType::Annotated { field_type, .. }
+                | FieldType::Const { field_type, .. } => {
+                    field_type_references(field_type, references)
+                }
+                _ => {}
+            }
+        }
+
+        /// Returns the first cycle reachable from `name`.
+        fn find_cycle<'a>(
+            name: &'a str,
+            graph: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+            path: &mut Vec<&'a str>,
+            done: &mut BTreeSet<&'a str>,
+        ) -> Option<Vec<&'a str>> {
+            if let Some(start) = path.iter().position(|visited| *visited == name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name);
+                return Some(cycle);
+            }
+            if done.contains(name) {
+                return None;
+            }
+            path.push(name);
+            for reference in graph.get(name).into_iter().flatten() {
+                if let Some(cycle) = find_cycle(reference, graph, path, done) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            done.insert(name);
+            None
+        }
+
+        let graph: BTreeMap<_, _> = shared_types
+            .iter()
+            .map(|(name, custom_type)| {
+                let mut references = BTreeSet::new();
+                direct_references(custom_type, &mut references);
+                (*name, references)
+            })
+            .collect();
+        let mut done = BTreeSet::new();
+        for name in graph.keys() {
+            if let Some(cycle) = find_cycle(name, &graph, &mut Vec::new(), &mut done) {
+                // Everything on the cycle is done, so each cycle is reported once.
+                done.extend(cycle.iter().copied());
+                self.at(
+                    PathSegment::SharedType(cycle[0].to_owned().into()),
+                    |validator| {
+                        validator.error(format!(
+                            "The shared type \"{}\" contains itself through \"{}\" without an \
+                         array in between.",
+                            cycle[0],
+                            cycle.join("\" -> \"")
+                        ))
+                    },
                 );
             }
         }
-        Ok(())
     }
 }
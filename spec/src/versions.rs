@@ -0,0 +1,133 @@
+use crate::*;
+use std::fmt;
+
+/// The protocol versions a packet, struct field, enum variant or shared type is in, from
+/// `since` up to and including `until`. A missing bound leaves the range open on that side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VersionRange {
+    pub since: Option<ProtocolVersion>,
+    pub until: Option<ProtocolVersion>,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: ProtocolVersion) -> bool {
+        !matches!(self.since, Some(since) if version < since)
+            && !matches!(self.until, Some(until) if version > until)
+    }
+
+    /// Whether the range covers every version, as everything in a spec of a single version does.
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!((self.since, self.until), (Some(since), Some(until)) if since > until)
+    }
+
+    pub fn overlaps(&self, other: &VersionRange) -> bool {
+        let since = self.since.max(other.since);
+        let until = match (self.until, other.until) {
+            (Some(until), Some(other_until)) => Some(until.min(other_until)),
+            (until, other_until) => until.or(other_until),
+        };
+        !VersionRange { since, until }.is_empty()
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.since, self.until) {
+            (Some(since), Some(until)) => write!(f, "since {} until {}", *since, *until),
+            (Some(since), None) => write!(f, "since {}", *since),
+            (None, Some(until)) => write!(f, "until {}", *until),
+            (None, None) => write!(f, "every version"),
+        }
+    }
+}
+
+impl Protocol {
+    /// Projects a spec covering several protocol versions onto one of them.
+    ///
+    /// Packets, struct fields, enum variants and shared types which are not in `version` are
+    /// left out, and the packets after a left out packet move up to close its ID, as
+    /// [`Protocol::remove_packet`] does. The result has no version ranges left.
+    pub fn project(&self, version: ProtocolVersion) -> Protocol {
+        let mut protocol = self.clone();
+        protocol.version = version;
+
+        let left_out: Vec<_> = protocol
+            .packets
+            .iter()
+            .filter(|(_, packet)| !packet.versions().contains(version))
+            .map(|(identifier, _)| identifier.clone())
+            .collect();
+        // Removing the last packet first keeps the identifiers of the others.
+        for identifier in left_out.iter().rev() {
+            protocol
+                .remove_packet(identifier)
+                .expect("the packet is in the protocol");
+        }
+        for packet in protocol.packets.values_mut() {
+            project_custom_type(packet, version);
+        }
+
+        protocol
+            .shared_types
+            .retain(|shared_type| shared_type.versions().contains(version));
+        for shared_type in protocol.shared_types.iter_mut() {
+            project_custom_type(shared_type, version);
+        }
+
+        protocol
+    }
+}
+
+fn project_custom_type(custom_type: &mut CustomType, version: ProtocolVersion) {
+    let attributes = custom_type.attributes_mut();
+    attributes.since = None;
+    attributes.until = None;
+    match custom_type {
+        CustomType::Enum {
+            variant, variants, ..
+        } => {
+            project_field_type(variant, version);
+            variants.retain(|_, variant| variant.versions().contains(version));
+            for variant in variants.values_mut() {
+                project_custom_type(variant, version);
+            }
+        }
+        CustomType::Struct { fields, .. } => {
+            fields.retain(|_, field_type| field_type.versions().contains(version));
+            for field_type in fields.values_mut() {
+                project_field_type(field_type, version);
+            }
+        }
+        CustomType::BitField { .. } | CustomType::BitFlags { .. } | CustomType::Unit { .. } => {}
+    }
+}
+
+fn project_field_type(field_type: &mut FieldType, version: ProtocolVersion) {
+    match field_type {
+        FieldType::Annotated {
+            attributes,
+            field_type: inner,
+        } => {
+            attributes.since = None;
+            attributes.until = None;
+            if attributes.is_empty() {
+                // Nothing is left to annotate.
+                let mut inner = (**inner).clone();
+                project_field_type(&mut inner, version);
+                *field_type = inner;
+            } else {
+                project_field_type(inner, version);
+            }
+        }
+        FieldType::Primitive(PrimitiveType::Array { field_type, .. })
+        | FieldType::Primitive(PrimitiveType::Option(field_type))
+        | FieldType::Conditional { field_type, .. }
+        | FieldType::Const { field_type, .. } => project_field_type(field_type, version),
+        FieldType::CustomType(custom_type) => project_custom_type(custom_type, version),
+        _ => {}
+    }
+}
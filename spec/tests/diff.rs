@@ -0,0 +1,278 @@
+use feather_protocol_spec::*;
+
+fn protocol(packets: &str, shared_types: &str) -> Protocol {
+    let spec = format!(
+        "(version: 578, packets: {{ {} }}, shared_types: [{}])",
+        packets, shared_types
+    );
+    ron::de::from_str(&spec).unwrap()
+}
+
+#[test]
+fn identical_protocols() {
+    let minecraft: Minecraft = ron::de::from_str(include_str!("../../protocols/1.15.2.ron")).unwrap();
+    assert_eq!(diff(&minecraft.protocol, &minecraft.protocol), vec![]);
+}
+
+#[test]
+fn packets_added_removed_and_moved() {
+    let old = protocol(
+        r#"(Client, Play, 0): Unit(name: "keep_alive"),
+           (Client, Play, 1): Unit(name: "camera"),"#,
+        "",
+    );
+    let new = protocol(
+        r#"(Client, Play, 0): Unit(name: "spawn_entity"),
+           (Client, Play, 1): Unit(name: "keep_alive"),"#,
+        "",
+    );
+
+    assert_eq!(
+        diff(&old, &new),
+        vec![
+            Change::PacketRemoved {
+                path: "(Client, Play, 0x01)".to_owned(),
+                name: "camera".to_owned(),
+            },
+            Change::PacketMoved {
+                path: "(Client, Play, 0x01)".to_owned(),
+                name: "keep_alive".to_owned(),
+                old_id: 0,
+                new_id: 1,
+            },
+            Change::PacketAdded {
+                path: "(Client, Play, 0x00)".to_owned(),
+                name: "spawn_entity".to_owned(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn fields_added_removed_retyped_and_reordered() {
+    let old = protocol(
+        r#"(Client, Play, 0): Struct(name: "spawn_player", fields: {
+            "entity_id": VarInt,
+            "uuid": Uuid,
+            "yaw": U8,
+            "pitch": U8,
+            "current_item": I16,
+        }),"#,
+        "",
+    );
+    let new = protocol(
+        r#"(Client, Play, 0): Struct(name: "spawn_player", fields: {
+            "entity_id": VarInt,
+            "uuid": Uuid,
+            "pitch": U8,
+            "yaw": I8,
+            "x": F64,
+        }),"#,
+        "",
+    );
+
+    assert_eq!(
+        diff(&old, &new),
+        vec![
+            Change::FieldRemoved {
+                path: "(Client, Play, 0x00).current_item".to_owned(),
+                field_type: "I16".to_owned(),
+            },
+            Change::FieldRetyped {
+                path: "(Client, Play, 0x00).yaw".to_owned(),
+                old_type: "U8".to_owned(),
+                new_type: "I8".to_owned(),
+            },
+            Change::FieldAdded {
+                path: "(Client, Play, 0x00).x".to_owned(),
+                field_type: "F64".to_owned(),
+            },
+            Change::FieldsReordered {
+                path: "(Client, Play, 0x00)".to_owned(),
+                old_order: vec!["entity_id", "uuid", "yaw", "pitch"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                new_order: vec!["entity_id", "uuid", "pitch", "yaw"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn nested_and_shared_types() {
+    let old = protocol(
+        r#"(Client, Play, 0): Struct(name: "tags", fields: {
+            "tags": Array(length: Prefixed(VarInt), field_type: Struct(name: "tag", fields: {
+                "name": String(32767),
+            })),
+        }),"#,
+        r#"Struct(name: "slot", fields: { "present": Boolean }),
+           Unit(name: "position"),"#,
+    );
+    let new = protocol(
+        r#"(Client, Play, 0): Struct(name: "tags", fields: {
+            "tags": Array(length: Prefixed(VarInt), field_type: Struct(name: "tag", fields: {
+                "name": String(32767),
+                "entries": Array(length: Prefixed(VarInt), field_type: VarInt),
+            })),
+        }),"#,
+        r#"Struct(name: "slot", fields: { "present": Boolean, "item_id": VarInt }),
+           Unit(name: "angle"),"#,
+    );
+
+    assert_eq!(
+        diff(&old, &new),
+        vec![
+            Change::FieldAdded {
+                path: "(Client, Play, 0x00).tags.entries".to_owned(),
+                field_type: "Array(Prefixed(VarInt), VarInt)".to_owned(),
+            },
+            Change::SharedTypeRemoved {
+                path: "shared_types.position".to_owned(),
+            },
+            Change::SharedTypeAdded {
+                path: "shared_types.angle".to_owned(),
+            },
+            Change::FieldAdded {
+                path: "shared_types.slot.item_id".to_owned(),
+                field_type: "VarInt".to_owned(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn enum_variants() {
+    let old = protocol(
+        r#"(Client, Play, 0): Enum(name: "game_mode", variant: U8, variants: {
+            0: Unit(name: "survival"),
+            1: Unit(name: "creative"),
+            3: Unit(name: "spectator"),
+        }),"#,
+        "",
+    );
+    let new = protocol(
+        r#"(Client, Play, 0): Enum(name: "game_mode", variant: VarInt, variants: {
+            0: Unit(name: "survival"),
+            1: Unit(name: "creative_mode"),
+            2: Unit(name: "adventure"),
+        }),"#,
+        "",
+    );
+
+    let changes = diff(&old, &new);
+    assert_eq!(
+        changes,
+        vec![
+            Change::DiscriminantRetyped {
+                path: "(Client, Play, 0x00)".to_owned(),
+                old_type: "U8".to_owned(),
+                new_type: "VarInt".to_owned(),
+            },
+            Change::VariantRemoved {
+                path: "(Client, Play, 0x00)[3]".to_owned(),
+                name: "spectator".to_owned(),
+            },
+            Change::VariantRenamed {
+                path: "(Client, Play, 0x00)[1]".to_owned(),
+                old_name: "creative".to_owned(),
+                new_name: "creative_mode".to_owned(),
+            },
+            Change::VariantAdded {
+                path: "(Client, Play, 0x00)[2]".to_owned(),
+                name: "adventure".to_owned(),
+            },
+        ]
+    );
+
+    let readable: Vec<_> = changes.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        readable,
+        vec![
+            "~ discriminant of (Client, Play, 0x00): U8 -> VarInt",
+            "- variant (Client, Play, 0x00)[3] spectator",
+            "~ variant (Client, Play, 0x00)[1]: creative -> creative_mode",
+            "+ variant (Client, Play, 0x00)[2] adventure",
+        ]
+    );
+}
+
+#[test]
+fn json_output() {
+    let old = protocol(r#"(Client, Play, 0): Unit(name: "keep_alive"),"#, "");
+    let new = protocol(r#"(Client, Play, 2): Unit(name: "keep_alive"),"#, "");
+
+    let json = serde_json::to_value(diff(&old, &new)).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([{
+            "change": "packet_moved",
+            "path": "(Client, Play, 0x02)",
+            "name": "keep_alive",
+            "old_id": 0,
+            "new_id": 2,
+        }])
+    );
+}
+
+#[test]
+fn documentation_and_defaults_are_not_changes() {
+    let old = protocol(
+        r#"(Client, Play, 0): Struct(name: "effect", fields: {
+            "disable_relative_volume": Boolean,
+        }),"#,
+        "",
+    );
+    let new = protocol(
+        r#"(Client, Play, 0): Struct(name: "effect", fields: {
+            "disable_relative_volume": Annotated(attributes: (doc: "Plays the sound at full volume.", default: false), field_type: Boolean),
+        }),"#,
+        "",
+    );
+    assert_eq!(diff(&old, &new), vec![]);
+}
+
+#[test]
+fn versions() {
+    let spec = |version: &str, protocol_version: u64| -> Minecraft {
+        let spec = format!(
+            r#"(spec_version: 1, version: "{}", major_version: "1.15", protocol: (version: {}, packets: {{}}, shared_types: []))"#,
+            version, protocol_version
+        );
+        ron::de::from_str(&spec).unwrap()
+    };
+    let old = spec("1.15.1", 575);
+    let new = spec("1.15.2", 578);
+
+    let changes = diff_minecraft(&old, &new);
+    assert_eq!(
+        changes,
+        vec![
+            Change::VersionChanged {
+                path: "version".to_owned(),
+                old_version: "1.15.1".to_owned(),
+                new_version: "1.15.2".to_owned(),
+            },
+            Change::VersionChanged {
+                path: "protocol.version".to_owned(),
+                old_version: "575".to_owned(),
+                new_version: "578".to_owned(),
+            },
+        ]
+    );
+    assert_eq!(changes[1].to_string(), "~ protocol.version: 575 -> 578");
+    assert_eq!(
+        serde_json::to_value(&changes[1]).unwrap(),
+        serde_json::json!({
+            "change": "version_changed",
+            "path": "protocol.version",
+            "old_version": "575",
+            "new_version": "578",
+        })
+    );
+    assert_eq!(diff_minecraft(&new, &new), vec![]);
+}
@@ -0,0 +1,142 @@
+use feather_protocol_spec::*;
+
+const SPEC: &str = r#"(
+    spec_version: 1,
+    version: "1.15.2",
+    major_version: "1.15",
+    protocol: (
+        version: 578,
+        packets: {
+            (Client, Play, 0): Unit(name: "spawn_entity"),
+            // Moves with its packet.
+            (Client, Play, 1): Unit(name: "spawn_experience_orb"),
+            (Client, Play, 2): Unit(name: "spawn_weather_entity"),
+            (Server, Play, 0): Unit(name: "teleport_confirm"),
+            (Server, Play, 1): Unit(name: "query_block_nbt"),
+        },
+        shared_types: [],
+    ),
+)
+"#;
+
+fn minecraft() -> Minecraft {
+    ron::de::from_str(SPEC).unwrap()
+}
+
+fn identifier(direction: PacketDirection, id: u64) -> PacketIdentifier {
+    PacketIdentifier(direction, PacketStage::Play, id.into())
+}
+
+fn names(protocol: &Protocol) -> Vec<(PacketDirection, u64, &str)> {
+    protocol
+        .packets
+        .iter()
+        .map(|(identifier, packet)| (identifier.direction(), *identifier.id(), packet.name()))
+        .collect()
+}
+
+#[test]
+fn insert_renumbers_following_packets() {
+    use PacketDirection::*;
+    let mut protocol = minecraft().protocol;
+    let packet: CustomType = ron::de::from_str(r#"Unit(name: "spawn_painting")"#).unwrap();
+    let renumbering = protocol.insert_packet(identifier(Client, 1), packet);
+
+    assert_eq!(
+        names(&protocol),
+        [
+            (Client, 0, "spawn_entity"),
+            (Client, 1, "spawn_painting"),
+            (Client, 2, "spawn_experience_orb"),
+            (Client, 3, "spawn_weather_entity"),
+            (Server, 0, "teleport_confirm"),
+            (Server, 1, "query_block_nbt"),
+        ]
+    );
+    assert_eq!(renumbering.len(), 2);
+    assert_eq!(renumbering[&identifier(Client, 1)], identifier(Client, 2));
+    assert!(protocol.diagnostics().is_empty());
+}
+
+#[test]
+fn remove_renumbers_following_packets() {
+    use PacketDirection::*;
+    let mut protocol = minecraft().protocol;
+    let (packet, renumbering) = protocol.remove_packet(&identifier(Client, 0)).unwrap();
+
+    assert_eq!(packet.name(), "spawn_entity");
+    assert_eq!(
+        names(&protocol),
+        [
+            (Client, 0, "spawn_experience_orb"),
+            (Client, 1, "spawn_weather_entity"),
+            (Server, 0, "teleport_confirm"),
+            (Server, 1, "query_block_nbt"),
+        ]
+    );
+    assert_eq!(renumbering.len(), 2);
+    assert!(protocol.remove_packet(&identifier(Client, 2)).is_err());
+}
+
+#[test]
+fn move_renumbers_packets_in_between() {
+    use PacketDirection::*;
+    let mut protocol = minecraft().protocol;
+    let renumbering = protocol
+        .move_packet(&identifier(Client, 2), 0.into())
+        .unwrap();
+
+    assert_eq!(
+        names(&protocol)[..3],
+        [
+            (Client, 0, "spawn_weather_entity"),
+            (Client, 1, "spawn_entity"),
+            (Client, 2, "spawn_experience_orb"),
+        ]
+    );
+    let expected: Renumbering = vec![
+        (identifier(Client, 0), identifier(Client, 1)),
+        (identifier(Client, 1), identifier(Client, 2)),
+        (identifier(Client, 2), identifier(Client, 0)),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(renumbering, expected);
+}
+
+#[test]
+fn gaps_are_still_flagged() {
+    use PacketDirection::*;
+    let mut protocol = minecraft().protocol;
+    let renumbering = protocol
+        .move_packet(&identifier(Server, 0), 5.into())
+        .unwrap();
+
+    assert_eq!(renumbering[&identifier(Server, 0)], identifier(Server, 5));
+    let diagnostics = protocol.diagnostics();
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert!(diagnostics[0].message.contains("skip from 0x00 to 0x05"));
+}
+
+#[test]
+fn comments_follow_renumbered_packets() {
+    let mut minecraft = minecraft();
+    let renumbering = minecraft
+        .protocol
+        .move_packet(&identifier(PacketDirection::Client, 1), 2.into())
+        .unwrap();
+    let spec = Spec::Minecraft(minecraft);
+    let rewritten = Format::Ron.rewrite(SPEC, &spec, &renumbering).unwrap();
+
+    assert!(
+        rewritten.contains(
+            r#"            // Moves with its packet.
+            (Client, Play, 2): Unit(
+                name: "spawn_experience_orb",
+            ),"#
+        ),
+        "{}",
+        rewritten
+    );
+}
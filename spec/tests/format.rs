@@ -0,0 +1,316 @@
+use feather_protocol_spec::*;
+
+const SPEC: &str = include_str!("../../protocols/1.15.2.ron");
+
+fn round_trip(format: Format) -> String {
+    let spec = Spec::parse(SPEC, Format::Ron).unwrap();
+    let converted = spec.to_string(format).unwrap();
+    Spec::parse(&converted, format)
+        .unwrap()
+        .to_string(Format::Ron)
+        .unwrap()
+}
+
+#[test]
+fn formats_by_extension() {
+    assert_eq!(Format::from_path("protocols/1.15.2.ron"), Some(Format::Ron));
+    assert_eq!(Format::from_path("1.15.2.json"), Some(Format::Json));
+    assert_eq!(Format::from_path("1.15.2.yaml"), Some(Format::Yaml));
+    assert_eq!(Format::from_path("1.15.2.yml"), Some(Format::Yaml));
+    assert_eq!(Format::from_path("1.15.2.toml"), None);
+}
+
+#[test]
+fn json_round_trip() {
+    assert_eq!(round_trip(Format::Json), SPEC);
+}
+
+#[test]
+fn yaml_round_trip() {
+    assert_eq!(round_trip(Format::Yaml), SPEC);
+}
+
+#[test]
+fn json_keys_are_strings() {
+    let spec = Spec::parse(SPEC, Format::Ron).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&spec.to_string(Format::Json).unwrap()).unwrap();
+    let packets = &json["protocol"]["packets"];
+    assert_eq!(packets["(Client, Status, 0)"]["Struct"]["name"], "response");
+    assert_eq!(packets["(Server, Handshaking, 0)"]["Struct"]["name"], "handshake");
+}
+
+#[test]
+fn json_spec() {
+    let source = r#"{
+        "spec_version": 1,
+        "version": "1.15.2",
+        "major_version": "1.15",
+        "protocol": {
+            "version": 578,
+            "packets": {
+                "(Client, Play, 0)": {
+                    "Struct": {
+                        "name": "keep_alive",
+                        "fields": { "id": "I64", "reason": { "String": 16 } }
+                    }
+                },
+                "(Client, Play, 1)": {
+                    "Enum": {
+                        "name": "flag",
+                        "variant": "Boolean",
+                        "variants": {
+                            "false": { "Unit": { "name": "off" } },
+                            "true": { "Unit": { "name": "on" } }
+                        }
+                    }
+                }
+            },
+            "shared_types": []
+        }
+    }"#;
+    let minecraft = match Spec::parse(source, Format::Json).unwrap() {
+        Spec::Minecraft(minecraft) => minecraft,
+        Spec::Patch(_) => panic!("parsed as a patch"),
+    };
+    let packets = &minecraft.protocol.packets;
+    let keep_alive = &packets[&PacketIdentifier(PacketDirection::Client, PacketStage::Play, 0.into())];
+    match keep_alive {
+        CustomType::Struct { fields, .. } => {
+            assert_eq!(fields.keys().map(|field| field.as_str()).collect::<Vec<_>>(), ["id", "reason"]);
+            assert_eq!(fields[0], FieldType::Integer(IntegerType::I64));
+            assert_eq!(fields[1], FieldType::Primitive(PrimitiveType::String(16)));
+        }
+        _ => panic!("not a struct"),
+    }
+    let flag = &packets[&PacketIdentifier(PacketDirection::Client, PacketStage::Play, 1.into())];
+    match flag {
+        CustomType::Enum { variants, .. } => assert_eq!(
+            variants.keys().cloned().collect::<Vec<_>>(),
+            [Literal::Boolean(false), Literal::Boolean(true)]
+        ),
+        _ => panic!("not an enum"),
+    }
+    assert!(minecraft.validate().is_ok());
+}
+
+#[test]
+fn json_keys_take_the_type_of_their_discriminant() {
+    let source = r#"{
+        "spec_version": 1,
+        "version": "1.15.2",
+        "major_version": "1.15",
+        "protocol": {
+            "version": 578,
+            "packets": {
+                "(Client, Play, 0)": {
+                    "Enum": {
+                        "name": "channel",
+                        "variant": { "String": 8 },
+                        "variants": {
+                            "1": { "Unit": { "name": "one" } },
+                            "true": { "Unit": { "name": "yes" } }
+                        }
+                    }
+                },
+                "(Client, Play, 1)": {
+                    "Struct": {
+                        "name": "teams",
+                        "fields": {
+                            "mode": "I8",
+                            "action": {
+                                "Enum": {
+                                    "name": "teams_action",
+                                    "variant": { "Key": "mode" },
+                                    "variants": {
+                                        "0": { "Unit": { "name": "create" } },
+                                        "1": { "Unit": { "name": "remove" } }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "shared_types": []
+        }
+    }"#;
+    let minecraft = match Spec::parse(source, Format::Json).unwrap() {
+        Spec::Minecraft(minecraft) => minecraft,
+        Spec::Patch(_) => panic!("parsed as a patch"),
+    };
+    let packets = &minecraft.protocol.packets;
+    let channel = &packets[&PacketIdentifier(PacketDirection::Client, PacketStage::Play, 0.into())];
+    match channel {
+        CustomType::Enum { variants, .. } => assert_eq!(
+            variants.keys().cloned().collect::<Vec<_>>(),
+            [Literal::String("1".to_owned()), Literal::String("true".to_owned())]
+        ),
+        _ => panic!("not an enum"),
+    }
+    let teams = &packets[&PacketIdentifier(PacketDirection::Client, PacketStage::Play, 1.into())];
+    match teams {
+        CustomType::Struct { fields, .. } => match &fields[1] {
+            FieldType::CustomType(CustomType::Enum { variants, .. }) => assert_eq!(
+                variants.keys().cloned().collect::<Vec<_>>(),
+                [Literal::Int(0), Literal::Int(1)]
+            ),
+            _ => panic!("not an enum"),
+        },
+        _ => panic!("not a struct"),
+    }
+    assert!(minecraft.validate().is_ok());
+}
+
+#[test]
+fn json_patch() {
+    let source = r#"{
+        "spec_version": 1,
+        "base": "1.15.2.ron",
+        "version": "1.14.4",
+        "major_version": "1.14",
+        "protocol_version": 498,
+        "remove_packets": [["Client", "Play", 0]],
+        "renumber": { "(Client, Play, 2)": 1 }
+    }"#;
+    match Spec::parse(source, Format::Json).unwrap() {
+        Spec::Patch(patch) => {
+            assert_eq!(patch.remove_packets, [PacketIdentifier(PacketDirection::Client, PacketStage::Play, 0.into())]);
+            assert_eq!(patch.renumber.len(), 1);
+        }
+        Spec::Minecraft(_) => panic!("parsed as a full spec"),
+    }
+}
+
+#[test]
+fn canonical_spec_is_formatted() {
+    assert_eq!(Format::Ron.format(SPEC).unwrap(), SPEC);
+}
+
+const COMMENTED: &str = r#"// The protocol of 1.15.2.
+(
+    spec_version: 1,
+    version: "1.15.2",
+    major_version: "1.15",
+    protocol: (
+        version: 578,
+        packets: {
+            // Sent every few seconds.
+            (Client, Play, 0x21): Struct(name: "keep_alive", fields: {
+                "id": I64, // echoed by the client
+            }),
+            /* First in the play stage. */
+            (Client, Play, 0x00): Unit(name: "spawn_entity"),
+        },
+        shared_types: [
+            Enum(
+                name: "hand",
+                variant: VarInt,
+                variants: {
+                    1: Unit(name: "off_hand"),
+                    // The default.
+                    0: Unit(name: "main_hand"),
+                },
+            ),
+            // No more shared types.
+        ],
+    ),
+)
+"#;
+
+#[test]
+fn formatting_keeps_comments() {
+    let formatted = Format::Ron.format(COMMENTED).unwrap();
+    assert_eq!(
+        formatted,
+        r#"// The protocol of 1.15.2.
+(
+    spec_version: 1,
+    version: "1.15.2",
+    major_version: "1.15",
+    protocol: (
+        version: 578,
+        packets: {
+            /* First in the play stage. */
+            (Client, Play, 0): Unit(
+                name: "spawn_entity",
+            ),
+            // Sent every few seconds.
+            (Client, Play, 33): Struct(
+                name: "keep_alive",
+                fields: {
+                    "id": Integer(I64), // echoed by the client
+                },
+            ),
+        },
+        shared_types: [
+            Enum(
+                name: "hand",
+                variant: Integer(VarInt),
+                variants: {
+                    // The default.
+                    0: Unit(
+                        name: "main_hand",
+                    ),
+                    1: Unit(
+                        name: "off_hand",
+                    ),
+                },
+            ),
+            // No more shared types.
+        ],
+    ),
+)
+"#
+    );
+    assert_eq!(Format::Ron.format(&formatted).unwrap(), formatted);
+}
+
+const CLOSING_COMMENTS: &str = r#"(
+    spec_version: 1,
+    version: "1.15.2",
+    major_version: "1.15",
+    protocol: (
+        version: 578,
+        packets: {
+            (Client, Play, 0x21): Struct(name: "keep_alive", fields: { // echoed by the client
+                "id": I64,
+            }), // sent every few seconds
+        },
+        shared_types: [],
+    ),
+)
+"#;
+
+#[test]
+fn comments_after_closing_brackets() {
+    let formatted = Format::Ron.format(CLOSING_COMMENTS).unwrap();
+    assert_eq!(
+        formatted,
+        r#"(
+    spec_version: 1,
+    version: "1.15.2",
+    major_version: "1.15",
+    protocol: (
+        version: 578,
+        packets: {
+            (Client, Play, 33): Struct(
+                name: "keep_alive",
+                fields: { // echoed by the client
+                    "id": Integer(I64),
+                },
+            ), // sent every few seconds
+        },
+        shared_types: [],
+    ),
+)
+"#
+    );
+    assert_eq!(Format::Ron.format(&formatted).unwrap(), formatted);
+}
+
+#[test]
+fn yaml_comments_are_not_dropped() {
+    let spec = Spec::parse(SPEC, Format::Ron).unwrap();
+    let yaml = format!("# The protocol of 1.15.2.\n{}", spec.to_string(Format::Yaml).unwrap());
+    assert!(Format::Yaml.format(&yaml).is_err());
+}
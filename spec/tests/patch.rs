@@ -0,0 +1,139 @@
+use feather_protocol_spec::*;
+
+fn base() -> Minecraft {
+    ron::de::from_str(
+        r#"(spec_version: 1, version: "1.15.2", major_version: "1.15", protocol: (version: 578, packets: {
+            (Client, Play, 0): Unit(name: "spawn_entity"),
+            (Client, Play, 1): Unit(name: "spawn_experience_orb"),
+            (Client, Play, 2): Unit(name: "spawn_global_entity"),
+        }, shared_types: [
+            Struct(name: "slot", fields: { "present": Boolean }),
+            Struct(name: "position", fields: { "value": U64 }),
+        ]))"#,
+    )
+    .unwrap()
+}
+
+/// A patch deriving 1.14.4 from `base` through `overrides`.
+fn patch(overrides: &str) -> ProtocolPatch {
+    let source = format!(
+        r#"(
+            spec_version: 1,
+            base: "1.15.2.ron",
+            version: "1.14.4",
+            major_version: "1.14",
+            protocol_version: 498,
+            {}
+        )"#,
+        overrides
+    );
+    match Spec::parse(&source, Format::Ron).unwrap() {
+        Spec::Patch(patch) => patch,
+        Spec::Minecraft(_) => panic!("parsed as a full spec"),
+    }
+}
+
+#[test]
+fn empty_patch() {
+    let minecraft = patch("").apply(base()).unwrap();
+    assert_eq!(*minecraft.version, "1.14.4");
+    assert_eq!(*minecraft.major_version, "1.14");
+    assert_eq!(*minecraft.protocol.version, 498);
+    assert_eq!(minecraft.protocol.packets, base().protocol.packets);
+    assert_eq!(
+        minecraft.protocol.shared_types,
+        base().protocol.shared_types
+    );
+}
+
+#[test]
+fn packet_overrides() {
+    let protocol = patch(
+        r#"
+            remove_packets: [(Client, Play, 0)],
+            renumber: { (Client, Play, 1): 0, (Client, Play, 2): 1 },
+            packets: {
+                (Client, Play, 1): Struct(name: "spawn_global_entity", fields: { "entity_id": VarInt }),
+                (Client, Play, 2): Unit(name: "spawn_mob"),
+            },
+        "#,
+    )
+    .apply(base())
+    .unwrap()
+    .protocol;
+
+    let packets: Vec<_> = protocol
+        .packets
+        .iter()
+        .map(|(identifier, packet)| (*identifier.id(), packet.name().to_owned()))
+        .collect();
+    assert_eq!(
+        packets,
+        vec![
+            (0, "spawn_experience_orb".to_owned()),
+            (1, "spawn_global_entity".to_owned()),
+            (2, "spawn_mob".to_owned()),
+        ]
+    );
+    assert!(matches!(
+        protocol.packets[&PacketIdentifier(PacketDirection::Client, PacketStage::Play, 1.into())],
+        CustomType::Struct { .. }
+    ));
+}
+
+#[test]
+fn shared_type_overrides() {
+    let protocol = patch(
+        r#"
+            remove_shared_types: ["position"],
+            shared_types: [
+                Struct(name: "slot", fields: { "present": Boolean, "item_id": VarInt }),
+                Unit(name: "angle"),
+            ],
+        "#,
+    )
+    .apply(base())
+    .unwrap()
+    .protocol;
+
+    let names: Vec<_> = protocol
+        .shared_types
+        .iter()
+        .map(|shared_type| shared_type.name())
+        .collect();
+    assert_eq!(names, vec!["slot", "angle"]);
+    assert!(
+        matches!(&protocol.shared_types[0], CustomType::Struct { fields, .. } if fields.len() == 2)
+    );
+}
+
+#[test]
+fn renumbered_onto_existing_packet() {
+    let error = patch("renumber: { (Client, Play, 0): 2 },")
+        .apply(base())
+        .unwrap_err();
+    assert_eq!(
+        error,
+        "Packet \"spawn_entity\" is renumbered to (Client, Play, 0x02), which is taken by \"spawn_global_entity\"."
+    );
+}
+
+#[test]
+fn removing_missing_packet() {
+    let error = patch("remove_packets: [(Server, Play, 0)],")
+        .apply(base())
+        .unwrap_err();
+    assert_eq!(error, "There is no packet (Server, Play, 0x00) to remove.");
+}
+
+#[test]
+fn load_resolves_base() {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../protocols");
+    let patched = Minecraft::load(directory.join("1.15.1.ron")).unwrap();
+    let base = Minecraft::load(directory.join("1.15.2.ron")).unwrap();
+
+    assert_eq!(*patched.version, "1.15.1");
+    assert_eq!(*patched.protocol.version, 575);
+    assert_eq!(diff(&base.protocol, &patched.protocol), vec![]);
+    patched.validate().unwrap();
+}
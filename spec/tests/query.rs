@@ -0,0 +1,76 @@
+use feather_protocol_spec::*;
+
+fn protocol() -> Protocol {
+    let minecraft: Minecraft = ron::de::from_str(include_str!("../../protocols/1.15.2.ron")).unwrap();
+    minecraft.protocol
+}
+
+#[test]
+fn packets_by_direction_and_stage() {
+    let protocol = protocol();
+    let filter = PacketFilter {
+        direction: Some(PacketDirection::Server),
+        stage: Some(PacketStage::Status),
+    };
+    let names: Vec<_> = protocol
+        .packets_matching(filter)
+        .map(|(_, packet)| packet.name())
+        .collect();
+    assert_eq!(names, ["request", "ping"]);
+    assert_eq!(protocol.packets_matching(PacketFilter::default()).count(), protocol.packets.len());
+}
+
+#[test]
+fn packets_by_name() {
+    let protocol = protocol();
+    assert_eq!(protocol.packets_named("keep_alive", PacketFilter::default()).len(), 2);
+
+    let filter = PacketFilter {
+        direction: Some(PacketDirection::Client),
+        stage: None,
+    };
+    let found = protocol.packets_named("keep_alive", filter);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0.direction(), PacketDirection::Client);
+}
+
+#[test]
+fn uses_of_shared_type() {
+    let protocol = protocol();
+    let uses = protocol.uses("slot", PacketFilter::default());
+    let set_slot = uses.iter().find(|found| found.owner == "set_slot").unwrap();
+    assert_eq!(set_slot.path.to_string(), "(Client, Play, 0x17).slot_data");
+    assert_eq!(set_slot.field_type, "Shared(slot)");
+    assert!(uses
+        .iter()
+        .any(|found| found.field_type == "Option(Shared(slot))"));
+
+    let filter = PacketFilter {
+        direction: Some(PacketDirection::Server),
+        stage: None,
+    };
+    assert!(protocol
+        .uses("slot", filter)
+        .iter()
+        .all(|found| found.owner != "set_slot"));
+}
+
+#[test]
+fn layout_of_packet() {
+    let protocol = protocol();
+    let (_, packet) = protocol.packets_named("update_structure_block", PacketFilter::default())[0];
+    let lines: Vec<_> = layout(packet)
+        .into_iter()
+        .take(4)
+        .map(|line| (line.depth, line.label, line.wire_type))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (0, "location".to_owned(), "Shared(position)".to_owned()),
+            (0, "action".to_owned(), "Enum(action)".to_owned()),
+            (1, "variant".to_owned(), "VarInt".to_owned()),
+            (1, "0 => update_data".to_owned(), String::new()),
+        ]
+    );
+}
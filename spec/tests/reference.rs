@@ -0,0 +1,119 @@
+use feather_protocol_spec::*;
+
+fn protocol() -> Protocol {
+    let minecraft: Minecraft =
+        ron::de::from_str(include_str!("../../protocols/1.15.2.ron")).unwrap();
+    minecraft.protocol
+}
+
+const SPEC: &str = r#"(
+    version: 578,
+    packets: {
+        (Server, Play, 14): Struct(name: "use_entity", attributes: (doc: "Attacks or <right> clicks | an entity."), fields: {
+            "target": VarInt,
+            "mouse": VarInt,
+            "x": Conditional(condition: Equal("mouse", 2), field_type: F32),
+            "interaction": Enum(name: "interaction", variant: Key("mouse"), variants: {
+                0: Struct(name: "interact", fields: {"hand": Shared("hand")}),
+                1: Unit(name: "attack"),
+            }),
+            "reserved": Const(field_type: U8, value: 0),
+        }),
+    },
+    shared_types: [
+        Enum(name: "hand", variant: VarInt, variants: {
+            0: Unit(name: "main_hand"),
+            1: Unit(name: "off_hand"),
+        }),
+    ],
+)"#;
+
+#[test]
+fn packets_are_grouped_by_stage_and_direction() {
+    let markdown = reference(&protocol(), Markup::Markdown);
+    assert!(markdown
+        .starts_with("# Protocol 578\n\n## Handshaking\n\n### Serverbound\n\n#### handshake\n"));
+    assert!(
+        markdown.contains(
+            "| Packet ID | State | Bound To | Field Name | Field Type | Notes |
+| --- | --- | --- | --- | --- | --- |
+| `0x00` | Handshaking | Server | protocol_version | VarInt |  |
+|  |  |  | server_address | String (255) |  |"
+        ),
+        "{}",
+        markdown
+    );
+
+    let stages: Vec<_> = markdown
+        .lines()
+        .filter(|line| line.starts_with("## "))
+        .collect();
+    assert_eq!(
+        stages,
+        [
+            "## Handshaking",
+            "## Status",
+            "## Login",
+            "## Play",
+            "## Shared Types"
+        ]
+    );
+    assert!(markdown.contains("| `0x00` | Status | Server | no fields |  |  |"));
+}
+
+#[test]
+fn nested_types_are_shown_inline() {
+    let markdown = reference(&protocol(), Markup::Markdown);
+    assert!(markdown.contains(
+        "|  |  |  | &emsp;&emsp;&emsp;darken_sky | Flag | Mask `0x01`. |
+|  |  |  | &emsp;&emsp;&emsp;dragon_bar | Flag | Mask `0x02`. |"
+    ));
+    assert!(markdown.contains("| slot_data | Array of [slot](#shared-slot) (length in `count`) |"));
+    assert!(markdown.contains(
+        "<a id=\"shared-position\"></a>
+
+### position
+
+Wire type: BitField (U64)
+
+| Field Name | Field Type | Notes |
+| --- | --- | --- |
+| x | I32 (26 bits) | Bits 63 to 38. |
+| z | I32 (26 bits) | Bits 37 to 12. |
+| y | I16 (12 bits) | Bits 11 to 0. |"
+    ));
+}
+
+#[test]
+fn html_tables_span_the_packet_columns() {
+    let protocol: Protocol = ron::de::from_str(SPEC).unwrap();
+    let html = reference(&protocol, Markup::Html);
+
+    assert!(
+        html.contains("<h4>use_entity</h4>\n<p>Attacks or &lt;right&gt; clicks | an entity.</p>")
+    );
+    let rows: Vec<_> = html
+        .lines()
+        .filter(|line| line.starts_with("<tr><td"))
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            "<tr><td rowspan=\"8\"><code>0x0e</code></td><td rowspan=\"8\">Play</td>\
+             <td rowspan=\"8\">Server</td><td>target</td><td>VarInt</td><td></td></tr>",
+            "<tr><td>mouse</td><td>VarInt</td><td></td></tr>",
+            "<tr><td>x</td><td>F32</td><td>Only present when <code>mouse</code> is <code>2</code>.</td></tr>",
+            "<tr><td>interaction</td><td>Enum</td><td></td></tr>",
+            "<tr><td>&emsp;interact</td><td>Struct</td><td>When <code>mouse</code> is <code>0</code>.</td></tr>",
+            "<tr><td>&emsp;&emsp;hand</td><td><a href=\"#shared-hand\">hand</a></td><td></td></tr>",
+            "<tr><td>&emsp;attack</td><td></td><td>When <code>mouse</code> is <code>1</code>.</td></tr>",
+            "<tr><td>reserved</td><td>U8</td><td>Always <code>0</code>.</td></tr>",
+            "<tr><td>main_hand</td><td></td><td>Written as <code>0</code>.</td></tr>",
+            "<tr><td>off_hand</td><td></td><td>Written as <code>1</code>.</td></tr>",
+        ]
+    );
+    assert!(html.contains("<h3 id=\"shared-hand\">hand</h3>\n<p>Wire type: Enum (VarInt)</p>"));
+
+    let markdown = reference(&protocol, Markup::Markdown);
+    assert!(markdown.contains("Attacks or <right> clicks \\| an entity."));
+}
@@ -0,0 +1,52 @@
+use feather_protocol_spec::*;
+use serde_json::{json, Value};
+
+fn schema_json() -> Value {
+    serde_json::to_value(schema()).unwrap()
+}
+
+#[test]
+fn schema_defines_spec_types() {
+    let schema = schema_json();
+    let definitions = schema["definitions"].as_object().unwrap();
+    for name in &[
+        "Minecraft",
+        "ProtocolPatch",
+        "Protocol",
+        "CustomType",
+        "FieldType",
+        "ArrayLength",
+        "IntegerType",
+        "PrimitiveType",
+        "Condition",
+        "Literal",
+    ] {
+        assert!(definitions.contains_key(*name), "`{}` is not defined", name);
+    }
+    assert_eq!(schema["title"], json!("Spec"));
+}
+
+#[test]
+fn field_type_schema_accepts_shorthands() {
+    let schema = schema_json();
+    let field_type = serde_json::to_string(&schema["definitions"]["FieldType"]).unwrap();
+    for form in &["\"VarInt\"", "\"Boolean\"", "\"String\"", "\"Struct\"", "\"Annotated\"", "\"Primitive\""] {
+        assert!(field_type.contains(form), "{} is not described", form);
+    }
+}
+
+#[test]
+fn optional_fields_are_not_required() {
+    let schema = schema_json();
+    let unit = schema["definitions"]["CustomType"]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|variant| &variant["properties"]["Unit"])
+        .find(|unit| !unit.is_null())
+        .unwrap();
+    assert_eq!(unit["required"], json!(["name"]));
+    let attributes = &schema["definitions"]["Attributes"];
+    assert_eq!(attributes["properties"]["doc"]["type"], json!("string"));
+    assert!(attributes["required"].is_null());
+}
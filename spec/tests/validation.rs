@@ -0,0 +1,498 @@
+use anyhow::Result;
+use feather_protocol_spec::*;
+
+fn protocol(packet: &str) -> Protocol {
+    protocol_with_shared_types(packet, "")
+}
+
+fn protocol_with_shared_types(packet: &str, shared_types: &str) -> Protocol {
+    let spec = format!(
+        "(version: 578, packets: {{ (Client, Play, 0): {} }}, shared_types: [{}])",
+        packet, shared_types
+    );
+    ron::de::from_str(&spec).unwrap()
+}
+
+#[test]
+fn protocol_1_15_2() -> Result<()> {
+    let minecraft: Minecraft = ron::de::from_str(include_str!("../../protocols/1.15.2.ron"))?;
+    minecraft.validate()
+}
+
+#[test]
+fn conditional_field() {
+    let protocol = protocol(
+        r#"Struct(name: "chunk_data", fields: {
+            "full_chunk": Boolean,
+            "biomes": Conditional(
+                condition: Equal("full_chunk", true),
+                field_type: Array(length: FixedLength(1024), field_type: I32),
+            ),
+        })"#,
+    );
+    assert!(protocol.validate().is_ok());
+}
+
+#[test]
+fn conditional_field_refers_to_later_field() {
+    let protocol = protocol(
+        r#"Struct(name: "map_data", fields: {
+            "rows": Conditional(condition: NotEqual("columns", 0), field_type: U8),
+            "columns": U8,
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("comes after"), "{}", error);
+}
+
+#[test]
+fn conditional_field_refers_to_missing_field() {
+    let protocol = protocol(
+        r#"Struct(name: "property", fields: {
+            "signature": Conditional(condition: Equal("is_signed", true), field_type: String(32767)),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("does not exist"), "{}", error);
+}
+
+#[test]
+fn conditional_field_compares_to_the_wrong_type() {
+    let protocol = protocol_with_shared_types(
+        r#"Struct(name: "map_data", fields: {
+            "columns": U8,
+            "rows": Conditional(condition: NotEqual("columns", true), field_type: U8),
+            "mode": Shared("mode"),
+            "data": Conditional(condition: Equal("mode", 1), field_type: U8),
+        })"#,
+        r#"Enum(name: "mode", variant: VarInt, variants: { 1: Unit(name: "full") })"#,
+    );
+    let diagnostics = protocol.diagnostics();
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    assert_eq!(
+        diagnostics[0].message,
+        "The condition of \"map_data.rows\" compares \"columns\" to true, which is not a value \
+         of its type U8."
+    );
+    assert_eq!(diagnostics[0].path.to_string(), "(Client, Play, 0x00).rows");
+    assert!(diagnostics[1].message.contains("compares \"mode\" to 1"));
+}
+
+#[test]
+fn conditional_field_compares_a_bit_field_member() {
+    let protocol = protocol_with_shared_types(
+        r#"Struct(name: "node", fields: {
+            "flags": Shared("node_flags"),
+            "redirect_node": Conditional(condition: Equal("flags.has_redirect", true), field_type: VarInt),
+            "name": Conditional(condition: NotEqual("flags.node_type", 0), field_type: String(32767)),
+        })"#,
+        r#"BitField(name: "node_flags", fields: { "unused": U8(5), "has_redirect": Boolean, "node_type": U8(2) })"#,
+    );
+    assert!(protocol.validate().is_ok());
+}
+
+#[test]
+fn conditional_field_compares_a_missing_or_mistyped_bit_field_member() {
+    let protocol = protocol(
+        r#"Struct(name: "node", fields: {
+            "flags": BitField(name: "node_flags", fields: { "unused": U8(6), "node_type": U8(2) }),
+            "redirect_node": Conditional(condition: Equal("flags.has_redirect", true), field_type: VarInt),
+            "parser": Conditional(condition: Equal("flags.node_type", 4), field_type: String(32767)),
+            "count": U8,
+            "name": Conditional(condition: Equal("count.node_type", 1), field_type: String(32767)),
+        })"#,
+    );
+    let diagnostics = protocol.diagnostics();
+    assert_eq!(diagnostics.len(), 3, "{:?}", diagnostics);
+    assert_eq!(
+        diagnostics[0].message,
+        "The condition of \"node.redirect_node\" compares \"flags.has_redirect\", but \
+         BitField(node_flags) has no member \"has_redirect\"."
+    );
+    assert_eq!(
+        diagnostics[1].message,
+        "The condition of \"node.parser\" compares \"flags.node_type\" to 4, which is not a value \
+         of its type U8(2)."
+    );
+    assert!(diagnostics[2].message.contains("but U8 has no member \"node_type\""));
+}
+
+#[test]
+fn array_length_field() {
+    let protocol = protocol(
+        r#"Struct(name: "window_items", fields: {
+            "count": I16,
+            "slot_data": Array(length: Field("count"), field_type: U8),
+        })"#,
+    );
+    assert!(protocol.validate().is_ok());
+}
+
+#[test]
+fn array_length_field_is_not_an_integer() {
+    let protocol = protocol(
+        r#"Struct(name: "window_items", fields: {
+            "count": String(16),
+            "slot_data": Array(length: Field("count"), field_type: Shared("slot")),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("not an integer"), "{}", error);
+}
+
+#[test]
+fn array_length_field_in_nested_array() {
+    let protocol = protocol(
+        r#"Struct(name: "window_items", fields: {
+            "count": VarInt,
+            "slot_data": Array(
+                length: Prefixed(VarInt),
+                field_type: Array(length: Field("count"), field_type: U8),
+            ),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("nested in another array"), "{}", error);
+}
+
+#[test]
+fn array_length_field_shared_by_two_arrays() {
+    let protocol = protocol(
+        r#"Struct(name: "window_items", fields: {
+            "count": VarInt,
+            "a": Array(length: Field("count"), field_type: U8),
+            "b": Option(Array(length: Field("count"), field_type: U8)),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "The length of \"window_items.b\" refers to \"count\", which is already the length of \"a\"."
+    );
+}
+
+#[test]
+fn terminated_array_terminator_out_of_range() {
+    let protocol = protocol(
+        r#"Struct(name: "entity_metadata", fields: {
+            "entries": Array(length: Terminated(U8, 256), field_type: U8),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("does not fit in U8"), "{}", error);
+}
+
+#[test]
+fn const_and_default_fields() {
+    let protocol = protocol(
+        r#"Struct(name: "effect", fields: {
+            "marker": Const(field_type: U8, value: 7),
+            "disable_relative_volume": Annotated(attributes: (default: false), field_type: Boolean),
+        })"#,
+    );
+    assert!(protocol.validate().is_ok());
+}
+
+#[test]
+fn const_and_default_values_of_the_wrong_type() {
+    let protocol = protocol(
+        r#"Struct(name: "effect", fields: {
+            "marker": Const(field_type: U8, value: 256),
+            "disable_relative_volume": Annotated(attributes: (default: "no"), field_type: Boolean),
+            "nested": Option(Const(field_type: Boolean, value: true)),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("The value 256 of \"effect.marker\" is not a value of its type U8."), "{}", error);
+    assert!(error.contains("The default \"no\" of \"effect.disable_relative_volume\""), "{}", error);
+    assert!(error.contains("constants are only valid directly on a struct field"), "{}", error);
+}
+
+#[test]
+fn field_constraints() {
+    let protocol = protocol(
+        r#"Struct(name: "client_settings", fields: {
+            "view_distance": Annotated(attributes: (min: 2, max: 32), field_type: I8),
+            "brands": Annotated(attributes: (max_len: 4), field_type: Array(length: Prefixed(VarInt), field_type: String(8))),
+        })"#,
+    );
+    assert!(protocol.validate().is_ok());
+}
+
+#[test]
+fn field_constraints_of_the_wrong_type() {
+    let protocol = protocol(
+        r#"Struct(name: "client_settings", fields: {
+            "view_distance": Annotated(attributes: (min: 32, max: 2), field_type: I8),
+            "slot": Annotated(attributes: (max: 300), field_type: U8),
+            "locale": Annotated(attributes: (min: 0), field_type: String(16)),
+            "chat_colors": Annotated(attributes: (max_len: 1), field_type: Boolean),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("The minimum 32 of \"client_settings.view_distance\" is larger than its maximum 2."), "{}", error);
+    assert!(error.contains("The bound 300 of \"client_settings.slot\" does not fit in U8."), "{}", error);
+    assert!(error.contains("\"client_settings.locale\" has a minimum or maximum"), "{}", error);
+    assert!(error.contains("\"client_settings.chat_colors\" has a maximum length"), "{}", error);
+}
+
+#[test]
+fn keyed_enum() {
+    let protocol = protocol(
+        r#"Struct(name: "player_info", fields: {
+            "action": VarInt,
+            "players": Array(length: Prefixed(VarInt), field_type: Struct(name: "player", fields: {
+                "uuid": Uuid,
+                "action": Enum(name: "action", variant: Key("action"), variants: {
+                    4: Unit(name: "remove_player"),
+                }),
+            })),
+        })"#,
+    );
+    assert!(protocol.validate().is_ok());
+}
+
+#[test]
+fn keyed_enum_refers_to_missing_field() {
+    let protocol = protocol(
+        r#"Struct(name: "teams", fields: {
+            "action": Enum(name: "teams_action", variant: Key("mode"), variants: {
+                1: Unit(name: "remove_team"),
+            }),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("not an earlier field of an enclosing struct"), "{}", error);
+}
+
+#[test]
+fn keyed_enum_refers_to_custom_type() {
+    let protocol = protocol(
+        r#"Struct(name: "teams", fields: {
+            "mode": Unit(name: "mode"),
+            "action": Enum(name: "teams_action", variant: Key("mode"), variants: {
+                1: Unit(name: "remove_team"),
+            }),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("not an integer, boolean or string"), "{}", error);
+}
+
+#[test]
+fn key_outside_of_enum() {
+    let protocol = protocol(
+        r#"Struct(name: "teams", fields: {
+            "mode": I8,
+            "action": Key("mode"),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("only valid as the discriminant of an enum"), "{}", error);
+}
+
+#[test]
+fn unresolved_shared_type() {
+    let protocol = protocol_with_shared_types(
+        r#"Struct(name: "update_structure_block", fields: {
+            "location": Shared("postion"),
+        })"#,
+        r#"BitField(name: "position", fields: { "x": I32(26), "z": I32(26), "y": I16(12) })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("\"postion\", which does not exist"), "{}", error);
+}
+
+#[test]
+fn duplicate_shared_type() {
+    let protocol = protocol_with_shared_types(
+        r#"Struct(name: "held_item_change", fields: {})"#,
+        r#"Unit(name: "hand"), Unit(name: "hand")"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("\"hand\" is used more than once"), "{}", error);
+}
+
+#[test]
+fn recursive_shared_type() {
+    let protocol = protocol_with_shared_types(
+        r#"Struct(name: "declare_commands", fields: {})"#,
+        r#"Struct(name: "node", fields: { "child": Option(Shared("redirect")) }),
+           Struct(name: "redirect", fields: { "node": Shared("node") })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("\"node\" -> \"redirect\" -> \"node\""), "{}", error);
+
+    let protocol = protocol_with_shared_types(
+        r#"Struct(name: "declare_commands", fields: {})"#,
+        r#"Struct(name: "node", fields: {
+            "children": Array(length: Prefixed(VarInt), field_type: Shared("node")),
+        })"#,
+    );
+    assert!(protocol.validate().is_ok());
+}
+
+#[test]
+fn enum_literal_does_not_fit_discriminant() {
+    let overflowing = protocol(
+        r#"Struct(name: "entity_animation", fields: {
+            "animation": Enum(name: "animation", variant: U8, variants: {
+                0: Unit(name: "swing_main_arm"),
+                256: Unit(name: "swing_offhand"),
+            }),
+        })"#,
+    );
+    let error = overflowing.validate().unwrap_err().to_string();
+    assert!(error.contains("does not fit its discriminant type"), "{}", error);
+
+    let mismatched = protocol(
+        r#"Struct(name: "teams", fields: {
+            "collision_rule": Enum(name: "collision_rule", variant: String(32), variants: {
+                0: Unit(name: "always"),
+            }),
+        })"#,
+    );
+    let error = mismatched.validate().unwrap_err().to_string();
+    assert!(error.contains("does not fit its discriminant type"), "{}", error);
+}
+
+#[test]
+fn bit_field_width() {
+    let too_narrow = protocol(
+        r#"Struct(name: "block_change", fields: {
+            "location": BitField(name: "position", fields: { "x": I32(26), "z": I32(26), "y": I16(11) }),
+        })"#,
+    );
+    let error = too_narrow.validate().unwrap_err().to_string();
+    assert!(error.contains("63 bits wide in total"), "{}", error);
+
+    let too_wide = protocol(
+        r#"Struct(name: "block_change", fields: {
+            "location": BitField(name: "position", fields: { "x": I8(12), "y": U8(4) }),
+        })"#,
+    );
+    let error = too_wide.validate().unwrap_err().to_string();
+    assert!(error.contains("does not fit into its 8 bit type"), "{}", error);
+}
+
+#[test]
+fn zero_length_string() {
+    let protocol = protocol(
+        r#"Struct(name: "update_command_block", fields: {
+            "command": String(0),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("maximum length of 0"), "{}", error);
+}
+
+#[test]
+fn colliding_type_names() {
+    let protocol = protocol(
+        r#"Struct(name: "update_structure_block", fields: {
+            "mirror": Enum(name: "mirror", variant: VarInt, variants: { 0: Unit(name: "none") }),
+            "rotation": Enum(name: "rotation", variant: VarInt, variants: { 0: Unit(name: "none") }),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert!(error.contains("are both generated as `None`"), "{}", error);
+}
+
+#[test]
+fn collects_every_problem() {
+    let protocol = protocol(
+        r#"Struct(name: "update_command_block", fields: {
+            "command": String(0),
+            "location": Shared("postion"),
+        })"#,
+    );
+    let diagnostics = protocol.diagnostics();
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    assert!(diagnostics.iter().all(Diagnostic::is_error));
+    assert_eq!(diagnostics[1].path.to_string(), "(Client, Play, 0x00).location");
+}
+
+#[test]
+fn skipped_packet_ids_are_warnings() -> Result<()> {
+    let protocol: Protocol = ron::de::from_str(
+        r#"(version: 578, packets: {
+            (Client, Play, 0): Struct(name: "spawn_entity", fields: {}),
+            (Client, Play, 2): Struct(name: "spawn_weather_entity", fields: {}),
+        }, shared_types: [])"#,
+    )?;
+    let diagnostics = protocol.diagnostics();
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert!(diagnostics[0].message.contains("skip from 0x00 to 0x02"));
+    protocol.validate()
+}
+
+#[test]
+fn diagnostics_are_located() -> Result<()> {
+    let source = r#"(
+    version: 578,
+    packets: {
+        // Comments are skipped like whitespace.
+        (Client, Play, 0x2F): Struct(
+            name: "player_info",
+            fields: {
+                "action": VarInt,
+                "players": Array(length: Prefixed(VarInt), field_type: Struct(
+                    name: "player",
+                    fields: {
+                        "name": String(0),
+                    },
+                )),
+            },
+        ),
+    },
+    shared_types: [
+        Struct(name: "chat", fields: { "json": Shared("text") }),
+    ],
+)"#;
+    let protocol: Protocol = ron::de::from_str(source)?;
+    let mut diagnostics = protocol.diagnostics();
+    locate(&mut diagnostics, source);
+
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].path.to_string(), "(Client, Play, 0x2F).players.name");
+    assert_eq!(diagnostics[0].location, Some(Location { line: 12, column: 25 }));
+    assert_eq!(diagnostics[1].path.to_string(), "shared_types.chat.json");
+    assert_eq!(diagnostics[1].location, Some(Location { line: 19, column: 40 }));
+
+    let rendered = diagnostics[0].render("player_info.ron", source);
+    assert!(rendered.starts_with("error: \"player.name\" is a string with a maximum length of 0.\n"));
+    assert!(rendered.contains("  --> player_info.ron:12:25\n"));
+    Ok(())
+}
+
+#[test]
+fn attributes_inside_field_type() {
+    let protocol = protocol(
+        r#"Struct(name: "spawn_entity", attributes: (doc: "Spawns an entity."), fields: {
+            "velocity_x": Annotated(attributes: (doc: "In units of 1/8000 of a block per tick."), field_type: I16),
+            "velocities": Option(Annotated(attributes: (doc: "Misplaced."), field_type: I16)),
+        })"#,
+    );
+    let error = protocol.validate().unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "\"spawn_entity.velocities\" is annotated inside of its type, but attributes are only \
+         valid directly on a struct field."
+    );
+}
+
+#[test]
+fn unsupported_spec_version() {
+    let minecraft: Minecraft = ron::de::from_str(
+        r#"(
+            spec_version: 2,
+            version: "1.15.2",
+            major_version: "1.15",
+            protocol: (version: 578, packets: {}, shared_types: []),
+        )"#,
+    )
+    .unwrap();
+    let error = minecraft.validate().unwrap_err().to_string();
+    assert_eq!(error, "The spec version 2 is not supported, only spec version 1 is.");
+}
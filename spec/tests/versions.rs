@@ -0,0 +1,166 @@
+use feather_protocol_spec::*;
+
+const COMBINED: &str = r#"(
+    version: 578,
+    packets: {
+        (Client, Play, 0): Unit(name: "spawn_entity"),
+        (Client, Play, 1): Unit(name: "spawn_weather_entity", attributes: (until: 500)),
+        (Client, Play, 2): Struct(name: "keep_alive", fields: {
+            "id": I64,
+            "extra": Annotated(attributes: (since: 499), field_type: VarInt),
+            "reason": Annotated(attributes: (doc: "Why.", until: 500), field_type: String(16)),
+        }),
+        (Client, Play, 3): Unit(name: "new_packet", attributes: (since: 499)),
+        (Server, Play, 0): Enum(name: "hand", variant: VarInt, variants: {
+            0: Unit(name: "main_hand"),
+            1: Unit(name: "off_hand", attributes: (since: 510)),
+        }),
+    },
+    shared_types: [
+        Unit(name: "old_type", attributes: (until: 400)),
+    ],
+)"#;
+
+fn combined() -> Protocol {
+    ron::de::from_str(COMBINED).unwrap()
+}
+
+fn names(protocol: &Protocol) -> Vec<(u64, &str)> {
+    protocol
+        .packets
+        .iter()
+        .filter(|(identifier, _)| identifier.direction() == PacketDirection::Client)
+        .map(|(identifier, packet)| (*identifier.id(), packet.name()))
+        .collect()
+}
+
+#[test]
+fn version_ranges() {
+    let range = VersionRange {
+        since: Some(499.into()),
+        until: Some(500.into()),
+    };
+    assert!(!range.contains(498.into()));
+    assert!(range.contains(499.into()));
+    assert!(range.contains(500.into()));
+    assert!(!range.contains(501.into()));
+    assert!(range.overlaps(&VersionRange {
+        since: Some(500.into()),
+        until: None,
+    }));
+    assert!(!range.overlaps(&VersionRange {
+        since: None,
+        until: Some(498.into()),
+    }));
+    assert_eq!(range.to_string(), "since 499 until 500");
+    assert!(VersionRange::default().contains(0.into()));
+}
+
+#[test]
+fn projection_renumbers_packets() {
+    let protocol = combined();
+
+    let old = protocol.project(498.into());
+    assert_eq!(*old.version, 498);
+    assert_eq!(
+        names(&old),
+        [
+            (0, "spawn_entity"),
+            (1, "spawn_weather_entity"),
+            (2, "keep_alive")
+        ]
+    );
+    assert!(old.diagnostics().is_empty(), "{:?}", old.diagnostics());
+
+    let new = protocol.project(578.into());
+    assert_eq!(
+        names(&new),
+        [(0, "spawn_entity"), (1, "keep_alive"), (2, "new_packet")]
+    );
+    assert!(new.shared_types.is_empty());
+    assert!(new
+        .packets
+        .values()
+        .all(|packet| packet.versions().is_unbounded()));
+}
+
+#[test]
+fn projection_leaves_out_fields_and_variants() {
+    let protocol = combined();
+    let keep_alive = |protocol: &Protocol| match protocol
+        .packets_named("keep_alive", PacketFilter::default())[0]
+        .1
+    {
+        CustomType::Struct { fields, .. } => fields.clone(),
+        _ => unreachable!(),
+    };
+
+    let old = protocol.project(499.into());
+    let fields = keep_alive(&old);
+    assert_eq!(
+        fields.keys().map(|name| name.as_str()).collect::<Vec<_>>(),
+        ["id", "extra", "reason"]
+    );
+    // Fields with nothing left to document are no longer documented.
+    let (_, extra) = fields.get_index(1).unwrap();
+    assert_eq!(*extra, FieldType::Integer(IntegerType::VarInt));
+    let (_, reason) = fields.get_index(2).unwrap();
+    assert_eq!(reason.doc(), Some("Why."));
+    assert!(reason.versions().is_unbounded());
+
+    let new = protocol.project(578.into());
+    assert_eq!(keep_alive(&new).len(), 2);
+    let hand =
+        |protocol: &Protocol| match protocol.packets_named("hand", PacketFilter::default())[0].1 {
+            CustomType::Enum { variants, .. } => variants.len(),
+            _ => unreachable!(),
+        };
+    assert_eq!(hand(&old), 1);
+    assert_eq!(hand(&new), 2);
+}
+
+#[test]
+fn empty_ranges_are_errors() {
+    let protocol: Protocol = ron::de::from_str(
+        r#"(version: 578, packets: {
+            (Client, Play, 0): Struct(name: "keep_alive", attributes: (since: 578, until: 498), fields: {
+                "id": Annotated(attributes: (since: 500, until: 499), field_type: I64),
+            }),
+        }, shared_types: [])"#,
+    )
+    .unwrap();
+    let diagnostics = protocol.diagnostics();
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    assert!(diagnostics[0].message.contains("since 578 until 498"));
+    assert_eq!(diagnostics[1].path.to_string(), "(Client, Play, 0x00).id");
+}
+
+#[test]
+fn references_to_versioned_fields_are_errors() {
+    let protocol: Protocol = ron::de::from_str(
+        r#"(version: 578, packets: {
+            (Client, Play, 0): Struct(name: "chunk_data", fields: {
+                "count": Annotated(attributes: (since: 500), field_type: VarInt),
+                "items": Array(length: Field("count"), field_type: U8),
+                "mode": Annotated(attributes: (until: 500), field_type: I8),
+                "action": Enum(name: "action", variant: Key("mode"), variants: {
+                    0: Unit(name: "add"),
+                }),
+            }),
+        }, shared_types: [])"#,
+    )
+    .unwrap();
+    let diagnostics = protocol.diagnostics();
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    assert!(diagnostics[0]
+        .message
+        .contains("only in some protocol versions (since 500)"));
+    assert!(diagnostics[1]
+        .message
+        .contains("only in some protocol versions (until 500)"));
+    assert!(protocol
+        .project(500.into())
+        .diagnostics()
+        .iter()
+        .all(|d| !d.is_error()));
+}
@@ -1,7 +1,6 @@
 //! Extension traits for `Bytes` and `BytesMut` which support Minecraft types.
 use bytes::buf::{Buf, BufExt, BufMut};
-use serde::de::DeserializeOwned;
-use serde::Serialize;
+use bytes::Bytes;
 use std::io::Read;
 use std::ops::Deref;
 use thiserror::Error;
@@ -15,9 +14,21 @@ pub enum Error {
     ValueTooLarge,
     #[error("Go home, you're drunk?")]
     Malformed,
+    #[error("`{field}` is out of range")]
+    OutOfRange { field: &'static str },
+    #[error("`{field}` is longer than {max_len}")]
+    TooLong { field: &'static str, max_len: usize },
+    #[error("`{field}` is {found} instead of the constant {expected}")]
+    UnexpectedConst {
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+    #[error("There is no packet {id:#04X} in this protocol version")]
+    UnknownPacket { id: u64 },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Nbt {
     pub blob: nbt::Blob,
 }
@@ -175,7 +186,8 @@ impl TryReadFrom for VarInt {
 
 impl WriteInto for VarInt {
     fn write(&self, buf: &mut impl BufMut) -> usize {
-        let mut x = **self;
+        // Shift as unsigned, otherwise negative numbers never reach zero.
+        let mut x = **self as u32;
         let mut bytes_written = 0;
         loop {
             let mut temp = (x & 0b0111_1111) as u8;
@@ -218,6 +230,27 @@ impl TryReadFrom for VarLong {
     }
 }
 
+impl WriteInto for VarLong {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        let mut x = **self as u64;
+        let mut bytes_written = 0;
+        loop {
+            let mut temp = (x & 0b0111_1111) as u8;
+            x >>= 7;
+            if x != 0 {
+                temp |= 0b1000_0000;
+            }
+            buf.put_u8(temp);
+            bytes_written += 1;
+            if x == 0 {
+                break;
+            }
+        }
+
+        bytes_written
+    }
+}
+
 impl TryReadFrom for String {
     fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
         let len: VarInt = buf.try_read()?;
@@ -243,14 +276,46 @@ impl TryReadFrom for String {
     }
 }
 
+impl WriteInto for String {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        let len = VarInt::from(self.len() as i32).write(buf);
+        buf.put_slice(self.as_bytes());
+        len + self.len()
+    }
+}
+
 impl TryReadFrom for Uuid {
     fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
+        if buf.remaining() < 16 {
+            return Err(Error::NotEnoughBytes);
+        }
         let mut bytes = [0u8; 16];
         buf.copy_to_slice(&mut bytes);
         Ok(Uuid::from_bytes(bytes))
     }
 }
 
+impl WriteInto for Uuid {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        buf.put_slice(self.as_bytes());
+        16
+    }
+}
+
+/// The rest of the buffer, such as the body of an enum variant with an unknown key.
+impl TryReadFrom for Bytes {
+    fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
+        Ok(buf.to_bytes())
+    }
+}
+
+impl WriteInto for Bytes {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        buf.put_slice(self);
+        self.len()
+    }
+}
+
 impl TryReadFrom for bool {
     fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
         let val: u8 = buf.try_read()?;
@@ -262,6 +327,13 @@ impl TryReadFrom for bool {
     }
 }
 
+impl WriteInto for bool {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        buf.put_u8(*self as u8);
+        1
+    }
+}
+
 impl TryReadFrom for Nbt {
     fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
         nbt::from_reader::<_, nbt::Blob>(buf.reader())
@@ -269,3 +341,500 @@ impl TryReadFrom for Nbt {
             .map_err(|_| Error::Malformed.into())
     }
 }
+
+impl WriteInto for Nbt {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        let mut bytes = Vec::new();
+        self.blob
+            .to_writer(&mut bytes)
+            .expect("NBT blob could not be serialized");
+        buf.put_slice(&bytes);
+        bytes.len()
+    }
+}
+
+/// A block position packed into 64 bits: 26 bits of x, 26 bits of z and 12 bits of y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl TryReadFrom for BlockPosition {
+    fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
+        let packed: i64 = buf.try_read()?;
+        Ok(BlockPosition {
+            x: (packed >> 38) as i32,
+            y: (packed << 52 >> 52) as i32,
+            z: (packed << 26 >> 38) as i32,
+        })
+    }
+}
+
+impl WriteInto for BlockPosition {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        let packed = ((i64::from(self.x) & 0x3FF_FFFF) << 38)
+            | ((i64::from(self.z) & 0x3FF_FFFF) << 12)
+            | (i64::from(self.y) & 0xFFF);
+        packed.write(buf)
+    }
+}
+
+/// The contents of a non-empty inventory slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    pub item_id: i32,
+    pub count: i8,
+    pub nbt: Option<Nbt>,
+}
+
+/// An inventory slot, which is written as a boolean followed by the item stack if it is set.
+impl TryReadFrom for Option<ItemStack> {
+    fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
+        let present: bool = buf.try_read()?;
+        if !present {
+            return Ok(None);
+        }
+        let item_id: VarInt = buf.try_read()?;
+        let count: i8 = buf.try_read()?;
+        // A missing tag is written as a lone TAG_End.
+        let nbt = if buf.remaining() > 0 && buf.bytes()[0] == 0 {
+            buf.advance(1);
+            None
+        } else {
+            Some(buf.try_read()?)
+        };
+        Ok(Some(ItemStack {
+            item_id: *item_id,
+            count,
+            nbt,
+        }))
+    }
+}
+
+impl WriteInto for Option<ItemStack> {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        match self {
+            Some(stack) => {
+                let mut len = true.write(buf);
+                len += VarInt::from(stack.item_id).write(buf);
+                len += stack.count.write(buf);
+                len += match &stack.nbt {
+                    Some(nbt) => nbt.write(buf),
+                    None => 0u8.write(buf),
+                };
+                len
+            }
+            None => false.write(buf),
+        }
+    }
+}
+
+/// The extra data of a particle, which depends on the particle's ID.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParticleData {
+    None,
+    Block(i32),
+    Dust {
+        red: f32,
+        green: f32,
+        blue: f32,
+        scale: f32,
+    },
+    FallingDust(i32),
+    Item(Option<ItemStack>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Particle {
+    pub id: i32,
+    pub data: ParticleData,
+}
+
+impl Particle {
+    // The IDs of the particles carrying data, as of 1.15.2.
+    const BLOCK: i32 = 3;
+    const DUST: i32 = 14;
+    const FALLING_DUST: i32 = 23;
+    const ITEM: i32 = 32;
+}
+
+impl TryReadFrom for Particle {
+    fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
+        let id: VarInt = buf.try_read()?;
+        let data = match *id {
+            Particle::BLOCK => ParticleData::Block(*TryReadInto::<VarInt>::try_read(buf)?),
+            Particle::DUST => ParticleData::Dust {
+                red: buf.try_read()?,
+                green: buf.try_read()?,
+                blue: buf.try_read()?,
+                scale: buf.try_read()?,
+            },
+            Particle::FALLING_DUST => {
+                ParticleData::FallingDust(*TryReadInto::<VarInt>::try_read(buf)?)
+            }
+            Particle::ITEM => ParticleData::Item(buf.try_read()?),
+            _ => ParticleData::None,
+        };
+        Ok(Particle { id: *id, data })
+    }
+}
+
+impl WriteInto for Particle {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        let len = VarInt::from(self.id).write(buf);
+        len + match &self.data {
+            ParticleData::None => 0,
+            ParticleData::Block(state) | ParticleData::FallingDust(state) => {
+                VarInt::from(*state).write(buf)
+            }
+            ParticleData::Dust {
+                red,
+                green,
+                blue,
+                scale,
+            } => red.write(buf) + green.write(buf) + blue.write(buf) + scale.write(buf),
+            ParticleData::Item(slot) => slot.write(buf),
+        }
+    }
+}
+
+/// A value of entity metadata. The type IDs are those of 1.15.2.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Byte(i8),
+    VarInt(i32),
+    Float(f32),
+    String(String),
+    Chat(String),
+    OptChat(Option<String>),
+    Slot(Option<ItemStack>),
+    Boolean(bool),
+    Rotation(f32, f32, f32),
+    Position(BlockPosition),
+    OptPosition(Option<BlockPosition>),
+    Direction(i32),
+    OptUuid(Option<Uuid>),
+    /// A block state ID, where 0 (air) means absent.
+    OptBlockId(Option<i32>),
+    Nbt(Nbt),
+    Particle(Particle),
+    VillagerData {
+        villager_type: i32,
+        profession: i32,
+        level: i32,
+    },
+    /// Written as the value plus one, where 0 means absent.
+    OptVarInt(Option<i32>),
+    Pose(i32),
+}
+
+impl MetadataValue {
+    pub fn type_id(&self) -> i32 {
+        match self {
+            MetadataValue::Byte(_) => 0,
+            MetadataValue::VarInt(_) => 1,
+            MetadataValue::Float(_) => 2,
+            MetadataValue::String(_) => 3,
+            MetadataValue::Chat(_) => 4,
+            MetadataValue::OptChat(_) => 5,
+            MetadataValue::Slot(_) => 6,
+            MetadataValue::Boolean(_) => 7,
+            MetadataValue::Rotation(..) => 8,
+            MetadataValue::Position(_) => 9,
+            MetadataValue::OptPosition(_) => 10,
+            MetadataValue::Direction(_) => 11,
+            MetadataValue::OptUuid(_) => 12,
+            MetadataValue::OptBlockId(_) => 13,
+            MetadataValue::Nbt(_) => 14,
+            MetadataValue::Particle(_) => 15,
+            MetadataValue::VillagerData { .. } => 16,
+            MetadataValue::OptVarInt(_) => 17,
+            MetadataValue::Pose(_) => 18,
+        }
+    }
+}
+
+fn try_read_var_int(buf: &mut impl Buf) -> Result<i32, Error> {
+    TryReadInto::<VarInt>::try_read(buf).map(|value| *value)
+}
+
+fn try_read_optional<T: TryReadFrom>(buf: &mut impl Buf) -> Result<Option<T>, Error> {
+    let present: bool = buf.try_read()?;
+    if present {
+        Ok(Some(buf.try_read()?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn write_optional<T: WriteInto>(value: &Option<T>, buf: &mut impl BufMut) -> usize {
+    match value {
+        Some(value) => true.write(buf) + value.write(buf),
+        None => false.write(buf),
+    }
+}
+
+impl MetadataValue {
+    fn try_read_of_type(type_id: i32, buf: &mut impl Buf) -> Result<Self, Error> {
+        Ok(match type_id {
+            0 => MetadataValue::Byte(buf.try_read()?),
+            1 => MetadataValue::VarInt(try_read_var_int(buf)?),
+            2 => MetadataValue::Float(buf.try_read()?),
+            3 => MetadataValue::String(buf.try_read()?),
+            4 => MetadataValue::Chat(buf.try_read()?),
+            5 => MetadataValue::OptChat(try_read_optional(buf)?),
+            6 => MetadataValue::Slot(buf.try_read()?),
+            7 => MetadataValue::Boolean(buf.try_read()?),
+            8 => MetadataValue::Rotation(buf.try_read()?, buf.try_read()?, buf.try_read()?),
+            9 => MetadataValue::Position(buf.try_read()?),
+            10 => MetadataValue::OptPosition(try_read_optional(buf)?),
+            11 => MetadataValue::Direction(try_read_var_int(buf)?),
+            12 => MetadataValue::OptUuid(try_read_optional(buf)?),
+            13 => MetadataValue::OptBlockId(match try_read_var_int(buf)? {
+                0 => None,
+                state => Some(state),
+            }),
+            14 => MetadataValue::Nbt(buf.try_read()?),
+            15 => MetadataValue::Particle(buf.try_read()?),
+            16 => MetadataValue::VillagerData {
+                villager_type: try_read_var_int(buf)?,
+                profession: try_read_var_int(buf)?,
+                level: try_read_var_int(buf)?,
+            },
+            17 => MetadataValue::OptVarInt(match try_read_var_int(buf)? {
+                0 => None,
+                value => Some(value - 1),
+            }),
+            18 => MetadataValue::Pose(try_read_var_int(buf)?),
+            // The size of a value of an unknown type is unknown, so nothing after it can be read.
+            _ => return Err(Error::Malformed),
+        })
+    }
+}
+
+impl WriteInto for MetadataValue {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        match self {
+            MetadataValue::Byte(value) => value.write(buf),
+            MetadataValue::VarInt(value)
+            | MetadataValue::Direction(value)
+            | MetadataValue::Pose(value) => VarInt::from(*value).write(buf),
+            MetadataValue::Float(value) => value.write(buf),
+            MetadataValue::String(value) | MetadataValue::Chat(value) => value.write(buf),
+            MetadataValue::OptChat(value) => write_optional(value, buf),
+            MetadataValue::Slot(value) => value.write(buf),
+            MetadataValue::Boolean(value) => value.write(buf),
+            MetadataValue::Rotation(x, y, z) => x.write(buf) + y.write(buf) + z.write(buf),
+            MetadataValue::Position(value) => value.write(buf),
+            MetadataValue::OptPosition(value) => write_optional(value, buf),
+            MetadataValue::OptUuid(value) => write_optional(value, buf),
+            MetadataValue::OptBlockId(value) => VarInt::from(value.unwrap_or(0)).write(buf),
+            MetadataValue::Nbt(value) => value.write(buf),
+            MetadataValue::Particle(value) => value.write(buf),
+            MetadataValue::VillagerData {
+                villager_type,
+                profession,
+                level,
+            } => {
+                VarInt::from(*villager_type).write(buf)
+                    + VarInt::from(*profession).write(buf)
+                    + VarInt::from(*level).write(buf)
+            }
+            MetadataValue::OptVarInt(value) => {
+                VarInt::from(value.map_or(0, |value| value + 1)).write(buf)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataEntry {
+    pub index: u8,
+    pub value: MetadataValue,
+}
+
+/// The metadata of an entity, written as `(index, type, value)` entries followed by an index of 0xFF.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EntityMetadata {
+    pub entries: Vec<MetadataEntry>,
+}
+
+impl EntityMetadata {
+    const END: u8 = 0xFF;
+}
+
+impl TryReadFrom for EntityMetadata {
+    fn try_read(buf: &mut impl Buf) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        loop {
+            let index: u8 = buf.try_read()?;
+            if index == EntityMetadata::END {
+                break;
+            }
+            let type_id = try_read_var_int(buf)?;
+            let value = MetadataValue::try_read_of_type(type_id, buf)?;
+            entries.push(MetadataEntry { index, value });
+        }
+        Ok(EntityMetadata { entries })
+    }
+}
+
+impl WriteInto for EntityMetadata {
+    fn write(&self, buf: &mut impl BufMut) -> usize {
+        let mut len = 0;
+        for entry in self.entries.iter() {
+            len += entry.index.write(buf);
+            len += VarInt::from(entry.value.type_id()).write(buf);
+            len += entry.value.write(buf);
+        }
+        len + EntityMetadata::END.write(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use std::fmt::Debug;
+
+    /// Writes `value`, checks that exactly the bytes written read back into it and returns them.
+    fn round_trip<T: TryReadFrom + WriteInto + PartialEq + Debug>(value: &T) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        let written = value.write(&mut buf);
+        assert_eq!(written, buf.len());
+        let bytes = buf.to_vec();
+
+        let mut buf = buf.freeze();
+        assert_eq!(&T::try_read(&mut buf).unwrap(), value);
+        assert!(buf.is_empty(), "{} bytes left over", buf.len());
+        bytes
+    }
+
+    #[test]
+    fn block_position_packs_x_z_and_y() {
+        let position = BlockPosition {
+            x: 18_357_644,
+            y: 831,
+            z: -20_882_616,
+        };
+        assert_eq!(
+            round_trip(&position),
+            0x4607_632C_15B4_833Fu64.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn block_position_keeps_negative_coordinates() {
+        let position = BlockPosition {
+            x: -1,
+            y: -1,
+            z: -1,
+        };
+        assert_eq!(round_trip(&position), [0xFF; 8]);
+
+        let position = BlockPosition {
+            x: -33_554_432,
+            y: -2048,
+            z: 33_554_431,
+        };
+        assert_eq!(
+            round_trip(&position),
+            [0x80, 0x00, 0x00, 0x1F, 0xFF, 0xFF, 0xF8, 0x00]
+        );
+    }
+
+    #[test]
+    fn empty_slot() {
+        assert_eq!(round_trip(&None::<ItemStack>), [0]);
+
+        let slot = Some(ItemStack {
+            item_id: 300,
+            count: 2,
+            nbt: None,
+        });
+        assert_eq!(round_trip(&slot), [1, 0xAC, 0x02, 2, 0]);
+    }
+
+    #[test]
+    fn particles_carry_data_by_id() {
+        let block = Particle {
+            id: Particle::BLOCK,
+            data: ParticleData::Block(300),
+        };
+        assert_eq!(round_trip(&block), [3, 0xAC, 0x02]);
+
+        let dust = Particle {
+            id: Particle::DUST,
+            data: ParticleData::Dust {
+                red: 1.0,
+                green: 0.5,
+                blue: 0.0,
+                scale: 2.0,
+            },
+        };
+        assert_eq!(
+            round_trip(&dust),
+            [14, 0x3F, 0x80, 0, 0, 0x3F, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0]
+        );
+
+        let falling_dust = Particle {
+            id: Particle::FALLING_DUST,
+            data: ParticleData::FallingDust(1),
+        };
+        assert_eq!(round_trip(&falling_dust), [23, 1]);
+
+        let item = Particle {
+            id: Particle::ITEM,
+            data: ParticleData::Item(Some(ItemStack {
+                item_id: 1,
+                count: 64,
+                nbt: None,
+            })),
+        };
+        assert_eq!(round_trip(&item), [32, 1, 1, 64, 0]);
+
+        let flame = Particle {
+            id: 26,
+            data: ParticleData::None,
+        };
+        assert_eq!(round_trip(&flame), [26]);
+    }
+
+    #[test]
+    fn metadata_ends_with_0xff() {
+        let metadata = EntityMetadata {
+            entries: vec![
+                MetadataEntry {
+                    index: 0,
+                    value: MetadataValue::Byte(-3),
+                },
+                MetadataEntry {
+                    index: 1,
+                    value: MetadataValue::VarInt(300),
+                },
+                MetadataEntry {
+                    index: 7,
+                    value: MetadataValue::OptVarInt(None),
+                },
+                MetadataEntry {
+                    index: 8,
+                    value: MetadataValue::OptBlockId(Some(9)),
+                },
+            ],
+        };
+        assert_eq!(
+            round_trip(&metadata),
+            [0, 0, 0xFD, 1, 1, 0xAC, 0x02, 7, 17, 0, 8, 13, 9, 0xFF]
+        );
+        assert_eq!(round_trip(&EntityMetadata::default()), [0xFF]);
+    }
+
+    #[test]
+    fn unknown_metadata_type_is_an_error() {
+        let mut buf = Bytes::from_static(&[0, 19, 0, 0xFF]);
+        let error = <EntityMetadata as TryReadFrom>::try_read(&mut buf).unwrap_err();
+        assert!(matches!(error, Error::Malformed), "{:?}", error);
+    }
+}
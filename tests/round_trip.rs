@@ -0,0 +1,598 @@
+//! Reads and writes packets with the code generated from the specs in this directory.
+use bytes::BytesMut;
+use feather_protocol::types::{Error, TryReadFrom, WriteInto};
+use std::fmt::Debug;
+
+// The generated code refers to the runtime as `crate::types` and `crate::packet`.
+mod types {
+    pub use feather_protocol::types::*;
+}
+mod packet {
+    pub use feather_protocol::packet::*;
+}
+
+/// Writes `packet`, checks that exactly the bytes written read back into it and returns them.
+fn round_trip<T>(packet: &T) -> Vec<u8>
+where
+    T: TryReadFrom + WriteInto + PartialEq + Debug,
+{
+    let mut buf = BytesMut::new();
+    let written = packet.write(&mut buf);
+    assert_eq!(written, buf.len());
+    let bytes = buf.to_vec();
+
+    let mut buf = buf.freeze();
+    assert_eq!(&T::try_read(&mut buf).unwrap(), packet);
+    assert!(buf.is_empty(), "{} bytes left over", buf.len());
+    bytes
+}
+
+fn read<T: TryReadFrom>(bytes: &'static [u8]) -> Result<T, Error> {
+    T::try_read(&mut bytes::Bytes::from_static(bytes))
+}
+
+mod conditional_fields {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol!("tests/conditional_fields.ron");
+    use protocol::client_bound::play::declare_command::*;
+    use protocol::client_bound::play::{ChunkData, DeclareCommand};
+
+    #[test]
+    fn present_when_the_condition_holds() {
+        let chunk_data = ChunkData {
+            biomes: Some(vec![1, -2]),
+            columns: 3,
+            rows: Some(4),
+            light: Some(15),
+        };
+        assert_eq!(
+            round_trip(&chunk_data),
+            [1, 0, 0, 0, 1, 0xFF, 0xFF, 0xFF, 0xFE, 3, 4, 0, 15]
+        );
+    }
+
+    #[test]
+    fn absent_otherwise() {
+        let chunk_data = ChunkData {
+            biomes: None,
+            columns: 0,
+            rows: None,
+            light: None,
+        };
+        assert_eq!(round_trip(&chunk_data), [0, 0, 1]);
+        assert!(read::<ChunkData>(&[1, 0, 0]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "conditional field does not match its condition")]
+    fn mismatched_conditions_are_not_written() {
+        let chunk_data = ChunkData {
+            biomes: None,
+            columns: 0,
+            rows: Some(4),
+            light: None,
+        };
+        chunk_data.write(&mut BytesMut::new());
+    }
+
+    fn node_flags(has_redirect: bool, node_type: u8) -> NodeFlags {
+        NodeFlags {
+            unused: 0,
+            has_redirect,
+            executable: true,
+            node_type,
+        }
+    }
+
+    #[test]
+    fn members_of_bit_fields_are_compared() {
+        let argument = DeclareCommand {
+            flags: node_flags(true, 2),
+            redirect_node: Some(3),
+            name: Some("n".into()),
+            parser: Some(Parser::IntegerArgument(IntegerArgument { flags: 0 })),
+        };
+        let mut expected = vec![0b1110, 3, 1, b'n', 17];
+        expected.extend_from_slice(b"brigadier:integer");
+        expected.push(0);
+        assert_eq!(round_trip(&argument), expected);
+
+        let literal = DeclareCommand {
+            flags: node_flags(false, 1),
+            redirect_node: None,
+            name: Some("n".into()),
+            parser: None,
+        };
+        assert_eq!(round_trip(&literal), [0b0101, 1, b'n']);
+
+        let root = DeclareCommand {
+            flags: node_flags(false, 0),
+            redirect_node: None,
+            name: None,
+            parser: None,
+        };
+        assert_eq!(round_trip(&root), [0b0100]);
+    }
+
+    #[test]
+    #[should_panic(expected = "conditional field does not match its condition")]
+    fn mismatched_bit_field_members_are_not_written() {
+        let root = DeclareCommand {
+            flags: node_flags(false, 0),
+            redirect_node: None,
+            name: None,
+            parser: Some(Parser::BoolArgument(BoolArgument)),
+        };
+        root.write(&mut BytesMut::new());
+    }
+}
+
+mod const_and_default_fields {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol!("tests/const_and_default_fields.ron");
+    use protocol::client_bound::play::{Effect, Particle};
+
+    #[test]
+    fn constants_are_written_and_checked() {
+        let effect = Effect::new(3);
+        assert_eq!(
+            effect,
+            Effect {
+                effect_id: 3,
+                disable_relative_volume: false,
+                sound: "hi".into(),
+            }
+        );
+        assert_eq!(
+            round_trip(&effect),
+            [0xFF, 0xFE, 0, 0, 0, 3, 0, 2, b'h', b'i', 3, b'a', b'b', b'c', 0x81, 0x01]
+        );
+
+        let error = read::<Effect>(&[
+            0xFF, 0xFE, 0, 0, 0, 3, 0, 2, b'h', b'i', 3, b'a', b'b', b'd', 0x81, 0x01,
+        ])
+        .unwrap_err();
+        match error {
+            Error::UnexpectedConst {
+                field,
+                expected,
+                found,
+            } => {
+                assert_eq!(field, "effect.tag");
+                assert_eq!(expected, r#""abc""#);
+                assert_eq!(found, r#""abd""#);
+            }
+            error => panic!("{:?}", error),
+        }
+        assert!(matches!(
+            read::<Effect>(&[
+                0xFF, 0xFD, 0, 0, 0, 3, 0, 2, b'h', b'i', 3, b'a', b'b', b'c', 0x81, 0x01
+            ]),
+            Err(Error::UnexpectedConst {
+                field: "effect.marker",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn defaults_fill_in_fields() {
+        let particle = Particle::default();
+        assert_eq!(
+            particle,
+            Particle {
+                count: 5,
+                long_distance: true,
+            }
+        );
+        assert_eq!(round_trip(&particle), [5, 1]);
+    }
+}
+
+mod field_constraints {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol!("tests/field_constraints.ron");
+    use protocol::server_bound::play::ClientSettings;
+
+    fn settings() -> ClientSettings {
+        ClientSettings {
+            locale: "en".into(),
+            view_distance: 10,
+            brands: vec!["ab".into()],
+            scale: Some(1.5),
+        }
+    }
+
+    /// Writes `settings` without checking it and reads it back.
+    fn reread(settings: &ClientSettings) -> Result<ClientSettings, Error> {
+        let mut buf = BytesMut::new();
+        settings.write(&mut buf);
+        ClientSettings::try_read(&mut buf.freeze())
+    }
+
+    #[test]
+    fn values_in_range_are_read() {
+        assert_eq!(
+            round_trip(&settings()),
+            [2, b'e', b'n', 10, 1, 2, b'a', b'b', 1, 0x3F, 0xC0, 0, 0]
+        );
+        assert!(reread(&ClientSettings {
+            scale: None,
+            ..settings()
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn numbers_out_of_range_are_errors() {
+        let error = reread(&ClientSettings {
+            view_distance: 33,
+            ..settings()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::OutOfRange {
+                field: "client_settings.view_distance"
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "`client_settings.view_distance` is out of range"
+        );
+
+        let error = reread(&ClientSettings {
+            scale: Some(-1.0),
+            ..settings()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::OutOfRange {
+                field: "client_settings.scale"
+            }
+        ));
+    }
+
+    #[test]
+    fn long_strings_and_arrays_are_errors() {
+        let error = reread(&ClientSettings {
+            locale: "en_US".into(),
+            ..settings()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::TooLong {
+                field: "client_settings.locale",
+                max_len: 4
+            }
+        ));
+
+        let error = reread(&ClientSettings {
+            brands: vec!["a".into(), "b".into(), "c".into()],
+            ..settings()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::TooLong {
+                field: "client_settings.brands",
+                max_len: 2
+            }
+        ));
+
+        let error = reread(&ClientSettings {
+            brands: vec!["abc".into()],
+            ..settings()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::TooLong {
+                field: "client_settings.brands",
+                max_len: 2
+            }
+        ));
+    }
+}
+
+mod array_length_fields {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol!("tests/array_length_fields.ron");
+    use protocol::client_bound::play::{ShortArrays, WindowItems};
+
+    #[test]
+    fn length_is_written_from_the_array() {
+        let window_items = WindowItems {
+            window_id: 1,
+            slot_data: vec![7, 8, 9],
+        };
+        assert_eq!(round_trip(&window_items), [1, 0, 3, 7, 8, 9]);
+        assert_eq!(
+            round_trip(&WindowItems {
+                window_id: 1,
+                slot_data: vec![]
+            }),
+            [1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn negative_lengths_are_malformed() {
+        assert!(read::<WindowItems>(&[1, 0xFF, 0xFF]).is_err());
+        assert!(read::<WindowItems>(&[1, 0, 2, 7]).is_err());
+    }
+
+    #[test]
+    fn lengths_up_to_the_largest_of_their_type_are_written() {
+        let short_arrays = ShortArrays {
+            bytes: vec![1; 127],
+            prefixed: vec![2; 255],
+        };
+        let bytes = round_trip(&short_arrays);
+        assert_eq!((bytes[0], bytes[128]), (127, 255));
+    }
+
+    #[test]
+    #[should_panic(expected = "array is too long for its length field")]
+    fn arrays_too_long_for_their_length_field_are_not_written() {
+        let short_arrays = ShortArrays {
+            bytes: vec![1; 300],
+            prefixed: vec![],
+        };
+        short_arrays.write(&mut BytesMut::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "array is too long for its length field")]
+    fn arrays_too_long_for_their_prefix_are_not_written() {
+        let short_arrays = ShortArrays {
+            bytes: vec![],
+            prefixed: vec![2; 256],
+        };
+        short_arrays.write(&mut BytesMut::new());
+    }
+}
+
+mod keyed_enum {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol!("tests/keyed_enum.ron");
+    use protocol::client_bound::play::teams::*;
+    use protocol::client_bound::play::Teams;
+
+    #[test]
+    fn discriminant_is_a_sibling_field() {
+        let teams = Teams {
+            team_name: "a".into(),
+            action: TeamsAction::RemoveTeam(RemoveTeam),
+        };
+        assert_eq!(round_trip(&teams), [1, b'a', 1]);
+
+        let teams = Teams {
+            team_name: "a".into(),
+            action: TeamsAction::RemovePlayers(RemovePlayers {
+                players: vec!["b".into()],
+            }),
+        };
+        assert_eq!(round_trip(&teams), [1, b'a', 4, 1, 1, b'b']);
+
+        let teams = read::<Teams>(&[1, b'a', 2]).unwrap();
+        assert_eq!(teams.action, TeamsAction::Other(2));
+        assert_eq!(round_trip(&teams), [1, b'a', 2]);
+    }
+}
+
+mod keyed_by_enclosing_struct {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol!("codegen/tests/player_info_packet.ron");
+    use protocol::client_bound::play::player_info::*;
+    use protocol::client_bound::play::PlayerInfo;
+    use uuid::Uuid;
+
+    #[test]
+    fn discriminant_is_a_field_of_the_packet() {
+        let player_info = PlayerInfo {
+            players: vec![Player {
+                uuid: Uuid::nil(),
+                action: Action::UpdateLatency(UpdateLatency { ping: 5 }),
+            }],
+        };
+        let mut expected = vec![2, 1];
+        expected.extend_from_slice(&[0; 16]);
+        expected.push(5);
+        assert_eq!(round_trip(&player_info), expected);
+    }
+
+    #[test]
+    fn every_element_shares_the_discriminant() {
+        let add_player = |name: &str, signature: Option<String>| Player {
+            uuid: Uuid::nil(),
+            action: Action::AddPlayer(AddPlayer {
+                name: name.into(),
+                properties: vec![Property {
+                    name: "textures".into(),
+                    value: "v".into(),
+                    signature,
+                }],
+                gamemode: 1,
+                ping: 2,
+                display_name: None,
+            }),
+        };
+        let player_info = PlayerInfo {
+            players: vec![add_player("a", Some("s".into())), add_player("b", None)],
+        };
+        let mut expected = vec![0, 2];
+        for (name, signature) in &[(b'a', &[1, 1, b's'][..]), (b'b', &[0][..])] {
+            expected.extend_from_slice(&[0; 16]);
+            expected.extend_from_slice(&[1, *name, 1]);
+            expected.extend_from_slice(b"\x08textures\x01v");
+            expected.extend_from_slice(signature);
+            expected.extend_from_slice(&[1, 2, 0]);
+        }
+        assert_eq!(round_trip(&player_info), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "elements of an array sharing a key have different keys")]
+    fn elements_with_different_discriminants_are_not_written() {
+        let player_info = PlayerInfo {
+            players: vec![
+                Player {
+                    uuid: Uuid::nil(),
+                    action: Action::UpdateLatency(UpdateLatency { ping: 5 }),
+                },
+                Player {
+                    uuid: Uuid::nil(),
+                    action: Action::RemovePlayer(RemovePlayer {}),
+                },
+            ],
+        };
+        player_info.write(&mut BytesMut::new());
+    }
+}
+
+mod terminated_array {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol!("tests/terminated_array.ron");
+    use protocol::client_bound::play::TabComplete;
+
+    #[test]
+    fn elements_are_followed_by_the_terminator() {
+        let tab_complete = TabComplete {
+            matches: vec![1, 2],
+            transaction_id: 9,
+        };
+        assert_eq!(
+            round_trip(&tab_complete),
+            [1, 2, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 9]
+        );
+        let tab_complete = TabComplete {
+            matches: vec![],
+            transaction_id: 9,
+        };
+        assert_eq!(round_trip(&tab_complete), [0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 9]);
+    }
+
+    #[test]
+    fn missing_terminator_is_an_error() {
+        assert!(read::<TabComplete>(&[1, 2]).is_err());
+    }
+}
+
+mod string_keyed_enum {
+    use super::*;
+    use bytes::Bytes;
+
+    feather_protocol_codegen_proc::protocol!("tests/string_keyed_enum.ron");
+    use protocol::client_bound::play::declare_recipes::*;
+    use protocol::client_bound::play::DeclareRecipes;
+
+    #[test]
+    fn known_keys() {
+        let declare_recipes = DeclareRecipes {
+            recipe_id: "a".into(),
+            data: Data::Smelting(Smelting { cooking_time: 200 }),
+        };
+        let mut expected = vec![18];
+        expected.extend_from_slice(b"minecraft:smelting");
+        expected.extend_from_slice(&[1, b'a', 0xC8, 0x01]);
+        assert_eq!(round_trip(&declare_recipes), expected);
+    }
+
+    #[test]
+    fn unknown_keys_keep_their_payload() {
+        let declare_recipes = DeclareRecipes {
+            recipe_id: "a".into(),
+            data: Data::Other("minecraft:stonecutting".into(), Bytes::from_static(&[1, 2])),
+        };
+        let bytes = round_trip(&declare_recipes);
+        assert_eq!(bytes[1..23], b"minecraft:stonecutting"[..]);
+        assert_eq!(bytes[23..], [1, b'a', 1, 2]);
+    }
+}
+
+mod versioned {
+    use super::*;
+
+    feather_protocol_codegen_proc::versioned_protocol!("tests/versioned.ron");
+    use protocol::client_bound::{login, play, status};
+
+    #[test]
+    fn status_and_login_packets_are_in_their_stages() {
+        let pong = status::Pong { payload: 7 };
+        let mut buf = BytesMut::new();
+        let written = status::Packet::Pong(pong.clone()).write_versioned(&mut buf, 578);
+        assert_eq!(written, 8);
+        let read = status::Packet::try_read_versioned(&mut buf.freeze(), 578, 1).unwrap();
+        assert!(matches!(read, status::Packet::Pong(ref read) if *read == pong));
+
+        let login_success = login::LoginSuccess {
+            uuid: "0".into(),
+            username: "Notch".into(),
+        };
+        let mut buf = BytesMut::new();
+        login::Packet::LoginSuccess(login_success.clone()).write_versioned(&mut buf, 578);
+        assert_eq!(&buf[..], b"\x010\x05Notch");
+        let read = login::Packet::try_read_versioned(&mut buf.freeze(), 578, 2).unwrap();
+        assert!(matches!(read, login::Packet::LoginSuccess(ref read) if *read == login_success));
+
+        assert!(matches!(
+            status::Packet::try_read_versioned(&mut bytes::Bytes::new(), 578, 2),
+            Err(Error::UnknownPacket { id: 2 })
+        ));
+    }
+
+    #[test]
+    fn ids_follow_versions() {
+        assert_eq!(play::SpawnEntity::id(498), Some(0));
+        assert_eq!(play::SpawnEntity::id(501), None);
+        assert_eq!(play::KeepAlive::id(498), Some(1));
+        assert_eq!(play::KeepAlive::id(578), Some(0));
+    }
+
+    #[test]
+    fn fields_follow_versions() {
+        let keep_alive = play::KeepAlive { id: 5, extra: None };
+        let mut buf = BytesMut::new();
+        play::Packet::KeepAlive(keep_alive.clone()).write_versioned(&mut buf, 498);
+        assert_eq!(&buf[..], [0, 0, 0, 0, 0, 0, 0, 5]);
+        let read = play::Packet::try_read_versioned(&mut buf.freeze(), 498, 1).unwrap();
+        assert!(matches!(read, play::Packet::KeepAlive(ref read) if *read == keep_alive));
+
+        let keep_alive = play::KeepAlive {
+            id: 5,
+            extra: Some(300),
+        };
+        let mut buf = BytesMut::new();
+        play::Packet::KeepAlive(keep_alive.clone()).write_versioned(&mut buf, 578);
+        assert_eq!(&buf[..], [0, 0, 0, 0, 0, 0, 0, 5, 0xAC, 0x02]);
+        let read = play::Packet::try_read_versioned(&mut buf.freeze(), 578, 0).unwrap();
+        assert!(matches!(read, play::Packet::KeepAlive(ref read) if *read == keep_alive));
+    }
+}
+
+mod per_version {
+    use super::*;
+
+    feather_protocol_codegen_proc::protocol_versions!("tests/versioned.ron", 498, 578);
+
+    #[test]
+    fn each_version_has_its_own_fields() {
+        assert_eq!(v498::VERSION, 498);
+        let keep_alive = v498::protocol::client_bound::play::KeepAlive { id: 5 };
+        assert_eq!(round_trip(&keep_alive), [0, 0, 0, 0, 0, 0, 0, 5]);
+
+        assert_eq!(v578::VERSION, 578);
+        let keep_alive = v578::protocol::client_bound::play::KeepAlive { id: 5, extra: 1 };
+        assert_eq!(round_trip(&keep_alive), [0, 0, 0, 0, 0, 0, 0, 5, 1]);
+    }
+}